@@ -1,70 +1,326 @@
 //! Defines the common functionality for any kind of program arithmetization to be used
 //! in the SuperNova protocol.
-//! Additionally, includes a model which defines this functionality for R1CS.
+//! Additionally, includes models which define this functionality for R1CS and Plonk, and a
+//! customizable constraint system which is folded with a sum-check based multi-folding scheme.
 
+pub mod ccs;
+pub mod either;
+pub mod plonk;
 pub mod r1cs;
 
-use ark_bls12_381::{Fq, G1Affine};
-use ark_crypto_primitives::sponge::poseidon::PoseidonConfig;
+use crate::{
+    circuit, cycle::Swapped, CommitmentScheme, CurveCycle, ProverError, PublicParams, RandomOracle,
+};
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ec::short_weierstrass::Affine;
+use ark_ff::{PrimeField, Zero};
+use ark_r1cs_std::fields::fp::FpVar;
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+use ark_serialize::{CanonicalSerialize, Compress, SerializationError};
+use ccs::MultiFoldingProof;
 
-/// A foldable circuit representation.
-pub trait Arithmetization {
-    type ConstraintSystem;
-    type Input;
+/// The public part of an instance-witness pair, which can be shared with a verifier without
+/// revealing the witness. Instances are committed on the primary curve of `G`, and are folded
+/// in-circuit by the circuit over the other field of the cycle.
+pub trait Instance<G: CurveCycle>: Clone {
+    /// Returns the commitments to the witness, which are folded linearly.
+    fn witness_commitments(&self) -> Vec<Affine<G::C1>>;
 
-    // Returns the latest IO hash.
-    fn hash(&self) -> Fq;
+    /// Returns the commitment to the error terms.
+    fn error_commitment(&self) -> Affine<G::C1>;
 
-    // Returns the current witness commitment.
-    fn witness_commitment(&self) -> G1Affine;
+    /// Returns the scalar which relaxes the instance.
+    fn u(&self) -> G::F1;
 
-    // Returns the crossterms for hashing.
-    fn crossterms(&self) -> Vec<Fq>;
+    /// Returns the challenge which the second round of the witness may depend on, such as for
+    /// lookups. Unfolded instances derive it from their first witness commitment, which the circuit
+    /// over the other field checks, and it's folded linearly.
+    fn challenge(&self) -> G::F1;
 
-    // Checks if the arithmetization is correct.
-    fn is_satisfied(&self, generators: &[G1Affine]) -> bool;
+    /// Returns the public IO, which consists of the hashes output by the augmented circuit.
+    fn io(&self) -> &[G::F1];
+
+    /// Ensures that the instance hasn't been folded yet.
+    fn has_crossterms(&self) -> bool;
+
+    /// Returns the point at which a linearized instance claims evaluations of its constraints,
+    /// alongside the claimed evaluations. Instances which are folded with crossterms claim none.
+    fn evaluations(&self) -> (&[G::F1], &[G::F1]) {
+        (&[], &[])
+    }
+
+    /// Returns a placeholder instance, which stands in for the instances of a circuit when there
+    /// are none yet. Its public IO holds the two hashes output by the augmented circuit.
+    fn dummy() -> Self;
+
+    /// Returns the terms used for hashing the instance in the circuit over the other field.
+    fn hash_terms(&self) -> Vec<G::F2> {
+        circuit::instance_terms::<G, Self>(self)
+    }
+}
+
+// Returns a hash of the shape of a circuit, the parameters of the random oracle and the digest of
+// the commitment key, which is used as the circuit parameters.
+pub(crate) fn shape_digest<F: PrimeField, S: CanonicalSerialize, R: RandomOracle<F>>(
+    shape: &S,
+    oracle: &R,
+    key: &[u8; 32],
+) -> Result<F, SerializationError> {
+    let mut bytes = vec![];
+    shape.serialize_compressed(&mut bytes)?;
+    oracle.serialize_with_mode(&mut bytes, Compress::Yes)?;
+    bytes.extend_from_slice(key);
+    Ok(oracle.hash(&bytes.to_sponge_field_elements_as_vec()))
+}
+
+/// A proof that an instance was folded into a running instance, which the circuit over the other
+/// field of the cycle verifies.
+#[derive(Clone, Debug)]
+pub enum FoldingProof<G: CurveCycle> {
+    /// The commitment to the crossterms, which are folded into the error terms.
+    Crossterms(Affine<G::C1>),
+    /// A multi-folding proof, alongside the multisets and coefficients of the shape, which the
+    /// circuit combines the evaluations of the folded instance with.
+    MultiFolding {
+        proof: MultiFoldingProof<G::F1>,
+        multisets: Vec<Vec<usize>>,
+        coefficients: Vec<G::F1>,
+    },
+}
+
+/// The commitment key of the arithmetization `A` over the curve cycle `G`.
+pub type Key<G, A> =
+    <<A as Arithmetization<G>>::Scheme as CommitmentScheme<<G as CurveCycle>::C1>>::Key;
+
+/// A step circuit of a program over the field `F`, which is invoked whenever the program counter
+/// selects it. Circuits can hold their own state, such as non-deterministic advice for the step
+/// they're invoked at.
+pub trait StepCircuit<F: PrimeField> {
+    /// Returns the number of inputs of the circuit, which equals its number of outputs.
+    fn arity(&self) -> usize;
+
+    /// Synthesizes the circuit over the given input, and returns the next program counter
+    /// alongside the output. Circuits should only allocate witnesses, as the public IO of the
+    /// constraint system is reserved for the augmented circuit.
+    #[allow(clippy::type_complexity)]
+    fn synthesize(
+        &self,
+        cs: ConstraintSystemRef<F>,
+        z: &[FpVar<F>],
+    ) -> Result<(FpVar<F>, Vec<FpVar<F>>), SynthesisError>;
+}
+
+/// A foldable circuit representation over the curve cycle `G`, which is committed to with the
+/// scheme `Scheme`. Every invocation of a circuit shares the same `Shape`.
+pub trait Arithmetization<G: CurveCycle>: Clone {
+    type Instance: Instance<G>;
+    type Scheme: CommitmentScheme<G::C1>;
+    type Shape: Clone;
+
+    // Returns the public part of the instance-witness pair.
+    fn instance(&self) -> Self::Instance;
+
+    // Checks if the arithmetization is correct, and if its commitments match its witness.
+    fn is_satisfied(&self, key: &Key<G, Self>) -> bool;
+
+    // Checks if the commitments of the instance are commitments to the witness and error vector.
+    fn commitments_match(&self, key: &Key<G, Self>) -> bool;
 
     // Returns the circuit metadata used for hashing.
-    fn params(&self) -> Fq;
+    fn params(&self) -> G::F1;
 
     // Returns the circuit output.
-    fn output(&self) -> &[Fq];
+    fn output(&self) -> &[G::F1];
+
+    // Returns the program counter computed by the step circuit, which selects the next circuit to
+    // be invoked.
+    fn pc(&self) -> usize;
 
     // Ensures that the arithmetization hasn't been folded yet.
     fn has_crossterms(&self) -> bool;
 
-    // Returns a set of base case inputs. Should in all cases just return as many one scalars as
-    // there are inputs.
-    fn z0(&self) -> Vec<Fq>;
+    // Returns the circuit parameters of the shape of this pair, which match `digest`.
+    fn shape_params<R: RandomOracle<G::F1>>(
+        &self,
+        oracle: &R,
+        key: &Key<G, Self>,
+    ) -> Result<G::F1, SerializationError>;
+
+    // Checks if the pair has the given shape, which is cheaper than comparing the parameters of
+    // its shape against a digest.
+    fn has_shape(&self, shape: &Self::Shape) -> bool;
+
+    // Sets the circuit metadata used for hashing, once the shape of the pair has been checked.
+    fn set_params(&mut self, params: G::F1);
+
+    /// Returns a new instance-witness pair with the given step circuit, which is invoked whenever
+    /// the program counter equals `pc`. The pair is the base case of the circuit, which serves as
+    /// its initial running instance. Fails if the circuit doesn't match the one the public
+    /// parameters were built with at `pc`.
+    fn new<S, R1, R2, C>(
+        params: &PublicParams<G, Self, S, R1, R2>,
+        pc: usize,
+        z0: Vec<G::F1>,
+        c: &C,
+    ) -> Result<Self, ProverError>
+    where
+        Self: Sized,
+        S: CommitmentScheme<G::C2>,
+        R1: RandomOracle<G::F1>,
+        R2: RandomOracle<G::F2>,
+        C: StepCircuit<G::F1> + ?Sized,
+    {
+        let param = params.circuit_digest(pc)?;
+
+        // The secondary instances are placeholders, as they are discarded in the base case.
+        let secondary = r1cs::R1CSInstance::<Swapped<G>>::dummy();
+        let mut pair = Self::synthesize(
+            G::F1::zero(),
+            &z0,
+            &z0,
+            pc,
+            0,
+            &secondary,
+            &secondary,
+            &FoldingProof::Crossterms(Affine::identity()),
+            params.oracle(),
+            params.secondary_oracle(),
+            params.key(),
+            &params.shapes()[pc],
+            c,
+        )?;
+        if pair.shape_params(params.oracle(), params.key())? != param {
+            return Err(ProverError::ShapeMismatch(pc));
+        }
 
-    // Returns the terms used for calculating the IO hash.
-    fn hash_terms(&self) -> Vec<Fq>;
+        pair.set_params(param);
+        Ok(pair)
+    }
 
-    // Synthesizes a new invocation of the augmented step circuit, which folds the two current
-    // instance-witness pairs in-circuit and returns a new instance-witness pair representing the
-    // invocation.
+    // Synthesizes a new invocation of the augmented step circuit, which checks that the latest
+    // secondary instance hashes the given state, folds it into the running secondary instance
+    // in-circuit with the given proof, and returns a new instance-witness pair representing the
+    // invocation. The step circuit returns the next program counter alongside its output, and `pc`
+    // is the program counter of the circuit being invoked, whose shape is expected to be `shape`.
+    // Fails if the step circuit fails or doesn't match the arity of the state, if the augmented
+    // circuit is unsatisfied or doesn't have the expected shape, or if the commitment key is too
+    // small for it. The random oracle of the circuit over the other field derives the challenge of
+    // the pair.
     #[allow(clippy::too_many_arguments)]
-    fn synthesize<C: Fn(Self::ConstraintSystem, &[Self::Input]) -> Vec<Self::Input>>(
-        &mut self,
-        params: Fq,
-        prev_terms: Vec<Fq>,
-        latest_witness: G1Affine,
-        latest_hash: Fq,
-        old_pc: usize,
-        new_pc: usize,
+    fn synthesize<
+        I: Instance<Swapped<G>>,
+        C: StepCircuit<G::F1> + ?Sized,
+        R1: RandomOracle<G::F1>,
+        R2: RandomOracle<G::F2>,
+    >(
+        params: G::F1,
+        z0: &[G::F1],
+        z: &[G::F1],
+        pc: usize,
         i: usize,
-        constants: &PoseidonConfig<Fq>,
-        generators: &[G1Affine],
-        circuit: C,
-    ) -> Self;
-
-    // Performs the folding of the two instance-witness pairs natively. Should only be called after
-    // [`synthesize`].
-    fn fold(
+        running: &I,
+        latest: &I,
+        proof: &FoldingProof<Swapped<G>>,
+        oracle: &R1,
+        secondary_oracle: &R2,
+        key: &Key<G, Self>,
+        shape: &Self::Shape,
+        circuit: &C,
+    ) -> Result<Self, ProverError>
+    where
+        Self: Sized;
+
+    // Returns the shape of the augmented step circuit, which is invoked whenever the program
+    // counter equals `pc`. Fails like `synthesize` does on the base case, which starts from the
+    // initial input `z0`.
+    fn shape<C: StepCircuit<G::F1> + ?Sized, R: RandomOracle<G::F1>>(
+        pc: usize,
+        z0: &[G::F1],
+        oracle: &R,
+        circuit: &C,
+    ) -> Result<Self::Shape, ProverError>;
+
+    // Returns the number of bases the commitment key needs for a circuit of the given shape.
+    fn key_size(shape: &Self::Shape) -> usize;
+
+    // Returns the circuit parameters of the given shape, which bind it to the random oracle and the
+    // commitment key.
+    fn digest<R: RandomOracle<G::F1>>(
+        shape: &Self::Shape,
+        oracle: &R,
+        key: &Key<G, Self>,
+    ) -> Result<G::F1, SerializationError>;
+
+    // Returns the crossterms of folding another, unfolded instance-witness pair into this one,
+    // alongside their commitment. Fails if the key is too small to commit to them.
+    #[allow(clippy::type_complexity)]
+    fn commit_t(
+        &self,
+        other: &Self,
+        key: &Key<G, Self>,
+    ) -> Result<(Vec<G::F1>, Affine<G::C1>), ProverError>;
+
+    // Folds another, unfolded instance-witness pair into this one natively with the challenge
+    // `r`, given the crossterms and their commitment. The pair is left as is on error.
+    fn fold_with(
+        &mut self,
+        other: &Self,
+        t: Vec<G::F1>,
+        comm_T: &Affine<G::C1>,
+        r: G::F1,
+    ) -> Result<(), ProverError>;
+
+    // Folds another, unfolded instance-witness pair into this one natively, with the challenge
+    // derived in the circuit over the other field. Returns the proof which that circuit needs to
+    // verify the folding, which is the commitment to the crossterms unless overridden, or an error
+    // if the key is too small to commit to them. The pair is left as is on error.
+    fn fold<R: RandomOracle<G::F2>>(
         &mut self,
         other: &Self,
-        constants: &PoseidonConfig<Fq>,
-        generators: &[G1Affine],
-        params: Fq,
-    );
+        oracle: &R,
+        key: &Key<G, Self>,
+        params: G::F2,
+    ) -> Result<FoldingProof<G>, ProverError>
+    where
+        Self: Sized,
+    {
+        let (t, comm_T) = self.commit_t(other, key)?;
+        let r = circuit::challenge::<G, _, R>(
+            oracle,
+            params,
+            &self.instance(),
+            &other.instance(),
+            &comm_T,
+        );
+        self.fold_with(other, t, &comm_T, r)?;
+        Ok(FoldingProof::Crossterms(comm_T))
+    }
+
+    // Returns placeholders for the running instances of circuits of the given shapes, and for an
+    // instance folded into them alongside its proof, which the secondary circuit is synthesized
+    // over to obtain its shape.
+    #[allow(clippy::type_complexity)]
+    fn placeholders(
+        shapes: &[Self::Shape],
+    ) -> (Vec<Self::Instance>, Self::Instance, FoldingProof<G>) {
+        (
+            vec![Self::Instance::dummy(); shapes.len()],
+            Self::Instance::dummy(),
+            FoldingProof::Crossterms(Affine::identity()),
+        )
+    }
+
+    // Returns an instance alongside a proof of folding it into this base case, which the base case
+    // of the secondary circuit folds with the given parameters in place of an actual instance. The
+    // folded instance is discarded, so the crossterms are placeholders unless overridden.
+    fn base_folding<R: RandomOracle<G::F2>>(
+        &self,
+        _oracle: &R,
+        _key: &Key<G, Self>,
+        _params: G::F2,
+    ) -> Result<(Self::Instance, FoldingProof<G>), ProverError> {
+        let instance = self.instance();
+        let comm = instance.witness_commitments()[0];
+        Ok((instance, FoldingProof::Crossterms(comm)))
+    }
 }
@@ -0,0 +1,1013 @@
+//! A collection of logic and structures for folding committed customizable constraint systems
+//! (CCS), following the multi-folding scheme of HyperNova.
+//!
+//! A CCS shape consists of matrices `M_j`, multisets `S_i` over the matrix indices and constants
+//! `c_i`, and is satisfied by a vector `z` if `sum_i c_i * prod_{j in S_i} M_j z = 0`, where the
+//! products are taken entry-wise. This captures R1CS, Plonkish and AIR constraints of any degree
+//! in a single representation, so that high-degree constraints don't need to be reduced to
+//! quadratic ones before they can be folded.
+//!
+//! Rather than folding two instances of the same relation, a fresh committed instance is folded
+//! into a running linearized instance, which claims the evaluations `v_j` of the multilinear
+//! extensions of `M_j z` at a point `r_x`. A sum-check reduces the claims of both instances to
+//! claims about a single new point, after which they are combined with a random linear
+//! combination. Like for the other arithmetizations, `z` starts with the scalar `u`, which is one
+//! for fresh instances, followed by the public inputs and the witness.
+//!
+//! CCS implements [`Arithmetization`] for step circuits synthesized as R1CS. The challenges of the
+//! multi-folding are derived over the other field of the cycle, so that the circuit over that
+//! field verifies the sum-check in place of folding crossterms. The base case of every circuit is
+//! linearized to serve as its running instance, and the running instances of a program are all
+//! linearized at points of as many variables as its largest circuit needs, so that the circuit
+//! folds into any of them alike. Lookups aren't supported, and CCS can't be mixed with the other
+//! arithmetizations through [`Either`](crate::either::Either), as those fold with crossterms.
+//!
+//! [`Arithmetization`]: crate::Arithmetization
+
+use crate::{
+    circuit::{synthesize_primary, witness_challenge, FoldingTranscript},
+    compression::{
+        polynomial::{eq_eval, eq_evals, evaluate},
+        SumcheckProof, SumcheckTranscript,
+    },
+    cycle::Swapped,
+    lookup::Lookups,
+    plonk::PlonkShape,
+    r1cs::R1CSInstance,
+    shape_digest, Arithmetization, CommitmentScheme, CurveCycle, FoldingProof, Instance, Pedersen,
+    ProverError, PublicParams, RandomOracle, StepCircuit, VerificationError,
+};
+use ark_ec::short_weierstrass::Affine;
+use ark_ff::{One, PrimeField, Zero};
+use ark_relations::r1cs::{
+    ConstraintMatrices, ConstraintSystemRef, Matrix, OptimizationGoal, SynthesisError,
+};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use core::marker::PhantomData;
+use rayon::prelude::*;
+
+/// The shape of a customizable constraint system. Every matrix is stored sparsely row by row, and
+/// indexes into `z`.
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize)]
+pub struct CCSShape<F: PrimeField> {
+    pub num_constraints: usize,
+    pub num_public_inputs: usize,
+    pub num_witness_variables: usize,
+    pub matrices: Vec<Matrix<F>>,
+    pub multisets: Vec<Vec<usize>>,
+    pub coefficients: Vec<F>,
+}
+
+impl<F: PrimeField> CCSShape<F> {
+    /// Converts an R1CS shape, which is expressed as `Az * Bz - Cz = 0`.
+    pub fn from_r1cs(matrices: &ConstraintMatrices<F>) -> Self {
+        Self {
+            num_constraints: matrices.num_constraints,
+            num_public_inputs: matrices.num_instance_variables - 1,
+            num_witness_variables: matrices.num_witness_variables,
+            matrices: vec![matrices.a.clone(), matrices.b.clone(), matrices.c.clone()],
+            multisets: vec![vec![0, 1], vec![2]],
+            coefficients: vec![F::one(), -F::one()],
+        }
+    }
+
+    /// Converts a Plonk shape and its wire assignment, and returns the shape alongside the
+    /// witness. Every cycle of the copy constraints becomes a single witness variable, and the
+    /// gates are expressed as `(q_L a + q_R b + q_O c + q_C - PI) + (q_M a) * b = 0`.
+    pub fn from_plonk(shape: &PlonkShape<F>, wires: &[Vec<F>; 3]) -> (Self, Vec<F>) {
+        let n = shape.gates.len();
+        let offset = 1 + shape.num_public_inputs;
+
+        // Walk every cycle of sigma once, and assign each wire position to its variable.
+        let mut vars = vec![usize::MAX; 3 * n];
+        let mut witness = vec![];
+        for start in 0..3 * n {
+            if vars[start] != usize::MAX {
+                continue;
+            }
+
+            let mut pos = start;
+            while vars[pos] == usize::MAX {
+                vars[pos] = offset + witness.len();
+                pos = shape.sigma[pos];
+            }
+            witness.push(wires[start / n][start % n]);
+        }
+
+        let mut linear = Vec::with_capacity(n);
+        let mut left = Vec::with_capacity(n);
+        let mut right = Vec::with_capacity(n);
+        for (i, gate) in shape.gates.iter().enumerate() {
+            let (a, b, c) = (vars[i], vars[n + i], vars[2 * n + i]);
+            let mut row = [(gate.q_L, a), (gate.q_R, b), (gate.q_O, c), (gate.q_C, 0)]
+                .into_iter()
+                .filter(|(coeff, _)| !coeff.is_zero())
+                .collect::<Vec<(F, usize)>>();
+            if i < shape.num_public_inputs {
+                row.push((-F::one(), 1 + i));
+            }
+
+            linear.push(row);
+            left.push(if gate.q_M.is_zero() {
+                vec![]
+            } else {
+                vec![(gate.q_M, a)]
+            });
+            right.push(vec![(F::one(), b)]);
+        }
+
+        (
+            Self {
+                num_constraints: n,
+                num_public_inputs: shape.num_public_inputs,
+                num_witness_variables: witness.len(),
+                matrices: vec![linear, left, right],
+                multisets: vec![vec![0], vec![1, 2]],
+                coefficients: vec![F::one(), F::one()],
+            },
+            witness,
+        )
+    }
+
+    /// Returns the highest degree of any term in the constraints.
+    pub fn degree(&self) -> usize {
+        self.multisets.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    /// Checks whether `z` satisfies every constraint.
+    pub fn is_satisfied(&self, z: &[F]) -> bool {
+        if z.len() != 1 + self.num_public_inputs + self.num_witness_variables {
+            return false;
+        }
+
+        let products = self.products(z, self.num_rounds());
+        (0..self.num_constraints).into_par_iter().all(|row| {
+            self.combine(&products.iter().map(|p| p[row]).collect::<Vec<F>>())
+                .is_zero()
+        })
+    }
+
+    // Returns the number of variables of the multilinear extensions of the constraint rows.
+    fn num_rounds(&self) -> usize {
+        self.num_constraints.next_power_of_two().trailing_zeros() as usize
+    }
+
+    // Returns the degree of the sum-check of a multi-folding, which multiplies the constraints by
+    // an eq polynomial.
+    fn sumcheck_degree(&self) -> usize {
+        self.degree().max(1) + 1
+    }
+
+    // Computes every `M_j z`, padded to `2^num_rounds` entries.
+    fn products(&self, z: &[F], num_rounds: usize) -> Vec<Vec<F>> {
+        self.matrices
+            .par_iter()
+            .map(|m| {
+                let mut v = m
+                    .iter()
+                    .map(|row| row.iter().map(|(coeff, var)| *coeff * z[*var]).sum())
+                    .collect::<Vec<F>>();
+                v.resize(1 << num_rounds, F::zero());
+                v
+            })
+            .collect()
+    }
+
+    // Evaluates `sum_i c_i * prod_{j in S_i} v_j` for the given evaluations of every `M_j z`.
+    fn combine(&self, v: &[F]) -> F {
+        self.multisets
+            .iter()
+            .zip(&self.coefficients)
+            .map(|(set, c)| set.iter().fold(*c, |acc, j| acc * v[*j]))
+            .sum()
+    }
+}
+
+/// The public part of a committed CCS instance-witness pair. Fresh instances have `u = 1` and
+/// claim no evaluations, while linearized instances claim the evaluations `v` at the point `r_x`.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct CCSInstance<G: CurveCycle> {
+    pub(crate) comm_witness: Affine<G::C1>,
+    pub(crate) u: G::F1,
+    pub(crate) challenge: G::F1,
+    pub(crate) io: Vec<G::F1>,
+    pub(crate) r_x: Vec<G::F1>,
+    pub(crate) v: Vec<G::F1>,
+}
+
+impl<G: CurveCycle> Instance<G> for CCSInstance<G> {
+    fn witness_commitments(&self) -> Vec<Affine<G::C1>> {
+        vec![self.comm_witness]
+    }
+
+    // There are no error terms, as the claimed evaluations take their place.
+    fn error_commitment(&self) -> Affine<G::C1> {
+        Affine::<G::C1>::identity()
+    }
+
+    fn u(&self) -> G::F1 {
+        self.u
+    }
+
+    fn challenge(&self) -> G::F1 {
+        self.challenge
+    }
+
+    fn io(&self) -> &[G::F1] {
+        &self.io
+    }
+
+    fn has_crossterms(&self) -> bool {
+        self.u != G::F1::one()
+    }
+
+    fn dummy() -> Self {
+        Self {
+            comm_witness: Affine::<G::C1>::identity(),
+            u: G::F1::one(),
+            challenge: G::F1::zero(),
+            io: vec![G::F1::zero(); 2],
+            r_x: vec![],
+            v: vec![],
+        }
+    }
+
+    fn evaluations(&self) -> (&[G::F1], &[G::F1]) {
+        (&self.r_x, &self.v)
+    }
+}
+
+impl<G: CurveCycle> CCSInstance<G> {
+    /// Computes the instance which results from folding the fresh instance `other` into this
+    /// linearized one with the given parameters, after checking the multi-folding proof. The
+    /// challenges are derived with the random oracle of the circuit over the other field, which
+    /// verifies the folding the same way.
+    pub fn fold<R: RandomOracle<G::F2>>(
+        &self,
+        shape: &CCSShape<G::F1>,
+        other: &Self,
+        proof: &MultiFoldingProof<G::F1>,
+        oracle: &R,
+        params: G::F2,
+    ) -> Result<Self, VerificationError<G::F1>> {
+        let t = shape.matrices.len();
+        let num_rounds = self.r_x.len();
+        if num_rounds < shape.num_rounds()
+            || self.v.len() != t
+            || self.io.len() != shape.num_public_inputs
+            || other.io.len() != shape.num_public_inputs
+            || other.u != G::F1::one()
+            || !other.r_x.is_empty()
+            || proof.sigmas.len() != t
+            || proof.thetas.len() != t
+        {
+            return Err(VerificationError::InvalidSumcheck);
+        }
+
+        let mut transcript = FoldingTranscript::new(oracle, params, self, other);
+        let (gammas, beta) = challenges(&mut transcript, t, num_rounds);
+
+        let claim = gammas[..t]
+            .iter()
+            .zip(&self.v)
+            .map(|(gamma, v)| *gamma * v)
+            .sum();
+        let (claim, r_x) = proof
+            .sumcheck
+            .verify(claim, num_rounds, shape.sumcheck_degree(), &mut transcript)
+            .ok_or(VerificationError::InvalidSumcheck)?;
+
+        let e_running = eq_eval(&self.r_x, &r_x);
+        let e_latest = eq_eval(&beta, &r_x);
+        let expected = gammas[..t]
+            .iter()
+            .zip(&proof.sigmas)
+            .map(|(gamma, sigma)| *gamma * e_running * sigma)
+            .sum::<G::F1>()
+            + gammas[t] * e_latest * shape.combine(&proof.thetas);
+        if claim != expected {
+            return Err(VerificationError::InvalidSumcheck);
+        }
+
+        transcript.absorb_scalars(&proof.sigmas);
+        transcript.absorb_scalars(&proof.thetas);
+        let rho = transcript.challenge();
+
+        Ok(Self {
+            comm_witness: (other.comm_witness * rho + self.comm_witness).into(),
+            u: self.u + rho,
+            challenge: self.challenge + rho * other.challenge,
+            io: self
+                .io
+                .iter()
+                .zip(&other.io)
+                .map(|(a, b)| *a + rho * b)
+                .collect(),
+            r_x,
+            v: proof
+                .sigmas
+                .iter()
+                .zip(&proof.thetas)
+                .map(|(sigma, theta)| *sigma + rho * theta)
+                .collect(),
+        })
+    }
+}
+
+/// A committed CCS instance-witness pair, whose witness is committed to with the scheme `P`. A
+/// fresh pair can be linearized, after which it claims that the multilinear extension of every
+/// `M_j z` evaluates to `v_j` at the point `r_x`, and fresh pairs can be folded into it.
+#[derive(Clone)]
+pub struct CCS<G: CurveCycle, P: CommitmentScheme<G::C1> = Pedersen> {
+    pub(crate) shape: CCSShape<G::F1>,
+    pub(crate) param: G::F1,
+    pub(crate) comm_witness: Affine<G::C1>,
+    pub(crate) witness: Vec<G::F1>,
+    pub(crate) u: G::F1,
+    pub(crate) challenge: G::F1,
+    pub(crate) io: Vec<G::F1>,
+    pub(crate) r_x: Vec<G::F1>,
+    pub(crate) v: Vec<G::F1>,
+    pub(crate) output: Vec<G::F1>,
+    pub(crate) pc: usize,
+    pub(crate) scheme: PhantomData<P>,
+}
+
+impl<G: CurveCycle, P: CommitmentScheme<G::C1>> CCS<G, P> {
+    /// Creates a fresh instance-witness pair by committing to the witness. The pair isn't bound to
+    /// a circuit of a program, so its challenge is zero. Fails if the public inputs or the witness
+    /// don't match the shape, or if the key is too small to commit to the witness.
+    pub fn from_witness(
+        shape: CCSShape<G::F1>,
+        io: Vec<G::F1>,
+        witness: Vec<G::F1>,
+        key: &P::Key,
+    ) -> Result<Self, ProverError> {
+        if io.len() != shape.num_public_inputs {
+            return Err(ProverError::AssignmentMismatch(
+                shape.num_public_inputs,
+                io.len(),
+            ));
+        }
+        if witness.len() != shape.num_witness_variables {
+            return Err(ProverError::AssignmentMismatch(
+                shape.num_witness_variables,
+                witness.len(),
+            ));
+        }
+
+        Ok(Self {
+            comm_witness: P::commit(key, &witness)?,
+            shape,
+            param: G::F1::zero(),
+            witness,
+            u: G::F1::one(),
+            challenge: G::F1::zero(),
+            io,
+            r_x: vec![],
+            v: vec![],
+            output: vec![],
+            pc: 0,
+            scheme: PhantomData,
+        })
+    }
+
+    /// Linearizes a fresh pair to start folding from, at a point of `num_rounds` variables. The
+    /// point is derived from the instance with the random oracle of the circuit over the other
+    /// field, so that a verifier can derive it the same way. The amount of variables should be at
+    /// least the amount the constraint rows need.
+    pub fn linearize<R: RandomOracle<G::F2>>(&self, oracle: &R, num_rounds: usize) -> Self {
+        let r_x = linearization_point(&self.instance(), oracle, num_rounds);
+        let v = self
+            .shape
+            .products(&self.z(), num_rounds)
+            .iter()
+            .map(|p| evaluate(p, &r_x))
+            .collect();
+
+        Self {
+            r_x,
+            v,
+            ..self.clone()
+        }
+    }
+
+    /// Folds a fresh pair of the same shape into this linearized one with the given parameters,
+    /// and returns the proof which lets a verifier fold the instances on its own.
+    pub fn multi_fold<R: RandomOracle<G::F2>>(
+        &mut self,
+        other: &Self,
+        oracle: &R,
+        params: G::F2,
+    ) -> MultiFoldingProof<G::F1> {
+        let shape = &self.shape;
+        let t = shape.matrices.len();
+        let num_rounds = self.r_x.len();
+        let mut transcript =
+            FoldingTranscript::new(oracle, params, &self.instance(), &other.instance());
+        let (gammas, beta) = challenges(&mut transcript, t, num_rounds);
+
+        // Run a sum-check over
+        // g(x) = sum_j gamma^j * eq(r_x, x) * M_j z_1(x)
+        //      + gamma^t * eq(beta, x) * sum_i c_i * prod_{j in S_i} M_j z_2(x),
+        // whose sum is a combination of the claimed evaluations of the running pair, as the
+        // second term sums to zero if the fresh pair is satisfied.
+        let mut polys = vec![eq_evals(&self.r_x), eq_evals(&beta)];
+        polys.extend(shape.products(&self.z(), num_rounds));
+        polys.extend(shape.products(&other.z(), num_rounds));
+        let (sumcheck, r_x, evals) = SumcheckProof::prove(
+            polys,
+            shape.sumcheck_degree(),
+            |v| {
+                gammas[..t]
+                    .iter()
+                    .zip(&v[2..2 + t])
+                    .map(|(gamma, p)| *gamma * v[0] * p)
+                    .sum::<G::F1>()
+                    + gammas[t] * v[1] * shape.combine(&v[2 + t..])
+            },
+            &mut transcript,
+        );
+
+        let sigmas = evals[2..2 + t].to_vec();
+        let thetas = evals[2 + t..].to_vec();
+        transcript.absorb_scalars(&sigmas);
+        transcript.absorb_scalars(&thetas);
+        let rho = transcript.challenge();
+
+        self.comm_witness = P::add(&self.comm_witness, &P::scale(&other.comm_witness, rho));
+        self.witness
+            .par_iter_mut()
+            .zip(&other.witness)
+            .for_each(|(a, b)| *a += rho * b);
+        self.u += rho;
+        self.challenge += rho * other.challenge;
+        self.io
+            .iter_mut()
+            .zip(&other.io)
+            .for_each(|(a, b)| *a += rho * b);
+        self.r_x = r_x;
+        self.v = sigmas
+            .iter()
+            .zip(&thetas)
+            .map(|(sigma, theta)| *sigma + rho * theta)
+            .collect();
+
+        MultiFoldingProof {
+            sumcheck,
+            sigmas,
+            thetas,
+        }
+    }
+
+    // Returns a new fresh instance-witness pair from a synthesized constraint system. The
+    // challenge is derived with the random oracle of the circuit over the other field, which
+    // checks it when folding the pair.
+    fn from_cs<R: RandomOracle<G::F2>>(
+        cs: ConstraintSystemRef<G::F1>,
+        pc: usize,
+        output: Vec<G::F1>,
+        oracle: &R,
+        key: &P::Key,
+    ) -> Result<Self, ProverError> {
+        let shape = Self::compile(&cs)?;
+        let cs = cs.borrow().ok_or(SynthesisError::MissingCS)?;
+        let witness = cs.witness_assignment.clone();
+        let comm_witness = P::commit(key, &witness)?;
+
+        Ok(Self {
+            shape,
+            param: G::F1::zero(),
+            comm_witness,
+            witness,
+            u: G::F1::one(),
+            challenge: witness_challenge::<G, R>(oracle, &comm_witness),
+            io: cs.instance_assignment[1..].to_vec(),
+            r_x: vec![],
+            v: vec![],
+            output,
+            pc,
+            scheme: PhantomData,
+        })
+    }
+
+    // Converts the constraints of a synthesized constraint system into a CCS shape. Fails if the
+    // constraint system looks up any values.
+    fn compile(cs: &ConstraintSystemRef<G::F1>) -> Result<CCSShape<G::F1>, ProverError> {
+        if !Lookups::from_cs(cs)?.is_empty() {
+            return Err(ProverError::UnsupportedLookup);
+        }
+
+        let matrices = cs.to_matrices().ok_or(SynthesisError::MissingCS)?;
+        Ok(CCSShape::from_r1cs(&matrices))
+    }
+
+    fn z(&self) -> Vec<G::F1> {
+        [self.u]
+            .into_iter()
+            .chain(self.io.iter().copied())
+            .chain(self.witness.iter().copied())
+            .collect()
+    }
+}
+
+impl<G: CurveCycle, P: CommitmentScheme<G::C1>> Arithmetization<G> for CCS<G, P> {
+    type Instance = CCSInstance<G>;
+    type Scheme = P;
+    type Shape = CCSShape<G::F1>;
+
+    fn instance(&self) -> CCSInstance<G> {
+        CCSInstance {
+            comm_witness: self.comm_witness,
+            u: self.u,
+            challenge: self.challenge,
+            io: self.io.clone(),
+            r_x: self.r_x.clone(),
+            v: self.v.clone(),
+        }
+    }
+
+    // Fresh pairs are checked against the constraints, while linearized pairs are checked
+    // against their claimed evaluations.
+    fn is_satisfied(&self, key: &P::Key) -> bool {
+        if !self.commitments_match(key) {
+            return false;
+        }
+
+        if self.r_x.is_empty() {
+            return self.u == G::F1::one() && self.shape.is_satisfied(&self.z());
+        }
+
+        if self.r_x.len() < self.shape.num_rounds() || self.v.len() != self.shape.matrices.len() {
+            return false;
+        }
+
+        self.shape
+            .products(&self.z(), self.r_x.len())
+            .iter()
+            .zip(&self.v)
+            .all(|(p, v)| evaluate(p, &self.r_x) == *v)
+    }
+
+    fn commitments_match(&self, key: &P::Key) -> bool {
+        P::commit(key, &self.witness).is_ok_and(|comm| comm == self.comm_witness)
+    }
+
+    fn output(&self) -> &[G::F1] {
+        &self.output
+    }
+
+    fn params(&self) -> G::F1 {
+        self.param
+    }
+
+    fn pc(&self) -> usize {
+        self.pc
+    }
+
+    fn has_crossterms(&self) -> bool {
+        self.u != G::F1::one()
+    }
+
+    fn shape_params<R: RandomOracle<G::F1>>(
+        &self,
+        oracle: &R,
+        key: &P::Key,
+    ) -> Result<G::F1, SerializationError> {
+        Self::digest(&self.shape, oracle, key)
+    }
+
+    fn has_shape(&self, shape: &Self::Shape) -> bool {
+        self.shape == *shape
+    }
+
+    fn set_params(&mut self, params: G::F1) {
+        self.param = params;
+    }
+
+    // The base case is linearized, as it serves as the initial running pair of its circuit. The
+    // running pairs of a program are all linearized at points of as many variables as its
+    // largest circuit needs, so that the secondary circuit folds into any of them alike.
+    fn new<S, R1, R2, C>(
+        params: &PublicParams<G, Self, S, R1, R2>,
+        pc: usize,
+        z0: Vec<G::F1>,
+        c: &C,
+    ) -> Result<Self, ProverError>
+    where
+        Self: Sized,
+        S: CommitmentScheme<G::C2>,
+        R1: RandomOracle<G::F1>,
+        R2: RandomOracle<G::F2>,
+        C: StepCircuit<G::F1> + ?Sized,
+    {
+        let param = params.circuit_digest(pc)?;
+
+        // The secondary instances are placeholders, as they are discarded in the base case.
+        let secondary = R1CSInstance::<Swapped<G>>::dummy();
+        let mut pair = Self::synthesize(
+            G::F1::zero(),
+            &z0,
+            &z0,
+            pc,
+            0,
+            &secondary,
+            &secondary,
+            &FoldingProof::Crossterms(Affine::identity()),
+            params.oracle(),
+            params.secondary_oracle(),
+            params.key(),
+            &params.shapes()[pc],
+            c,
+        )?;
+        if pair.shape_params(params.oracle(), params.key())? != param {
+            return Err(ProverError::ShapeMismatch(pc));
+        }
+
+        pair.set_params(param);
+        Ok(pair.linearize(params.secondary_oracle(), num_rounds(params.shapes())))
+    }
+
+    fn synthesize<
+        I: Instance<Swapped<G>>,
+        C: StepCircuit<G::F1> + ?Sized,
+        R1: RandomOracle<G::F1>,
+        R2: RandomOracle<G::F2>,
+    >(
+        params: G::F1,
+        z0: &[G::F1],
+        z: &[G::F1],
+        pc: usize,
+        i: usize,
+        running: &I,
+        latest: &I,
+        proof: &FoldingProof<Swapped<G>>,
+        oracle: &R1,
+        secondary_oracle: &R2,
+        key: &P::Key,
+        shape: &Self::Shape,
+        circuit: &C,
+    ) -> Result<Self, ProverError> {
+        let (cs, next_pc, output) = synthesize_primary::<Swapped<G>, I, C, R1>(
+            params,
+            z0,
+            z,
+            pc,
+            i,
+            running,
+            latest,
+            proof,
+            oracle,
+            OptimizationGoal::Constraints,
+            circuit,
+        )?;
+
+        let pair = Self::from_cs(cs, next_pc, output, secondary_oracle, key)?;
+        if pair.shape != *shape {
+            return Err(ProverError::ShapeMismatch(pc));
+        }
+
+        Ok(pair)
+    }
+
+    fn shape<C: StepCircuit<G::F1> + ?Sized, R: RandomOracle<G::F1>>(
+        pc: usize,
+        z0: &[G::F1],
+        oracle: &R,
+        circuit: &C,
+    ) -> Result<Self::Shape, ProverError> {
+        let secondary = R1CSInstance::<Swapped<G>>::dummy();
+        let (cs, _, _) = synthesize_primary::<Swapped<G>, _, C, R>(
+            G::F1::zero(),
+            z0,
+            z0,
+            pc,
+            0,
+            &secondary,
+            &secondary,
+            &FoldingProof::Crossterms(Affine::identity()),
+            oracle,
+            OptimizationGoal::Constraints,
+            circuit,
+        )?;
+        Self::compile(&cs)
+    }
+
+    fn key_size(shape: &Self::Shape) -> usize {
+        shape.num_witness_variables
+    }
+
+    fn digest<R: RandomOracle<G::F1>>(
+        shape: &Self::Shape,
+        oracle: &R,
+        key: &P::Key,
+    ) -> Result<G::F1, SerializationError> {
+        shape_digest(shape, oracle, &P::digest(key))
+    }
+
+    // CCS is folded with a multi-folding proof rather than with crossterms, so it can't be folded
+    // through `Either` with an arithmetization which is.
+    fn commit_t(
+        &self,
+        _other: &Self,
+        _key: &P::Key,
+    ) -> Result<(Vec<G::F1>, Affine<G::C1>), ProverError> {
+        Err(ProverError::ArithmetizationMismatch)
+    }
+
+    fn fold_with(
+        &mut self,
+        _other: &Self,
+        _t: Vec<G::F1>,
+        _comm_T: &Affine<G::C1>,
+        _r: G::F1,
+    ) -> Result<(), ProverError> {
+        Err(ProverError::ArithmetizationMismatch)
+    }
+
+    fn fold<R: RandomOracle<G::F2>>(
+        &mut self,
+        other: &Self,
+        oracle: &R,
+        _key: &P::Key,
+        params: G::F2,
+    ) -> Result<FoldingProof<G>, ProverError> {
+        let proof = self.multi_fold(other, oracle, params);
+        Ok(FoldingProof::MultiFolding {
+            proof,
+            multisets: self.shape.multisets.clone(),
+            coefficients: self.shape.coefficients.clone(),
+        })
+    }
+
+    // The running instances are linearized, and the proof has a round for every variable of
+    // their point. Every circuit is converted from R1CS, so they all share the multisets and
+    // coefficients of the first one.
+    fn placeholders(
+        shapes: &[Self::Shape],
+    ) -> (Vec<CCSInstance<G>>, CCSInstance<G>, FoldingProof<G>) {
+        let num_rounds = num_rounds(shapes);
+        let running = shapes
+            .iter()
+            .map(|shape| CCSInstance {
+                r_x: vec![G::F1::zero(); num_rounds],
+                v: vec![G::F1::zero(); shape.matrices.len()],
+                ..CCSInstance::dummy()
+            })
+            .collect();
+
+        let shape = &shapes[0];
+        let t = shape.matrices.len();
+        let proof = MultiFoldingProof {
+            sumcheck: SumcheckProof {
+                rounds: vec![vec![G::F1::zero(); shape.sumcheck_degree() + 1]; num_rounds],
+            },
+            sigmas: vec![G::F1::zero(); t],
+            thetas: vec![G::F1::zero(); t],
+        };
+        (
+            running,
+            CCSInstance::dummy(),
+            FoldingProof::MultiFolding {
+                proof,
+                multisets: shape.multisets.clone(),
+                coefficients: shape.coefficients.clone(),
+            },
+        )
+    }
+
+    // The secondary circuit checks multi-folding proofs even in its base case, so the base case is
+    // folded into a copy of itself, which it satisfies as a fresh pair.
+    fn base_folding<R: RandomOracle<G::F2>>(
+        &self,
+        oracle: &R,
+        key: &P::Key,
+        params: G::F2,
+    ) -> Result<(CCSInstance<G>, FoldingProof<G>), ProverError> {
+        let fresh = Self {
+            r_x: vec![],
+            v: vec![],
+            ..self.clone()
+        };
+        let proof = self.clone().fold(&fresh, oracle, key, params)?;
+        Ok((fresh.instance(), proof))
+    }
+}
+
+/// A proof that a fresh instance was folded into a running linearized instance, consisting of a
+/// sum-check proof and the evaluations of every `M_j z` of both instances at the final point.
+#[derive(Clone, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct MultiFoldingProof<F: PrimeField> {
+    pub(crate) sumcheck: SumcheckProof<F>,
+    pub(crate) sigmas: Vec<F>,
+    pub(crate) thetas: Vec<F>,
+}
+
+// Returns the amount of variables of the points which the running instances of a program are
+// linearized at, which fits its largest circuit.
+fn num_rounds<F: PrimeField>(shapes: &[CCSShape<F>]) -> usize {
+    shapes.iter().map(CCSShape::num_rounds).max().unwrap_or(0)
+}
+
+// Derives the point at which a fresh instance is linearized from the instance itself.
+fn linearization_point<G: CurveCycle, R: RandomOracle<G::F2>>(
+    instance: &CCSInstance<G>,
+    oracle: &R,
+    num_rounds: usize,
+) -> Vec<G::F1> {
+    let mut transcript = FoldingTranscript::<G, R>::with_terms(oracle, instance.hash_terms());
+    (0..num_rounds).map(|_| transcript.challenge()).collect()
+}
+
+// Derives the powers of gamma which batch the claims of both instances, and the point beta at
+// which the constraints of the fresh instance are checked.
+fn challenges<F: PrimeField, T: SumcheckTranscript<F>>(
+    transcript: &mut T,
+    t: usize,
+    num_rounds: usize,
+) -> (Vec<F>, Vec<F>) {
+    let gamma = transcript.challenge();
+    let gammas = (0..=t)
+        .scan(F::one(), |power, _| {
+            let current = *power;
+            *power *= gamma;
+            Some(current)
+        })
+        .collect();
+    let beta = (0..num_rounds).map(|_| transcript.challenge()).collect();
+    (gammas, beta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cycle::pasta::{Fp, Fq, PallasVesta},
+        poseidon_constants,
+    };
+    use ark_r1cs_std::{
+        alloc::AllocVar,
+        eq::EqGadget,
+        fields::{fp::FpVar, FieldVar},
+    };
+    use ark_relations::r1cs::ConstraintSystem;
+
+    // Synthesizes `x^3 + x + 5 = y` with a public `y`, and returns the matrices alongside the
+    // full assignment, which starts with the constant one.
+    fn cubic_r1cs(x: u64) -> (ConstraintMatrices<Fq>, Vec<Fq>) {
+        let x = Fq::from(x);
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let y = FpVar::new_input(cs.clone(), || Ok(x * x * x + x + Fq::from(5u64))).unwrap();
+        let x = FpVar::new_witness(cs.clone(), || Ok(x)).unwrap();
+        let x_cu = x.square().unwrap() * &x;
+        (x_cu + &x + Fq::from(5u64)).enforce_equal(&y).unwrap();
+        cs.finalize();
+
+        let matrices = cs.to_matrices().unwrap();
+        let cs = cs.borrow().unwrap();
+        let assignment = [
+            cs.instance_assignment.clone(),
+            cs.witness_assignment.clone(),
+        ]
+        .concat();
+        (matrices, assignment)
+    }
+
+    // Expresses `x^3 + x + 5 - y = 0` as a single constraint of degree three over `z = (1, y, x)`.
+    fn cubic_ccs(x: u64) -> (CCSShape<Fq>, Vec<Fq>) {
+        let shape = CCSShape {
+            num_constraints: 1,
+            num_public_inputs: 1,
+            num_witness_variables: 1,
+            matrices: vec![
+                vec![vec![(Fq::one(), 2)]],
+                vec![vec![(Fq::one(), 1)]],
+                vec![vec![(Fq::from(5u64), 0)]],
+            ],
+            multisets: vec![vec![0, 0, 0], vec![0], vec![2], vec![1]],
+            coefficients: vec![Fq::one(), Fq::one(), Fq::one(), -Fq::one()],
+        };
+        let x = Fq::from(x);
+        (shape, vec![Fq::one(), x * x * x + x + Fq::from(5u64), x])
+    }
+
+    #[test]
+    fn test_conversions() {
+        let (matrices, z) = cubic_r1cs(3);
+        let shape = CCSShape::from_r1cs(&matrices);
+        assert!(shape.is_satisfied(&z));
+        let mut tampered = z.clone();
+        tampered[2] += Fq::one();
+        assert!(!shape.is_satisfied(&tampered));
+
+        let (plonk, wires) = PlonkShape::compile(&matrices, &z);
+        let (shape, witness) = CCSShape::from_plonk(&plonk, &wires);
+        assert_eq!(shape.degree(), 2);
+        let z = [&z[..2], &witness].concat();
+        assert!(shape.is_satisfied(&z));
+        let mut tampered = z.clone();
+        tampered[1] += Fq::one();
+        assert!(!shape.is_satisfied(&tampered));
+
+        let (shape, z) = cubic_ccs(3);
+        assert_eq!(shape.degree(), 3);
+        assert!(shape.is_satisfied(&z));
+    }
+
+    fn multi_folding(assign: impl Fn(u64) -> (CCSShape<Fq>, Vec<Fq>)) {
+        let oracle = poseidon_constants::<Fp>();
+        let key = Pedersen::setup(b"supernova", 8).unwrap();
+        let params = Fp::from(7u64);
+        let pair = |x| {
+            let (shape, z) = assign(x);
+            let num_io = 1 + shape.num_public_inputs;
+            CCS::<PallasVesta>::from_witness(
+                shape,
+                z[1..num_io].to_vec(),
+                z[num_io..].to_vec(),
+                &key,
+            )
+            .unwrap()
+        };
+
+        let base = pair(1);
+        assert!(base.is_satisfied(&key));
+
+        // The pair is linearized at a point that the verifier derives from its instance, which
+        // may have more variables than the constraint rows need.
+        let num_rounds = base.shape.num_rounds() + 1;
+        let mut running = base.linearize(&oracle, num_rounds);
+        assert!(running.is_satisfied(&key));
+        assert_eq!(
+            running.r_x,
+            linearization_point(&base.instance(), &oracle, num_rounds)
+        );
+
+        for x in 2..5 {
+            let latest = pair(x);
+            let instance = running.instance();
+            let proof = running.multi_fold(&latest, &oracle, params);
+            assert!(running.is_satisfied(&key));
+
+            // The verifier should arrive at the same instance without the witnesses.
+            let folded = instance
+                .fold(&base.shape, &latest.instance(), &proof, &oracle, params)
+                .unwrap();
+            let expected = running.instance();
+            assert_eq!(folded.comm_witness, expected.comm_witness);
+            assert_eq!(folded.u, expected.u);
+            assert_eq!(folded.io, expected.io);
+            assert_eq!(folded.r_x, expected.r_x);
+            assert_eq!(folded.v, expected.v);
+
+            // Changing the claimed evaluations or the parameters should invalidate the proof.
+            let mut tampered = proof.clone();
+            tampered.thetas[0] += Fq::one();
+            assert!(matches!(
+                instance.fold(&base.shape, &latest.instance(), &tampered, &oracle, params),
+                Err(VerificationError::InvalidSumcheck)
+            ));
+            assert!(matches!(
+                instance.fold(&base.shape, &latest.instance(), &proof, &oracle, Fp::zero()),
+                Err(VerificationError::InvalidSumcheck)
+            ));
+        }
+
+        // Folding an unsatisfied pair should be rejected by the verifier.
+        let mut latest = pair(5);
+        latest.io[0] += Fq::one();
+        let instance = running.instance();
+        let proof = running.multi_fold(&latest, &oracle, params);
+        assert!(matches!(
+            instance.fold(&base.shape, &latest.instance(), &proof, &oracle, params),
+            Err(VerificationError::InvalidSumcheck)
+        ));
+    }
+
+    #[test]
+    fn test_assignment_mismatch() {
+        let key = Pedersen::setup(b"supernova", 8).unwrap();
+        let (shape, z) = cubic_ccs(3);
+        assert!(matches!(
+            CCS::<PallasVesta>::from_witness(shape.clone(), z[1..].to_vec(), vec![], &key),
+            Err(ProverError::AssignmentMismatch(1, 2))
+        ));
+        assert!(matches!(
+            CCS::<PallasVesta>::from_witness(shape, z[1..2].to_vec(), vec![], &key),
+            Err(ProverError::AssignmentMismatch(1, 0))
+        ));
+    }
+
+    #[test]
+    fn test_multi_folding_r1cs() {
+        multi_folding(|x| {
+            let (matrices, z) = cubic_r1cs(x);
+            (CCSShape::from_r1cs(&matrices), z)
+        });
+    }
+
+    #[test]
+    fn test_multi_folding_high_degree() {
+        multi_folding(cubic_ccs);
+    }
+}
@@ -0,0 +1,342 @@
+//! An arithmetization which dispatches between two others, so that the circuits of a single
+//! program can be arithmetized differently, such as some as R1CS and others as Plonk. Both
+//! arithmetizations commit with the same scheme. Nesting allows for more than two of them.
+
+use crate::{
+    cycle::Swapped, Arithmetization, CurveCycle, FoldingProof, Instance, Key, ProverError,
+    RandomOracle, StepCircuit,
+};
+use ark_ec::short_weierstrass::Affine;
+use ark_ff::PrimeField;
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, Read, SerializationError, Valid, Validate,
+    Write,
+};
+
+/// Either of two arithmetizations, or either of their instances or shapes. Circuits added to the
+/// public parameters are arithmetized with `A`, unless they're added with
+/// [`PublicParamsBuilder::right_circuit`](crate::PublicParamsBuilder::right_circuit).
+#[derive(Clone)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+// Binds circuit parameters to the side of the arithmetization, so that the shapes of both sides
+// can't be confused.
+fn tag<F: PrimeField, R: RandomOracle<F>>(oracle: &R, side: u64, params: F) -> F {
+    oracle.hash(&[F::from(side), params])
+}
+
+impl<G: CurveCycle, A: Instance<G>, B: Instance<G>> Instance<G> for Either<A, B> {
+    // The secondary circuit folds instances of both sides alike, so the witness commitments are
+    // padded to the same amount with the point at infinity, which folds into itself.
+    fn witness_commitments(&self) -> Vec<Affine<G::C1>> {
+        let width = A::dummy()
+            .witness_commitments()
+            .len()
+            .max(B::dummy().witness_commitments().len());
+        let mut commitments = match self {
+            Either::Left(a) => a.witness_commitments(),
+            Either::Right(b) => b.witness_commitments(),
+        };
+        commitments.resize(width, Affine::identity());
+        commitments
+    }
+
+    fn error_commitment(&self) -> Affine<G::C1> {
+        match self {
+            Either::Left(a) => a.error_commitment(),
+            Either::Right(b) => b.error_commitment(),
+        }
+    }
+
+    fn u(&self) -> G::F1 {
+        match self {
+            Either::Left(a) => a.u(),
+            Either::Right(b) => b.u(),
+        }
+    }
+
+    fn challenge(&self) -> G::F1 {
+        match self {
+            Either::Left(a) => a.challenge(),
+            Either::Right(b) => b.challenge(),
+        }
+    }
+
+    fn io(&self) -> &[G::F1] {
+        match self {
+            Either::Left(a) => a.io(),
+            Either::Right(b) => b.io(),
+        }
+    }
+
+    fn has_crossterms(&self) -> bool {
+        match self {
+            Either::Left(a) => a.has_crossterms(),
+            Either::Right(b) => b.has_crossterms(),
+        }
+    }
+
+    fn dummy() -> Self {
+        Either::Left(A::dummy())
+    }
+
+    fn evaluations(&self) -> (&[G::F1], &[G::F1]) {
+        match self {
+            Either::Left(a) => a.evaluations(),
+            Either::Right(b) => b.evaluations(),
+        }
+    }
+}
+
+impl<G, A, B> Arithmetization<G> for Either<A, B>
+where
+    G: CurveCycle,
+    A: Arithmetization<G>,
+    B: Arithmetization<G, Scheme = A::Scheme>,
+{
+    type Instance = Either<A::Instance, B::Instance>;
+    type Scheme = A::Scheme;
+    type Shape = Either<A::Shape, B::Shape>;
+
+    fn instance(&self) -> Self::Instance {
+        match self {
+            Either::Left(a) => Either::Left(a.instance()),
+            Either::Right(b) => Either::Right(b.instance()),
+        }
+    }
+
+    fn is_satisfied(&self, key: &Key<G, Self>) -> bool {
+        match self {
+            Either::Left(a) => a.is_satisfied(key),
+            Either::Right(b) => b.is_satisfied(key),
+        }
+    }
+
+    fn commitments_match(&self, key: &Key<G, Self>) -> bool {
+        match self {
+            Either::Left(a) => a.commitments_match(key),
+            Either::Right(b) => b.commitments_match(key),
+        }
+    }
+
+    fn params(&self) -> G::F1 {
+        match self {
+            Either::Left(a) => a.params(),
+            Either::Right(b) => b.params(),
+        }
+    }
+
+    fn output(&self) -> &[G::F1] {
+        match self {
+            Either::Left(a) => a.output(),
+            Either::Right(b) => b.output(),
+        }
+    }
+
+    fn pc(&self) -> usize {
+        match self {
+            Either::Left(a) => a.pc(),
+            Either::Right(b) => b.pc(),
+        }
+    }
+
+    fn has_crossterms(&self) -> bool {
+        match self {
+            Either::Left(a) => a.has_crossterms(),
+            Either::Right(b) => b.has_crossterms(),
+        }
+    }
+
+    fn shape_params<R: RandomOracle<G::F1>>(
+        &self,
+        oracle: &R,
+        key: &Key<G, Self>,
+    ) -> Result<G::F1, SerializationError> {
+        Ok(match self {
+            Either::Left(a) => tag(oracle, 0, a.shape_params(oracle, key)?),
+            Either::Right(b) => tag(oracle, 1, b.shape_params(oracle, key)?),
+        })
+    }
+
+    fn has_shape(&self, shape: &Self::Shape) -> bool {
+        match (self, shape) {
+            (Either::Left(a), Either::Left(shape)) => a.has_shape(shape),
+            (Either::Right(b), Either::Right(shape)) => b.has_shape(shape),
+            _ => false,
+        }
+    }
+
+    fn set_params(&mut self, params: G::F1) {
+        match self {
+            Either::Left(a) => a.set_params(params),
+            Either::Right(b) => b.set_params(params),
+        }
+    }
+
+    // The shape selects the side which synthesizes the circuit.
+    fn synthesize<
+        I: Instance<Swapped<G>>,
+        C: StepCircuit<G::F1> + ?Sized,
+        R1: RandomOracle<G::F1>,
+        R2: RandomOracle<G::F2>,
+    >(
+        params: G::F1,
+        z0: &[G::F1],
+        z: &[G::F1],
+        pc: usize,
+        i: usize,
+        running: &I,
+        latest: &I,
+        proof: &FoldingProof<Swapped<G>>,
+        oracle: &R1,
+        secondary_oracle: &R2,
+        key: &Key<G, Self>,
+        shape: &Self::Shape,
+        circuit: &C,
+    ) -> Result<Self, ProverError> {
+        Ok(match shape {
+            Either::Left(shape) => Either::Left(A::synthesize(
+                params,
+                z0,
+                z,
+                pc,
+                i,
+                running,
+                latest,
+                proof,
+                oracle,
+                secondary_oracle,
+                key,
+                shape,
+                circuit,
+            )?),
+            Either::Right(shape) => Either::Right(B::synthesize(
+                params,
+                z0,
+                z,
+                pc,
+                i,
+                running,
+                latest,
+                proof,
+                oracle,
+                secondary_oracle,
+                key,
+                shape,
+                circuit,
+            )?),
+        })
+    }
+
+    fn shape<C: StepCircuit<G::F1> + ?Sized, R: RandomOracle<G::F1>>(
+        pc: usize,
+        z0: &[G::F1],
+        oracle: &R,
+        circuit: &C,
+    ) -> Result<Self::Shape, ProverError> {
+        Ok(Either::Left(A::shape(pc, z0, oracle, circuit)?))
+    }
+
+    fn key_size(shape: &Self::Shape) -> usize {
+        match shape {
+            Either::Left(shape) => A::key_size(shape),
+            Either::Right(shape) => B::key_size(shape),
+        }
+    }
+
+    fn digest<R: RandomOracle<G::F1>>(
+        shape: &Self::Shape,
+        oracle: &R,
+        key: &Key<G, Self>,
+    ) -> Result<G::F1, SerializationError> {
+        Ok(match shape {
+            Either::Left(shape) => tag(oracle, 0, A::digest(shape, oracle, key)?),
+            Either::Right(shape) => tag(oracle, 1, B::digest(shape, oracle, key)?),
+        })
+    }
+
+    fn commit_t(
+        &self,
+        other: &Self,
+        key: &Key<G, Self>,
+    ) -> Result<(Vec<G::F1>, Affine<G::C1>), ProverError> {
+        match (self, other) {
+            (Either::Left(a), Either::Left(other)) => a.commit_t(other, key),
+            (Either::Right(b), Either::Right(other)) => b.commit_t(other, key),
+            _ => Err(ProverError::ArithmetizationMismatch),
+        }
+    }
+
+    // The challenge is derived from the padded instances by the default `fold`, which matches the
+    // challenge derived in-circuit.
+    fn fold_with(
+        &mut self,
+        other: &Self,
+        t: Vec<G::F1>,
+        comm_T: &Affine<G::C1>,
+        r: G::F1,
+    ) -> Result<(), ProverError> {
+        match (self, other) {
+            (Either::Left(a), Either::Left(other)) => a.fold_with(other, t, comm_T, r),
+            (Either::Right(b), Either::Right(other)) => b.fold_with(other, t, comm_T, r),
+            _ => Err(ProverError::ArithmetizationMismatch),
+        }
+    }
+}
+
+impl<A: CanonicalSerialize, B: CanonicalSerialize> CanonicalSerialize for Either<A, B> {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        match self {
+            Either::Left(a) => {
+                0u8.serialize_with_mode(&mut writer, compress)?;
+                a.serialize_with_mode(&mut writer, compress)
+            }
+            Either::Right(b) => {
+                1u8.serialize_with_mode(&mut writer, compress)?;
+                b.serialize_with_mode(&mut writer, compress)
+            }
+        }
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        0u8.serialized_size(compress)
+            + match self {
+                Either::Left(a) => a.serialized_size(compress),
+                Either::Right(b) => b.serialized_size(compress),
+            }
+    }
+}
+
+impl<A: Valid, B: Valid> Valid for Either<A, B> {
+    fn check(&self) -> Result<(), SerializationError> {
+        match self {
+            Either::Left(a) => a.check(),
+            Either::Right(b) => b.check(),
+        }
+    }
+}
+
+impl<A: CanonicalDeserialize, B: CanonicalDeserialize> CanonicalDeserialize for Either<A, B> {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        match u8::deserialize_with_mode(&mut reader, compress, validate)? {
+            0 => Ok(Either::Left(A::deserialize_with_mode(
+                reader, compress, validate,
+            )?)),
+            1 => Ok(Either::Right(B::deserialize_with_mode(
+                reader, compress, validate,
+            )?)),
+            _ => Err(SerializationError::InvalidData),
+        }
+    }
+}
@@ -0,0 +1,715 @@
+//! A collection of logic and structures for running the SuperNova protocol
+//! with a relaxed committed Plonk arithmetization.
+//!
+//! Circuits are compiled into vanilla Plonk gates of fan-in two, where every gate constrains its
+//! wires `a`, `b` and `c` as `q_L * a + q_R * b + q_O * c + q_M * a * b + q_C = PI`, and wires
+//! holding the same value are tied together by copy constraints. The gate equation is relaxed by
+//! homogenizing it with the scalar `u`, which makes it quadratic in the instance-witness pair, so
+//! that it can be folded with a single crossterm vector just like relaxed R1CS.
+
+use crate::{
+    circuit::{synthesize_primary, witness_challenge},
+    cycle::Swapped,
+    lookup::Lookups,
+    r1cs::R1CSInstance,
+    shape_digest, Arithmetization, CommitmentScheme, CurveCycle, FoldingProof, Instance, Pedersen,
+    ProverError, RandomOracle, StepCircuit,
+};
+use ark_ec::short_weierstrass::Affine;
+use ark_ff::{One, PrimeField, Zero};
+use ark_relations::r1cs::{
+    ConstraintMatrices, ConstraintSystemRef, OptimizationGoal, SynthesisError,
+};
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, Read, SerializationError, Valid, Validate,
+    Write,
+};
+use core::marker::PhantomData;
+use rayon::prelude::*;
+
+/// The selectors of a single Plonk gate.
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Gate<F: PrimeField> {
+    pub q_L: F,
+    pub q_R: F,
+    pub q_O: F,
+    pub q_M: F,
+    pub q_C: F,
+}
+
+impl<F: PrimeField> Gate<F> {
+    fn zero() -> Self {
+        Self {
+            q_L: F::zero(),
+            q_R: F::zero(),
+            q_O: F::zero(),
+            q_M: F::zero(),
+            q_C: F::zero(),
+        }
+    }
+
+    // Evaluates the relaxed gate equation, without the public input.
+    fn eval(&self, a: F, b: F, c: F, u: F) -> F {
+        u * (self.q_L * a + self.q_R * b + self.q_O * c) + self.q_M * a * b + self.q_C * u * u
+    }
+}
+
+/// The shape of a Plonk circuit. The first gates each expose a public input on their left wire,
+/// and `sigma` is the permutation over all wire positions which encodes the copy constraints.
+/// Wire positions are laid out column by column, so the left wire of gate `i` is at position `i`,
+/// the right wire at `n + i` and the output wire at `2n + i`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlonkShape<F: PrimeField> {
+    pub num_public_inputs: usize,
+    pub gates: Vec<Gate<F>>,
+    pub sigma: Vec<usize>,
+}
+
+impl<F: PrimeField> PlonkShape<F> {
+    // Compiles the constraints of a synthesized constraint system into gates and copy constraints,
+    // and returns the shape alongside the wire assignment. The full assignment should start with
+    // the constant one, followed by the instance and witness assignments.
+    pub(crate) fn compile(
+        matrices: &ConstraintMatrices<F>,
+        assignment: &[F],
+    ) -> (Self, [Vec<F>; 3]) {
+        let num_public_inputs = matrices.num_instance_variables - 1;
+        let mut compiler = Compiler::new(assignment);
+
+        // Expose the public inputs on the left wires of the first gates.
+        for var in 1..=num_public_inputs {
+            compiler.push(
+                Gate {
+                    q_L: F::one(),
+                    ..Gate::zero()
+                },
+                [var, compiler.unused, compiler.unused],
+            );
+        }
+
+        // Every constraint `(k_A * a + c_A) * (k_B * b + c_B) = k_C * c + c_C` is expanded into a
+        // single gate, once its linear combinations are reduced to single wires.
+        for ((a, b), c) in matrices.a.iter().zip(&matrices.b).zip(&matrices.c) {
+            let (a, k_A, c_A) = compiler.reduce(a);
+            let (b, k_B, c_B) = compiler.reduce(b);
+            let (c, k_C, c_C) = compiler.reduce(c);
+            compiler.push(
+                Gate {
+                    q_L: k_A * c_B,
+                    q_R: c_A * k_B,
+                    q_O: -k_C,
+                    q_M: k_A * k_B,
+                    q_C: c_A * c_B - c_C,
+                },
+                [a, b, c],
+            );
+        }
+
+        compiler.finish(num_public_inputs)
+    }
+
+    // Returns a hash of the shape and the commitment key, which is used as the circuit parameters.
+    fn digest<R: RandomOracle<F>>(
+        &self,
+        oracle: &R,
+        key: &[u8; 32],
+    ) -> Result<F, SerializationError> {
+        shape_digest(self, oracle, key)
+    }
+
+    // Evaluates the relaxed gate equations for the given wires, public inputs and scalar.
+    fn eval(&self, wires: &[Vec<F>; 3], instance: &[F], u: F) -> Vec<F> {
+        self.gates
+            .par_iter()
+            .enumerate()
+            .map(|(i, gate)| {
+                let pi = instance.get(i).copied().unwrap_or(F::zero());
+                gate.eval(wires[0][i], wires[1][i], wires[2][i], u) - u * pi
+            })
+            .collect()
+    }
+}
+
+impl<F: PrimeField> CanonicalSerialize for PlonkShape<F> {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        self.num_public_inputs
+            .serialize_with_mode(&mut writer, compress)?;
+        self.gates.serialize_with_mode(&mut writer, compress)?;
+        self.sigma.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        self.num_public_inputs.serialized_size(compress)
+            + self.gates.serialized_size(compress)
+            + self.sigma.serialized_size(compress)
+    }
+}
+
+impl<F: PrimeField> Valid for PlonkShape<F> {
+    fn check(&self) -> Result<(), SerializationError> {
+        // Every public input needs a gate, and sigma should be a permutation over all wires.
+        let num_wires = 3 * self.gates.len();
+        if self.num_public_inputs > self.gates.len() || self.sigma.len() != num_wires {
+            return Err(SerializationError::InvalidData);
+        }
+
+        let mut seen = vec![false; num_wires];
+        for &pos in &self.sigma {
+            if pos >= num_wires || seen[pos] {
+                return Err(SerializationError::InvalidData);
+            }
+            seen[pos] = true;
+        }
+
+        Ok(())
+    }
+}
+
+impl<F: PrimeField> CanonicalDeserialize for PlonkShape<F> {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let shape = Self {
+            num_public_inputs: usize::deserialize_with_mode(&mut reader, compress, validate)?,
+            gates: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+            sigma: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+        };
+
+        if validate == Validate::Yes {
+            shape.check()?;
+        }
+
+        Ok(shape)
+    }
+}
+
+// Keeps track of the gates and the variables they are wired to while compiling a circuit.
+struct Compiler<F: PrimeField> {
+    values: Vec<F>,
+    gates: Vec<Gate<F>>,
+    wires: Vec<[usize; 3]>,
+    // A variable which is wired to every unused gate input.
+    unused: usize,
+}
+
+impl<F: PrimeField> Compiler<F> {
+    fn new(assignment: &[F]) -> Self {
+        let mut values = assignment.to_vec();
+        values.push(F::zero());
+        Self {
+            unused: values.len() - 1,
+            values,
+            gates: vec![],
+            wires: vec![],
+        }
+    }
+
+    fn push(&mut self, gate: Gate<F>, wires: [usize; 3]) {
+        self.gates.push(gate);
+        self.wires.push(wires);
+    }
+
+    // Reduces a linear combination to a single scaled variable and a constant, by summing up its
+    // terms with addition gates.
+    fn reduce(&mut self, lc: &[(F, usize)]) -> (usize, F, F) {
+        // The constant one is the first variable.
+        let constant = lc
+            .iter()
+            .filter(|(_, var)| *var == 0)
+            .fold(F::zero(), |acc, (coeff, _)| acc + coeff);
+        let mut terms = lc.iter().filter(|(_, var)| *var != 0);
+
+        let Some(&(mut k, mut v)) = terms.next() else {
+            return (self.unused, F::zero(), constant);
+        };
+
+        for &(k_next, v_next) in terms {
+            let sum = self.values.len();
+            self.values
+                .push(k * self.values[v] + k_next * self.values[v_next]);
+            self.push(
+                Gate {
+                    q_L: k,
+                    q_R: k_next,
+                    q_O: -F::one(),
+                    ..Gate::zero()
+                },
+                [v, v_next, sum],
+            );
+            (k, v) = (F::one(), sum);
+        }
+
+        (v, k, constant)
+    }
+
+    // Assigns the wires and ties together all wire positions which hold the same variable.
+    fn finish(self, num_public_inputs: usize) -> (PlonkShape<F>, [Vec<F>; 3]) {
+        let n = self.gates.len();
+        let wires = [0, 1, 2].map(|col| {
+            self.wires
+                .iter()
+                .map(|row| self.values[row[col]])
+                .collect::<Vec<F>>()
+        });
+
+        let mut positions = vec![vec![]; self.values.len()];
+        for col in 0..3 {
+            for (row, vars) in self.wires.iter().enumerate() {
+                positions[vars[col]].push(col * n + row);
+            }
+        }
+
+        let mut sigma = vec![0; 3 * n];
+        for cycle in positions.iter().filter(|cycle| !cycle.is_empty()) {
+            for (i, pos) in cycle.iter().enumerate() {
+                sigma[*pos] = cycle[(i + 1) % cycle.len()];
+            }
+        }
+
+        (
+            PlonkShape {
+                num_public_inputs,
+                gates: self.gates,
+                sigma,
+            },
+            wires,
+        )
+    }
+}
+
+/// The public part of a Plonk instance-witness pair.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct PlonkInstance<G: CurveCycle> {
+    pub(crate) comm_wires: Vec<Affine<G::C1>>,
+    pub(crate) comm_E: Affine<G::C1>,
+    pub(crate) u: G::F1,
+    pub(crate) challenge: G::F1,
+    pub(crate) io: Vec<G::F1>,
+}
+
+impl<G: CurveCycle> Instance<G> for PlonkInstance<G> {
+    fn witness_commitments(&self) -> Vec<Affine<G::C1>> {
+        self.comm_wires.clone()
+    }
+
+    fn error_commitment(&self) -> Affine<G::C1> {
+        self.comm_E
+    }
+
+    fn u(&self) -> G::F1 {
+        self.u
+    }
+
+    fn challenge(&self) -> G::F1 {
+        self.challenge
+    }
+
+    fn io(&self) -> &[G::F1] {
+        &self.io
+    }
+
+    fn has_crossterms(&self) -> bool {
+        self.u != G::F1::one()
+    }
+
+    fn dummy() -> Self {
+        Self {
+            comm_wires: vec![Affine::<G::C1>::identity(); 3],
+            comm_E: Affine::<G::C1>::identity(),
+            u: G::F1::one(),
+            challenge: G::F1::zero(),
+            io: vec![G::F1::zero(); 2],
+        }
+    }
+}
+
+/// A representation of the Plonk instance-witness pair. This is a committed relaxed Plonk
+/// instance, where every wire column is committed to separately, and defines logic for native
+/// folding. Lookups aren't supported, so nothing depends on the challenge, but it's derived and
+/// folded regardless, so that Plonk instances are folded in-circuit like any other.
+#[derive(Clone)]
+pub struct Plonk<G: CurveCycle, P: CommitmentScheme<G::C1> = Pedersen> {
+    pub(crate) shape: PlonkShape<G::F1>,
+    pub(crate) param: G::F1,
+    pub(crate) comm_wires: [Affine<G::C1>; 3],
+    pub(crate) comm_E: Affine<G::C1>,
+    pub(crate) E: Vec<G::F1>,
+    pub(crate) wires: [Vec<G::F1>; 3],
+    pub(crate) instance: Vec<G::F1>,
+    pub(crate) u: G::F1,
+    pub(crate) challenge: G::F1,
+    pub(crate) output: Vec<G::F1>,
+    pub(crate) pc: usize,
+    pub(crate) scheme: PhantomData<P>,
+}
+
+impl<G: CurveCycle, P: CommitmentScheme<G::C1>> Arithmetization<G> for Plonk<G, P> {
+    type Instance = PlonkInstance<G>;
+    type Scheme = P;
+    type Shape = PlonkShape<G::F1>;
+
+    fn instance(&self) -> PlonkInstance<G> {
+        PlonkInstance {
+            comm_wires: self.comm_wires.to_vec(),
+            comm_E: self.comm_E,
+            u: self.u,
+            challenge: self.challenge,
+            io: self.instance.clone(),
+        }
+    }
+
+    fn is_satisfied(&self, key: &P::Key) -> bool {
+        // Verify if the relaxed gate equations evaluate to E.
+        let evals = self.shape.eval(&self.wires, &self.instance, self.u);
+        if evals.iter().zip(&self.E).any(|(eval, e)| eval != e) {
+            return false;
+        }
+
+        // Verify the copy constraints.
+        let n = self.shape.gates.len();
+        let wire = |pos: usize| self.wires[pos / n][pos % n];
+        if (0..3 * n).any(|pos| wire(pos) != wire(self.shape.sigma[pos])) {
+            return false;
+        }
+
+        self.commitments_match(key)
+    }
+
+    fn commitments_match(&self, key: &P::Key) -> bool {
+        // Verify if comm_wires and comm_E are commitments to the wires and E.
+        P::commit_many(
+            key,
+            &[&self.wires[0], &self.wires[1], &self.wires[2], &self.E],
+        )
+        .is_ok_and(|comms| comms[..3] == self.comm_wires && comms[3] == self.comm_E)
+    }
+
+    fn output(&self) -> &[G::F1] {
+        &self.output
+    }
+
+    fn params(&self) -> G::F1 {
+        self.param
+    }
+
+    fn pc(&self) -> usize {
+        self.pc
+    }
+
+    fn has_crossterms(&self) -> bool {
+        self.E.iter().any(|v| !v.is_zero()) || self.u != G::F1::one()
+    }
+
+    fn shape_params<R: RandomOracle<G::F1>>(
+        &self,
+        oracle: &R,
+        key: &P::Key,
+    ) -> Result<G::F1, SerializationError> {
+        Self::digest(&self.shape, oracle, key)
+    }
+
+    fn has_shape(&self, shape: &Self::Shape) -> bool {
+        self.shape == *shape
+    }
+
+    fn set_params(&mut self, params: G::F1) {
+        self.param = params;
+    }
+
+    fn synthesize<
+        I: Instance<Swapped<G>>,
+        C: StepCircuit<G::F1> + ?Sized,
+        R1: RandomOracle<G::F1>,
+        R2: RandomOracle<G::F2>,
+    >(
+        params: G::F1,
+        z0: &[G::F1],
+        z: &[G::F1],
+        pc: usize,
+        i: usize,
+        running: &I,
+        latest: &I,
+        proof: &FoldingProof<Swapped<G>>,
+        oracle: &R1,
+        secondary_oracle: &R2,
+        key: &P::Key,
+        shape: &Self::Shape,
+        circuit: &C,
+    ) -> Result<Self, ProverError> {
+        // Every term of a linear combination costs a gate, so the amount of terms is minimized
+        // rather than the amount of constraints.
+        let (cs, next_pc, output) = synthesize_primary::<Swapped<G>, I, C, R1>(
+            params,
+            z0,
+            z,
+            pc,
+            i,
+            running,
+            latest,
+            proof,
+            oracle,
+            OptimizationGoal::Weight,
+            circuit,
+        )?;
+
+        // The pair is folded into the pairs of the circuit at `pc`, which only works if it shares
+        // their shape.
+        let pair = Self::from_cs(cs, next_pc, output, secondary_oracle, key)?;
+        if pair.shape != *shape {
+            return Err(ProverError::ShapeMismatch(pc));
+        }
+
+        Ok(pair)
+    }
+
+    fn shape<C: StepCircuit<G::F1> + ?Sized, R: RandomOracle<G::F1>>(
+        pc: usize,
+        z0: &[G::F1],
+        oracle: &R,
+        circuit: &C,
+    ) -> Result<Self::Shape, ProverError> {
+        let secondary = R1CSInstance::<Swapped<G>>::dummy();
+        let (cs, _, _) = synthesize_primary::<Swapped<G>, _, C, R>(
+            G::F1::zero(),
+            z0,
+            z0,
+            pc,
+            0,
+            &secondary,
+            &secondary,
+            &FoldingProof::Crossterms(Affine::identity()),
+            oracle,
+            OptimizationGoal::Weight,
+            circuit,
+        )?;
+        Ok(Self::compile(&cs)?.0)
+    }
+
+    // Every wire column and the error vector have an entry per gate.
+    fn key_size(shape: &Self::Shape) -> usize {
+        shape.gates.len()
+    }
+
+    fn digest<R: RandomOracle<G::F1>>(
+        shape: &Self::Shape,
+        oracle: &R,
+        key: &P::Key,
+    ) -> Result<G::F1, SerializationError> {
+        shape.digest(oracle, &P::digest(key))
+    }
+
+    // Returns T and the commitment to T, which captures the crossterms of the relaxed gate
+    // equations. As these are homogeneous of degree two, T is found by evaluating them over the
+    // sum of both pairs, and subtracting the evaluations over either pair.
+    fn commit_t(
+        &self,
+        other: &Self,
+        key: &P::Key,
+    ) -> Result<(Vec<G::F1>, Affine<G::C1>), ProverError> {
+        let wires = [0, 1, 2].map(|col| {
+            self.wires[col]
+                .iter()
+                .zip(&other.wires[col])
+                .map(|(w1, w2)| *w1 + w2)
+                .collect::<Vec<G::F1>>()
+        });
+        let instance = self
+            .instance
+            .iter()
+            .zip(&other.instance)
+            .map(|(x1, x2)| *x1 + x2)
+            .collect::<Vec<G::F1>>();
+
+        let sum = self.shape.eval(&wires, &instance, self.u + other.u);
+        let evals1 = self.shape.eval(&self.wires, &self.instance, self.u);
+        let evals2 = self.shape.eval(&other.wires, &other.instance, other.u);
+        let t = sum
+            .into_iter()
+            .zip(evals1)
+            .zip(evals2)
+            .map(|((sum, eval1), eval2)| sum - eval1 - eval2)
+            .collect::<Vec<G::F1>>();
+        let comm_T = P::commit(key, &t)?;
+        Ok((t, comm_T))
+    }
+
+    fn fold_with(
+        &mut self,
+        other: &Self,
+        t: Vec<G::F1>,
+        comm_T: &Affine<G::C1>,
+        r: G::F1,
+    ) -> Result<(), ProverError> {
+        self.wires
+            .iter_mut()
+            .zip(&other.wires)
+            .for_each(|(col1, col2)| {
+                col1.par_iter_mut()
+                    .zip(col2)
+                    .for_each(|(w1, w2)| *w1 += *w2 * r)
+            });
+        self.instance
+            .par_iter_mut()
+            .zip(&other.instance)
+            .for_each(|(x1, x2)| *x1 += *x2 * r);
+        self.comm_wires
+            .iter_mut()
+            .zip(&other.comm_wires)
+            .for_each(|(comm1, comm2)| *comm1 = P::add(comm1, &P::scale(comm2, r)));
+        self.E.par_iter_mut().zip(t).for_each(|(a, b)| *a += r * b);
+        self.comm_E = P::add(&self.comm_E, &P::scale(comm_T, r));
+        self.u += r;
+        self.challenge += r * other.challenge;
+        Ok(())
+    }
+}
+
+impl<G: CurveCycle, P: CommitmentScheme<G::C1>> CanonicalSerialize for Plonk<G, P> {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        self.shape.serialize_with_mode(&mut writer, compress)?;
+        self.param.serialize_with_mode(&mut writer, compress)?;
+        self.comm_wires.serialize_with_mode(&mut writer, compress)?;
+        self.comm_E.serialize_with_mode(&mut writer, compress)?;
+        self.E.serialize_with_mode(&mut writer, compress)?;
+        self.wires.serialize_with_mode(&mut writer, compress)?;
+        self.instance.serialize_with_mode(&mut writer, compress)?;
+        self.u.serialize_with_mode(&mut writer, compress)?;
+        self.challenge.serialize_with_mode(&mut writer, compress)?;
+        self.output.serialize_with_mode(&mut writer, compress)?;
+        self.pc.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        self.shape.serialized_size(compress)
+            + self.param.serialized_size(compress)
+            + self.comm_wires.serialized_size(compress)
+            + self.comm_E.serialized_size(compress)
+            + self.E.serialized_size(compress)
+            + self.wires.serialized_size(compress)
+            + self.instance.serialized_size(compress)
+            + self.u.serialized_size(compress)
+            + self.challenge.serialized_size(compress)
+            + self.output.serialized_size(compress)
+            + self.pc.serialized_size(compress)
+    }
+}
+
+impl<G: CurveCycle, P: CommitmentScheme<G::C1>> Valid for Plonk<G, P> {
+    fn check(&self) -> Result<(), SerializationError> {
+        // The assignments should match the shape, as they are evaluated against it when checking
+        // satisfiability.
+        let n = self.shape.gates.len();
+        if self.wires.iter().any(|col| col.len() != n)
+            || self.instance.len() != self.shape.num_public_inputs
+            || self.E.len() != n
+        {
+            return Err(SerializationError::InvalidData);
+        }
+
+        Ok(())
+    }
+}
+
+impl<G: CurveCycle, P: CommitmentScheme<G::C1>> CanonicalDeserialize for Plonk<G, P> {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let plonk = Self {
+            shape: PlonkShape::deserialize_with_mode(&mut reader, compress, validate)?,
+            param: G::F1::deserialize_with_mode(&mut reader, compress, validate)?,
+            comm_wires: <[Affine<G::C1>; 3]>::deserialize_with_mode(
+                &mut reader,
+                compress,
+                validate,
+            )?,
+            comm_E: Affine::<G::C1>::deserialize_with_mode(&mut reader, compress, validate)?,
+            E: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+            wires: <[Vec<G::F1>; 3]>::deserialize_with_mode(&mut reader, compress, validate)?,
+            instance: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+            u: G::F1::deserialize_with_mode(&mut reader, compress, validate)?,
+            challenge: G::F1::deserialize_with_mode(&mut reader, compress, validate)?,
+            output: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+            pc: usize::deserialize_with_mode(&mut reader, compress, validate)?,
+            scheme: PhantomData,
+        };
+
+        if validate == Validate::Yes {
+            plonk.check()?;
+        }
+
+        Ok(plonk)
+    }
+}
+
+impl<G: CurveCycle, P: CommitmentScheme<G::C1>> Plonk<G, P> {
+    // Returns a new unfolded instance-witness pair from a synthesized constraint system. The
+    // challenge is derived with the random oracle of the circuit over the other field.
+    fn from_cs<R: RandomOracle<G::F2>>(
+        cs: ConstraintSystemRef<G::F1>,
+        pc: usize,
+        output: Vec<G::F1>,
+        oracle: &R,
+        key: &P::Key,
+    ) -> Result<Self, ProverError> {
+        let (shape, wires) = Self::compile(&cs)?;
+        let cs = cs.borrow().ok_or(SynthesisError::MissingCS)?;
+
+        let required = Self::key_size(&shape);
+        let available = P::bases(key).len();
+        if required > available {
+            return Err(ProverError::KeyTooSmall(required, available));
+        }
+
+        // The error vector is zero, so its commitment is the point at infinity.
+        let comm_wires: [Affine<G::C1>; 3] =
+            P::commit_many(key, &[&wires[0], &wires[1], &wires[2]])?
+                .try_into()
+                .unwrap();
+        Ok(Self {
+            challenge: witness_challenge::<G, R>(oracle, &comm_wires[0]),
+            comm_wires,
+            comm_E: Affine::<G::C1>::identity(),
+            E: vec![G::F1::zero(); shape.gates.len()],
+            shape,
+            param: G::F1::zero(),
+            wires,
+            instance: cs.instance_assignment[1..].to_vec(),
+            u: G::F1::one(),
+            output,
+            pc,
+            scheme: PhantomData,
+        })
+    }
+
+    // Compiles a synthesized constraint system into Plonk gates, and returns the shape alongside
+    // the wire columns. Fails if the constraint system looks up any values.
+    #[allow(clippy::type_complexity)]
+    fn compile(
+        cs: &ConstraintSystemRef<G::F1>,
+    ) -> Result<(PlonkShape<G::F1>, [Vec<G::F1>; 3]), ProverError> {
+        if !Lookups::from_cs(cs)?.is_empty() {
+            return Err(ProverError::UnsupportedLookup);
+        }
+
+        let matrices = cs.to_matrices().ok_or(SynthesisError::MissingCS)?;
+        let cs = cs.borrow().ok_or(SynthesisError::MissingCS)?;
+        let assignment = [
+            cs.instance_assignment.as_slice(),
+            cs.witness_assignment.as_slice(),
+        ]
+        .concat();
+        Ok(PlonkShape::compile(&matrices, &assignment))
+    }
+}
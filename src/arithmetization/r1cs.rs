@@ -1,438 +1,327 @@
 //! A collection of logic and structures for running the SuperNova protocol
 //! with a relaxed committed R1CS arithmetization.
 
-use crate::{commit, Arithmetization};
-use ark_bls12_381::{Config, Fq, G1Affine};
-use ark_crypto_primitives::sponge::{
-    constraints::CryptographicSpongeVar,
-    poseidon::{constraints::PoseidonSpongeVar, PoseidonConfig, PoseidonSponge},
-    CryptographicSponge, FieldBasedCryptographicSponge,
+use crate::{
+    circuit::{synthesize_primary, witness_challenge},
+    cycle::Swapped,
+    lookup::{LookupAssignment, Lookups},
+    serialization::{deserialize_matrices, matrices_serialized_size, serialize_matrices},
+    shape_digest, Arithmetization, CommitmentScheme, CurveCycle, FoldingProof, Instance, Pedersen,
+    ProverError, RandomOracle, StepCircuit,
 };
-use ark_ec::AffineRepr;
-use ark_ff::{One, PrimeField, UniformRand, Zero};
-use ark_r1cs_std::{
-    alloc::AllocVar,
-    eq::EqGadget,
-    fields::fp::FpVar,
-    groups::{
-        curves::short_weierstrass::bls12::{G1AffineVar, G1Var},
-        CurveVar,
-    },
-    select::CondSelectGadget,
-    R1CSVar, ToBitsGadget, ToConstraintFieldGadget,
+use ark_ec::short_weierstrass::Affine;
+use ark_ff::{One, PrimeField, Zero};
+use ark_relations::r1cs::{
+    ConstraintMatrices, ConstraintSystemRef, OptimizationGoal, SynthesisError,
 };
-use ark_relations::r1cs::{ConstraintMatrices, ConstraintSystem, ConstraintSystemRef};
-use ark_serialize::CanonicalSerialize;
-use core::ops::{Add, Mul};
-use rand_core::OsRng;
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, Read, SerializationError, Valid, Validate,
+    Write,
+};
+use core::marker::PhantomData;
 use rayon::prelude::*;
 
-// A simplification of the inputs used to create a parameter hash of a circuit.
-#[derive(CanonicalSerialize)]
-struct SerializableShape {
-    num_vars: usize,
-    num_public_inputs: usize,
-    A: Vec<Vec<Fq>>,
-    B: Vec<Vec<Fq>>,
-    C: Vec<Vec<Fq>>,
+/// The shape of an R1CS circuit, which consists of its constraint matrices and its lookups.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct R1CSShape<F: PrimeField> {
+    pub matrices: ConstraintMatrices<F>,
+    pub lookups: Lookups<F>,
 }
 
-impl From<&ConstraintMatrices<Fq>> for SerializableShape {
-    fn from(v: &ConstraintMatrices<Fq>) -> Self {
-        let convert_matrix = |m: &[Vec<(Fq, usize)>]| -> Vec<Vec<Fq>> {
-            m.iter()
-                .map(|row| row.iter().map(|(coeff, _var)| *coeff).collect::<Vec<Fq>>())
-                .collect::<Vec<Vec<Fq>>>()
-        };
+impl<F: PrimeField> R1CSShape<F> {
+    // Returns the shape of a synthesized constraint system.
+    pub(crate) fn from_cs(cs: &ConstraintSystemRef<F>) -> Result<Self, SynthesisError> {
+        Ok(Self {
+            matrices: cs.to_matrices().ok_or(SynthesisError::MissingCS)?,
+            lookups: Lookups::from_cs(cs)?,
+        })
+    }
+}
+
+// The inputs used to create a parameter hash of a circuit, which consist of the constraint and
+// variable counts, every coefficient of the matrices alongside its column index, and the lookups.
+#[derive(CanonicalSerialize)]
+pub(crate) struct SerializableShape<F: PrimeField> {
+    num_constraints: usize,
+    num_instance_variables: usize,
+    num_witness_variables: usize,
+    A: Vec<Vec<(F, usize)>>,
+    B: Vec<Vec<(F, usize)>>,
+    C: Vec<Vec<(F, usize)>>,
+    lookups: Lookups<F>,
+}
 
+impl<F: PrimeField> From<&R1CSShape<F>> for SerializableShape<F> {
+    fn from(v: &R1CSShape<F>) -> Self {
         Self {
-            num_vars: v.num_witness_variables,
-            num_public_inputs: v.num_instance_variables,
-            A: convert_matrix(&v.a),
-            B: convert_matrix(&v.b),
-            C: convert_matrix(&v.c),
+            num_constraints: v.matrices.num_constraints,
+            num_instance_variables: v.matrices.num_instance_variables,
+            num_witness_variables: v.matrices.num_witness_variables,
+            A: v.matrices.a.clone(),
+            B: v.matrices.b.clone(),
+            C: v.matrices.c.clone(),
+            lookups: v.lookups.clone(),
         }
     }
 }
 
-impl SerializableShape {
-    fn digest(&self, constants: &PoseidonConfig<Fq>) -> Fq {
-        let mut bytes = vec![];
-        self.serialize_compressed(&mut bytes).unwrap();
-
-        let mut sponge = PoseidonSponge::<Fq>::new(constants);
-        sponge.absorb(&bytes);
-        sponge.squeeze_native_field_elements(1)[0]
+impl<F: PrimeField> SerializableShape<F> {
+    pub(crate) fn digest<R: RandomOracle<F>>(
+        &self,
+        oracle: &R,
+        key: &[u8; 32],
+    ) -> Result<F, SerializationError> {
+        shape_digest(self, oracle, key)
     }
 }
 
-/// A representation of the R1CS instance-witness pair. This is essentially a committed relaxed
-/// R1CS instance, and defines logic for native and in-circuit folding.
-#[derive(Clone)]
-pub struct R1CS {
-    pub(crate) shape: ConstraintMatrices<Fq>,
-    pub(crate) param: Fq,
-    pub(crate) comm_witness: G1Affine,
-    pub(crate) comm_E: G1Affine,
-    pub(crate) comm_T: G1Affine,
-    pub(crate) E: Vec<Fq>,
-    pub(crate) witness: Vec<Fq>,
-    pub(crate) instance: Vec<Fq>,
-    pub(crate) u: Fq,
-    pub(crate) hash: Fq,
-    pub(crate) output: Vec<Fq>,
+/// The public part of an R1CS instance-witness pair.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct R1CSInstance<G: CurveCycle> {
+    pub(crate) comm_witness: Affine<G::C1>,
+    pub(crate) comm_lookup: Affine<G::C1>,
+    pub(crate) comm_E: Affine<G::C1>,
+    pub(crate) u: G::F1,
+    pub(crate) challenge: G::F1,
+    pub(crate) io: Vec<G::F1>,
 }
 
-impl Arithmetization for R1CS {
-    type ConstraintSystem = ConstraintSystemRef<Fq>;
-    type Input = FpVar<Fq>;
+impl<G: CurveCycle> Instance<G> for R1CSInstance<G> {
+    fn witness_commitments(&self) -> Vec<Affine<G::C1>> {
+        vec![self.comm_witness, self.comm_lookup]
+    }
 
-    fn hash(&self) -> Fq {
-        self.hash
+    fn error_commitment(&self) -> Affine<G::C1> {
+        self.comm_E
     }
 
-    fn witness_commitment(&self) -> G1Affine {
-        self.comm_witness
+    fn u(&self) -> G::F1 {
+        self.u
     }
 
-    fn crossterms(&self) -> Vec<Fq> {
-        vec![
-            self.comm_E.x,
-            self.comm_E.y,
-            Fq::from(self.comm_E.infinity),
-            self.u,
-        ]
+    fn challenge(&self) -> G::F1 {
+        self.challenge
+    }
+
+    fn io(&self) -> &[G::F1] {
+        &self.io
+    }
+
+    fn has_crossterms(&self) -> bool {
+        self.u != G::F1::one()
+    }
+
+    fn dummy() -> Self {
+        Self {
+            comm_witness: Affine::<G::C1>::identity(),
+            comm_lookup: Affine::<G::C1>::identity(),
+            comm_E: Affine::<G::C1>::identity(),
+            u: G::F1::one(),
+            challenge: G::F1::zero(),
+            io: vec![G::F1::zero(); 2],
+        }
+    }
+}
+
+/// A representation of the R1CS instance-witness pair. This is essentially a committed relaxed
+/// R1CS instance, and defines logic for native folding. The folding is verified in-circuit by the
+/// circuit over the other field of the cycle.
+///
+/// The witness is committed to alongside the multiplicities of the lookups, and the inverses of
+/// the lookups are committed to separately, as they depend on a challenge derived from the first
+/// commitment. The error vector holds an entry for every constraint, followed by one for every
+/// constraint on the inverses.
+#[derive(Clone)]
+pub struct R1CS<G: CurveCycle, P: CommitmentScheme<G::C1> = Pedersen> {
+    pub(crate) shape: R1CSShape<G::F1>,
+    pub(crate) param: G::F1,
+    pub(crate) comm_witness: Affine<G::C1>,
+    pub(crate) comm_lookup: Affine<G::C1>,
+    pub(crate) comm_E: Affine<G::C1>,
+    pub(crate) E: Vec<G::F1>,
+    pub(crate) witness: Vec<G::F1>,
+    pub(crate) multiplicities: Vec<G::F1>,
+    pub(crate) inverses: Vec<G::F1>,
+    pub(crate) instance: Vec<G::F1>,
+    pub(crate) u: G::F1,
+    pub(crate) challenge: G::F1,
+    pub(crate) output: Vec<G::F1>,
+    pub(crate) pc: usize,
+    pub(crate) scheme: PhantomData<P>,
+}
+
+impl<G: CurveCycle, P: CommitmentScheme<G::C1>> Arithmetization<G> for R1CS<G, P> {
+    type Instance = R1CSInstance<G>;
+    type Scheme = P;
+    type Shape = R1CSShape<G::F1>;
+
+    fn instance(&self) -> R1CSInstance<G> {
+        R1CSInstance {
+            comm_witness: self.comm_witness,
+            comm_lookup: self.comm_lookup,
+            comm_E: self.comm_E,
+            u: self.u,
+            challenge: self.challenge,
+            io: self.instance.clone(),
+        }
     }
 
-    fn is_satisfied(&self, _generators: &[G1Affine]) -> bool {
+    fn is_satisfied(&self, key: &P::Key) -> bool {
         // Verify if az * bz = u*cz + E.
         let (az, bz, cz) = self.eval_r1cs();
+        let n = self.shape.matrices.num_constraints;
+
+        if (0..n).any(|i| az[i] * bz[i] != self.u * cz[i] + self.E[i]) {
+            return false;
+        }
 
-        if (0..self.shape.num_constraints).any(|i| az[i] * bz[i] != self.u * cz[i] + self.E[i]) {
+        // Verify if the constraints on the inverses evaluate to the rest of E, and if the inverses
+        // of every table sum up.
+        let lookups = self.lookup_assignment();
+        if self.shape.lookups.eval(&lookups, &lookups) != self.E[n..]
+            || !self.shape.lookups.is_balanced(&self.inverses)
+        {
             return false;
         }
 
-        // Verify if comm_E and comm_witness are commitments to E and witness.
-        // NOTE: arkworks does not allow the circuit to be satisfied if you attempt scalar mul in
-        // circuit with points at infinity, so this can not work currently. needs to probably swap
-        // out the crypto backend.
-        // let comm_witness = commit(generators, &self.witness);
-        // let comm_E = commit(generators, &self.E);
-        // self.comm_witness == comm_witness && self.comm_E == comm_E
-        true
+        self.commitments_match(key)
     }
 
-    fn output(&self) -> &[Fq] {
+    fn commitments_match(&self, key: &P::Key) -> bool {
+        // Verify if comm_witness, comm_E and comm_lookup are commitments to the witness alongside
+        // the multiplicities, E and the inverses.
+        let witness = [self.witness.as_slice(), &self.multiplicities].concat();
+        P::commit_many(key, &[&witness, &self.E, &self.inverses]).is_ok_and(|comms| {
+            comms[0] == self.comm_witness && comms[1] == self.comm_E && comms[2] == self.comm_lookup
+        })
+    }
+
+    fn output(&self) -> &[G::F1] {
         &self.output
     }
 
-    fn params(&self) -> Fq {
+    fn params(&self) -> G::F1 {
         self.param
     }
 
+    fn pc(&self) -> usize {
+        self.pc
+    }
+
     fn has_crossterms(&self) -> bool {
-        self.E.iter().any(|v| !v.is_zero()) || self.u != Fq::one()
+        self.E.iter().any(|v| !v.is_zero()) || self.u != G::F1::one()
     }
 
-    fn z0(&self) -> Vec<Fq> {
-        vec![Fq::zero(); self.output().len()]
+    fn shape_params<R: RandomOracle<G::F1>>(
+        &self,
+        oracle: &R,
+        key: &P::Key,
+    ) -> Result<G::F1, SerializationError> {
+        Self::digest(&self.shape, oracle, key)
     }
 
-    fn hash_terms(&self) -> Vec<Fq> {
-        self.z0()
-            .into_iter()
-            .chain(self.output().to_vec())
-            .chain([
-                self.comm_witness.x,
-                self.comm_witness.y,
-                Fq::from(self.comm_witness.infinity),
-            ])
-            .chain([self.comm_E.x, self.comm_E.y, Fq::from(self.comm_E.infinity)])
-            .chain([self.u, self.hash])
-            .collect::<Vec<Fq>>()
-    }
-
-    fn synthesize<C: Fn(Self::ConstraintSystem, &[Self::Input]) -> Vec<Self::Input>>(
-        &mut self,
-        params: Fq,
-        prev_terms: Vec<Fq>,
-        latest_witness: G1Affine,
-        latest_hash: Fq,
-        old_pc: usize,
-        new_pc: usize,
-        i: usize,
-        constants: &PoseidonConfig<Fq>,
-        generators: &[G1Affine],
-        circuit: C,
-    ) -> R1CS {
-        // TODO: program counter should be calculated in circuit, for now it's just supplied by
-        // user
-        let mut cs = ConstraintSystem::<Fq>::new_ref();
-        let old_pc = FpVar::<Fq>::new_witness(cs.clone(), || Ok(Fq::from(old_pc as u64))).unwrap();
-        let new_pc = FpVar::<Fq>::new_witness(cs.clone(), || Ok(Fq::from(new_pc as u64))).unwrap();
-
-        // Allocate the inputs which are needed to check correctness of the hash in the latest
-        // instance-witness pair.
-        let params = FpVar::<_>::new_witness(cs.clone(), || Ok(params)).unwrap();
-        let i = FpVar::<_>::new_witness(cs.clone(), || Ok(Fq::from(i as u64))).unwrap();
-        let prev_terms = prev_terms
-            .iter()
-            .map(|v| FpVar::<_>::new_witness(cs.clone(), || Ok(v)).unwrap())
-            .collect::<Vec<_>>();
-        let latest_witness =
-            G1Var::<Config>::new_witness(cs.clone(), || Ok(latest_witness)).unwrap();
-        let latest_hash = FpVar::<Fq>::new_witness(cs.clone(), || Ok(latest_hash)).unwrap();
-
-        let zero = FpVar::<_>::new_witness(cs.clone(), || Ok(Fq::zero())).unwrap();
-        let one = FpVar::<_>::new_witness(cs.clone(), || Ok(Fq::one())).unwrap();
-        let is_base_case = FpVar::<_>::is_eq(&i, &zero).unwrap();
-
-        let i_is_one = FpVar::<_>::is_eq(&i, &one).unwrap();
-        let params_select = FpVar::<_>::conditionally_select(&i_is_one, &zero, &params).unwrap();
-
-        let io_hash = compute_io_hash(constants, &mut cs, &params_select, &i, &old_pc, &prev_terms);
-
-        let comp_hash = FpVar::<Fq>::conditionally_select(&is_base_case, &zero, &io_hash).unwrap();
-        FpVar::<Fq>::enforce_equal(&comp_hash, &latest_hash).unwrap();
-
-        // Compute folding in-circuit.
-        let comm_witness =
-            G1Var::<Config>::new_witness(cs.clone(), || Ok(self.comm_witness)).unwrap();
-        let comm_E = G1Var::<Config>::new_witness(cs.clone(), || Ok(self.comm_E)).unwrap();
-        let u = FpVar::<Fq>::new_witness(cs.clone(), || Ok(self.u)).unwrap();
-        let hash = FpVar::<Fq>::new_witness(cs.clone(), || Ok(self.hash)).unwrap();
-        let T = G1Var::<Config>::new_witness(cs.clone(), || Ok(self.comm_T)).unwrap();
-
-        let r = compute_r(
-            constants,
-            &mut cs,
-            &params,
-            &comm_witness.to_affine().unwrap(),
-            &comm_E.to_affine().unwrap(),
-            &u,
-            &hash,
-            &latest_witness.to_affine().unwrap(),
-            &latest_hash,
-            &T.to_affine().unwrap(),
-        );
-
-        // NOTE: this is unsatisfiable in arkworks with points at infinity.
-        let r_witness = latest_witness
-            .scalar_mul_le(r.to_bits_le().unwrap().iter())
-            .unwrap();
-        let witness_fold = comm_witness.clone().add(&r_witness);
-
-        let rT = T.scalar_mul_le(r.to_bits_le().unwrap().iter()).unwrap();
-        let E_fold = comm_E.clone().add(&rT);
-
-        let u_fold = u.clone().add(&r);
-
-        let r_hash = latest_hash.clone().mul(&r);
-        let hash_fold = hash.add(&r_hash);
-
-        // Pick variables for the new hash input.
-        let witness_new =
-            G1Var::<Config>::conditionally_select(&is_base_case, &comm_witness, &witness_fold)
-                .unwrap();
-        let E_new = G1Var::<Config>::conditionally_select(&is_base_case, &comm_E, &E_fold).unwrap();
-        let u_new = FpVar::<_>::conditionally_select(&is_base_case, &u, &u_fold).unwrap();
-        let hash_new =
-            FpVar::<_>::conditionally_select(&is_base_case, &latest_hash, &hash_fold).unwrap();
-
-        let i_new =
-            FpVar::<_>::new_witness(cs.clone(), || Ok(i.value().unwrap() + Fq::one())).unwrap();
-
-        let output = self
-            .output()
-            .iter()
-            .map(|v| FpVar::<_>::new_witness(cs.clone(), || Ok(v)).unwrap())
-            .collect::<Vec<_>>();
-
-        // Generate the new output by running the step circuit.
-        let z0 = self
-            .z0()
-            .iter()
-            .map(|v| FpVar::<_>::new_witness(cs.clone(), || Ok(v)).unwrap())
-            .collect::<Vec<_>>();
-        let new_input = output
-            .iter()
-            .zip(&z0)
-            .map(|(v_output, v_0)| {
-                FpVar::<_>::conditionally_select(&is_base_case, v_0, v_output).unwrap()
-            })
-            .collect::<Vec<FpVar<Fq>>>();
+    fn has_shape(&self, shape: &Self::Shape) -> bool {
+        self.shape == *shape
+    }
 
-        let output = circuit(cs.clone(), &new_input);
+    fn set_params(&mut self, params: G::F1) {
+        self.param = params;
+    }
 
-        // Compute the hash for the new instance-witness pair.
-        let terms = z0
-            .into_iter()
-            .chain(output.clone())
-            .chain(
-                witness_new
-                    .to_affine()
-                    .unwrap()
-                    .to_constraint_field()
-                    .unwrap(),
-            )
-            .chain(E_new.to_affine().unwrap().to_constraint_field().unwrap())
-            .chain([u_new, hash_new])
-            .collect::<Vec<FpVar<_>>>();
-
-        let hash = FpVar::<_>::new_input(cs.clone(), || {
-            Ok(
-                compute_io_hash(constants, &mut cs, &params, &i_new, &new_pc, &terms)
-                    .value()
-                    .unwrap(),
-            )
-        })
-        .unwrap();
-        cs.finalize();
-
-        // Set the new output for later use.
-        self.output = output
-            .iter()
-            .map(|v| v.value().unwrap())
-            .collect::<Vec<Fq>>();
-
-        // Generate a new R1CS instance-witness pair which contains the circuit we've just built.
-        let matrices = cs.to_matrices().unwrap();
-        let cs = cs.borrow().unwrap();
-        // NOTE: we randomise commitments since points at infinity are not casted the same natively
-        // and in-circuit, which leads to hash discrepancies.
-        R1CS {
-            shape: matrices.clone(),
-            param: self.param,
-            comm_witness: commit(generators, &cs.witness_assignment),
-            comm_E: G1Affine::rand(&mut OsRng {}),
-            comm_T: G1Affine::rand(&mut OsRng {}),
-            E: vec![Fq::zero(); matrices.num_constraints],
-            witness: cs.witness_assignment.clone(),
-            instance: cs.instance_assignment[1..].to_vec(),
-            u: Fq::one(),
-            hash: hash.value().unwrap(),
-            output: vec![],
+    fn synthesize<
+        I: Instance<Swapped<G>>,
+        C: StepCircuit<G::F1> + ?Sized,
+        R1: RandomOracle<G::F1>,
+        R2: RandomOracle<G::F2>,
+    >(
+        params: G::F1,
+        z0: &[G::F1],
+        z: &[G::F1],
+        pc: usize,
+        i: usize,
+        running: &I,
+        latest: &I,
+        proof: &FoldingProof<Swapped<G>>,
+        oracle: &R1,
+        secondary_oracle: &R2,
+        key: &P::Key,
+        shape: &Self::Shape,
+        circuit: &C,
+    ) -> Result<Self, ProverError> {
+        let (cs, next_pc, output) = synthesize_primary::<Swapped<G>, I, C, R1>(
+            params,
+            z0,
+            z,
+            pc,
+            i,
+            running,
+            latest,
+            proof,
+            oracle,
+            OptimizationGoal::Constraints,
+            circuit,
+        )?;
+
+        // The pair is folded into the pairs of the circuit at `pc`, which only works if it shares
+        // their shape.
+        let pair = Self::from_cs(cs, next_pc, output, secondary_oracle, key)?;
+        if pair.shape != *shape {
+            return Err(ProverError::ShapeMismatch(pc));
         }
+
+        Ok(pair)
     }
 
-    fn fold(
-        &mut self,
-        other: &Self,
-        constants: &PoseidonConfig<Fq>,
-        generators: &[G1Affine],
-        params: Fq,
-    ) {
-        let mut sponge = PoseidonSponge::<Fq>::new(constants);
-        sponge.absorb(
-            &[params]
-                .into_iter()
-                .chain([
-                    self.comm_witness.x,
-                    self.comm_witness.y,
-                    Fq::from(self.comm_witness.infinity),
-                ])
-                .chain([self.comm_E.x, self.comm_E.y, Fq::from(self.comm_E.infinity)])
-                .chain([self.u])
-                .chain([self.hash])
-                .chain([
-                    other.comm_witness.x,
-                    other.comm_witness.y,
-                    Fq::from(other.comm_witness.infinity),
-                ])
-                .chain([other.hash])
-                .chain([self.comm_T.x, self.comm_T.y, Fq::from(self.comm_T.infinity)])
-                .collect::<Vec<Fq>>(),
-        );
-        let r = sponge.squeeze_native_field_elements(1)[0];
-        let (t, comm_T) = self.commit_t(other, generators);
-        self.witness
-            .par_iter_mut()
-            .zip(&other.witness)
-            .for_each(|(w1, w2)| *w1 += *w2 * r);
-        self.instance
-            .par_iter_mut()
-            .zip(&other.instance)
-            .for_each(|(x1, x2)| *x1 += *x2 * r);
-        self.comm_witness =
-            (self.comm_witness + other.comm_witness.mul_bigint(r.into_bigint())).into();
-        self.E.par_iter_mut().zip(t).for_each(|(a, b)| *a += r * b);
-        self.comm_E = (self.comm_E + self.comm_T.mul_bigint(r.into_bigint())).into();
-        self.u += r;
-        self.comm_T = comm_T;
-        self.hash += other.hash * r;
+    fn shape<C: StepCircuit<G::F1> + ?Sized, R: RandomOracle<G::F1>>(
+        pc: usize,
+        z0: &[G::F1],
+        oracle: &R,
+        circuit: &C,
+    ) -> Result<Self::Shape, ProverError> {
+        let secondary = R1CSInstance::<Swapped<G>>::dummy();
+        let (cs, _, _) = synthesize_primary::<Swapped<G>, _, C, R>(
+            G::F1::zero(),
+            z0,
+            z0,
+            pc,
+            0,
+            &secondary,
+            &secondary,
+            &FoldingProof::Crossterms(Affine::identity()),
+            oracle,
+            OptimizationGoal::Constraints,
+            circuit,
+        )?;
+        Ok(R1CSShape::from_cs(&cs)?)
     }
-}
 
-impl R1CS {
-    /// Returns a new R1CS instance-witness pair with the given step circuit.
-    pub fn new<
-        C: Fn(
-            <Self as Arithmetization>::ConstraintSystem,
-            &[<Self as Arithmetization>::Input],
-        ) -> Vec<<Self as Arithmetization>::Input>,
-    >(
-        z0: Vec<Fq>,
-        c: &C,
-        constants: &PoseidonConfig<Fq>,
-        generators: &[G1Affine],
-    ) -> (Self, Self) {
-        let empty_shape = ConstraintMatrices::<Fq> {
-            num_instance_variables: z0.len(),
-            num_witness_variables: 0,
-            num_constraints: 0,
-            a_num_non_zero: 0,
-            b_num_non_zero: 0,
-            c_num_non_zero: 0,
-            a: vec![],
-            b: vec![],
-            c: vec![],
-        };
+    // The witness alongside the multiplicities, the inverses and the error vector are all
+    // committed to with the same key, and there are no more inverses than error terms.
+    fn key_size(shape: &Self::Shape) -> usize {
+        let lookups = &shape.lookups;
+        (shape.matrices.num_witness_variables + lookups.num_entries())
+            .max(shape.matrices.num_constraints + lookups.num_rows())
+    }
 
-        // NOTE: we randomise commitments since points at infinity are not casted the same natively
-        // and in-circuit, which leads to hash discrepancies.
-        let mut r1cs = Self {
-            shape: empty_shape,
-            param: Fq::zero(),
-            comm_witness: G1Affine::rand(&mut OsRng {}),
-            comm_E: G1Affine::rand(&mut OsRng {}),
-            comm_T: G1Affine::rand(&mut OsRng {}),
-            E: vec![],
-            witness: vec![],
-            instance: vec![],
-            u: Fq::one(),
-            hash: Fq::zero(),
-            output: z0,
-        };
+    fn digest<R: RandomOracle<G::F1>>(
+        shape: &Self::Shape,
+        oracle: &R,
+        key: &P::Key,
+    ) -> Result<G::F1, SerializationError> {
+        SerializableShape::from(shape).digest(oracle, &P::digest(key))
+    }
 
-        let mut circuit = r1cs.synthesize(
-            Fq::zero(),
-            r1cs.hash_terms(),
-            G1Affine::rand(&mut OsRng {}),
-            Fq::zero(),
-            0,
-            0,
-            0,
-            constants,
-            generators,
-            c,
-        );
-
-        // Fix mutated variables.
-        r1cs.hash = Fq::zero();
-        r1cs.witness = circuit.witness.clone();
-        r1cs.instance = circuit.instance.clone();
-        r1cs.E = vec![Fq::zero(); circuit.shape.num_constraints];
-        let param = SerializableShape::from(&circuit.shape).digest(constants);
-        r1cs.param = param;
-        circuit.param = param;
-        r1cs.shape = circuit.shape.clone();
-        (r1cs, circuit)
-    }
-
-    // Returns T and the commitment to T, which captures some of the relaxed R1CS crossterms.
-    fn commit_t(&self, other: &Self, generators: &[G1Affine]) -> (Vec<Fq>, G1Affine) {
+    // Returns T and the commitment to T, which captures some of the relaxed R1CS crossterms,
+    // followed by the crossterms of the constraints on the inverses.
+    fn commit_t(
+        &self,
+        other: &Self,
+        key: &P::Key,
+    ) -> Result<(Vec<G::F1>, Affine<G::C1>), ProverError> {
         let (az1, bz1, cz1) = self.eval_r1cs();
         let (az2, bz2, cz2) = other.eval_r1cs();
+        let (lookups1, lookups2) = (self.lookup_assignment(), other.lookup_assignment());
+        let lookups = &self.shape.lookups;
 
         let t = az1
             .into_iter()
@@ -444,86 +333,229 @@ impl R1CS {
             .map(|(((((az1, bz2), az2), bz1), cz1), cz2)| {
                 az1 * bz2 + az2 * bz1 - self.u * cz2 - cz1
             })
-            .collect::<Vec<Fq>>();
-        let mut comm_T = commit(generators, &t);
-
-        // NOTE: During our first fold in the base case, we may generate a commitment point that's at
-        // infinity. In this case, we need to ensure that the point isn't at infinity, otherwise
-        // the circuit is no longer satisfiable. This is due to some peculiarty, likely in
-        // arkworks, that needs to be investigated.
-        if comm_T.infinity {
-            comm_T = G1Affine::rand(&mut OsRng {});
+            .chain(
+                lookups
+                    .eval(&lookups1, &lookups2)
+                    .into_iter()
+                    .zip(lookups.eval(&lookups2, &lookups1))
+                    .map(|(t1, t2)| t1 + t2),
+            )
+            .collect::<Vec<G::F1>>();
+        let comm_T = P::commit(key, &t)?;
+        Ok((t, comm_T))
+    }
+
+    fn fold_with(
+        &mut self,
+        other: &Self,
+        t: Vec<G::F1>,
+        comm_T: &Affine<G::C1>,
+        r: G::F1,
+    ) -> Result<(), ProverError> {
+        for (v1, v2) in [
+            (&mut self.witness, &other.witness),
+            (&mut self.multiplicities, &other.multiplicities),
+            (&mut self.inverses, &other.inverses),
+            (&mut self.instance, &other.instance),
+        ] {
+            v1.par_iter_mut()
+                .zip(v2)
+                .for_each(|(x1, x2)| *x1 += *x2 * r);
+        }
+        self.comm_witness = P::add(&self.comm_witness, &P::scale(&other.comm_witness, r));
+        self.comm_lookup = P::add(&self.comm_lookup, &P::scale(&other.comm_lookup, r));
+        self.E.par_iter_mut().zip(t).for_each(|(a, b)| *a += r * b);
+        self.comm_E = P::add(&self.comm_E, &P::scale(comm_T, r));
+        self.u += r;
+        self.challenge += r * other.challenge;
+        Ok(())
+    }
+}
+
+impl<G: CurveCycle, P: CommitmentScheme<G::C1>> CanonicalSerialize for R1CS<G, P> {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        serialize_matrices(&self.shape.matrices, &mut writer, compress)?;
+        self.shape
+            .lookups
+            .serialize_with_mode(&mut writer, compress)?;
+        self.param.serialize_with_mode(&mut writer, compress)?;
+        self.comm_witness
+            .serialize_with_mode(&mut writer, compress)?;
+        self.comm_lookup
+            .serialize_with_mode(&mut writer, compress)?;
+        self.comm_E.serialize_with_mode(&mut writer, compress)?;
+        self.E.serialize_with_mode(&mut writer, compress)?;
+        self.witness.serialize_with_mode(&mut writer, compress)?;
+        self.multiplicities
+            .serialize_with_mode(&mut writer, compress)?;
+        self.inverses.serialize_with_mode(&mut writer, compress)?;
+        self.instance.serialize_with_mode(&mut writer, compress)?;
+        self.u.serialize_with_mode(&mut writer, compress)?;
+        self.challenge.serialize_with_mode(&mut writer, compress)?;
+        self.output.serialize_with_mode(&mut writer, compress)?;
+        self.pc.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        matrices_serialized_size(&self.shape.matrices, compress)
+            + self.shape.lookups.serialized_size(compress)
+            + self.param.serialized_size(compress)
+            + self.comm_witness.serialized_size(compress)
+            + self.comm_lookup.serialized_size(compress)
+            + self.comm_E.serialized_size(compress)
+            + self.E.serialized_size(compress)
+            + self.witness.serialized_size(compress)
+            + self.multiplicities.serialized_size(compress)
+            + self.inverses.serialized_size(compress)
+            + self.instance.serialized_size(compress)
+            + self.u.serialized_size(compress)
+            + self.challenge.serialized_size(compress)
+            + self.output.serialized_size(compress)
+            + self.pc.serialized_size(compress)
+    }
+}
+
+impl<G: CurveCycle, P: CommitmentScheme<G::C1>> Valid for R1CS<G, P> {
+    fn check(&self) -> Result<(), SerializationError> {
+        // The assignments should match the shape, as they are evaluated against it when checking
+        // satisfiability. The instance assignment excludes the leading constant.
+        let (matrices, lookups) = (&self.shape.matrices, &self.shape.lookups);
+        if self.witness.len() != matrices.num_witness_variables
+            || self.multiplicities.len() != lookups.num_entries()
+            || self.inverses.len() != lookups.num_rows()
+            || self.instance.len() + 1 != matrices.num_instance_variables
+            || self.E.len() != matrices.num_constraints + lookups.num_rows()
+            || !lookups.is_valid(matrices.num_witness_variables)
+        {
+            return Err(SerializationError::InvalidData);
+        }
+
+        Ok(())
+    }
+}
+
+impl<G: CurveCycle, P: CommitmentScheme<G::C1>> CanonicalDeserialize for R1CS<G, P> {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let r1cs = Self {
+            shape: R1CSShape {
+                matrices: deserialize_matrices(&mut reader, compress, validate)?,
+                lookups: Lookups::deserialize_with_mode(&mut reader, compress, validate)?,
+            },
+            param: G::F1::deserialize_with_mode(&mut reader, compress, validate)?,
+            comm_witness: Affine::<G::C1>::deserialize_with_mode(&mut reader, compress, validate)?,
+            comm_lookup: Affine::<G::C1>::deserialize_with_mode(&mut reader, compress, validate)?,
+            comm_E: Affine::<G::C1>::deserialize_with_mode(&mut reader, compress, validate)?,
+            E: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+            witness: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+            multiplicities: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+            inverses: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+            instance: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+            u: G::F1::deserialize_with_mode(&mut reader, compress, validate)?,
+            challenge: G::F1::deserialize_with_mode(&mut reader, compress, validate)?,
+            output: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+            pc: usize::deserialize_with_mode(&mut reader, compress, validate)?,
+            scheme: PhantomData,
+        };
+
+        if validate == Validate::Yes {
+            r1cs.check()?;
         }
 
-        (t, comm_T)
+        Ok(r1cs)
+    }
+}
+
+impl<G: CurveCycle, P: CommitmentScheme<G::C1>> R1CS<G, P> {
+    // Returns a new unfolded instance-witness pair from a synthesized constraint system. The
+    // challenge is derived with the random oracle of the circuit over the other field, which
+    // checks it when folding the pair. Fails if a value looked up isn't in its table.
+    pub(crate) fn from_cs<R: RandomOracle<G::F2>>(
+        cs: ConstraintSystemRef<G::F1>,
+        pc: usize,
+        output: Vec<G::F1>,
+        oracle: &R,
+        key: &P::Key,
+    ) -> Result<Self, ProverError> {
+        let shape = R1CSShape::from_cs(&cs)?;
+        let cs = cs.borrow().ok_or(SynthesisError::MissingCS)?;
+        let required = Self::key_size(&shape);
+        let available = P::bases(key).len();
+        if required > available {
+            return Err(ProverError::KeyTooSmall(required, available));
+        }
+
+        // The inverses are committed to once the challenge is derived from the witness and the
+        // multiplicities.
+        let witness = cs.witness_assignment.clone();
+        let multiplicities = shape
+            .lookups
+            .multiplicities(&witness)
+            .ok_or(ProverError::UnsatisfiedStep)?;
+        let comm_witness = P::commit(key, &[witness.as_slice(), &multiplicities].concat())?;
+        let challenge = witness_challenge::<G, R>(oracle, &comm_witness);
+        let inverses = shape
+            .lookups
+            .inverses(&witness, &multiplicities, challenge)
+            .ok_or(ProverError::UnsatisfiedStep)?;
+
+        // The error vector is zero, so its commitment is the point at infinity.
+        Ok(Self {
+            param: G::F1::zero(),
+            comm_witness,
+            comm_lookup: P::commit(key, &inverses)?,
+            comm_E: Affine::<G::C1>::identity(),
+            E: vec![G::F1::zero(); shape.matrices.num_constraints + shape.lookups.num_rows()],
+            shape,
+            witness,
+            multiplicities,
+            inverses,
+            instance: cs.instance_assignment[1..].to_vec(),
+            u: G::F1::one(),
+            challenge,
+            output,
+            pc,
+            scheme: PhantomData,
+        })
+    }
+
+    // Returns the assignment to the lookups of the pair.
+    fn lookup_assignment(&self) -> LookupAssignment<'_, G::F1> {
+        LookupAssignment {
+            u: self.u,
+            challenge: self.challenge,
+            witness: &self.witness,
+            multiplicities: &self.multiplicities,
+            inverses: &self.inverses,
+        }
     }
 
     // Evaluates the R1CS by multiplying the instance-witness vector with the coefficient matrices.
     // Returns Az, Bz and Cz, which are used for checking satisfiability of constraint equations.
     #[allow(clippy::type_complexity)]
-    fn eval_r1cs(&self) -> (Vec<Fq>, Vec<Fq>, Vec<Fq>) {
-        let sparse_matrix_vec_product = |m: &[Vec<(Fq, usize)>], z: &[Fq]| -> Vec<Fq> {
+    pub(crate) fn eval_r1cs(&self) -> (Vec<G::F1>, Vec<G::F1>, Vec<G::F1>) {
+        let sparse_matrix_vec_product = |m: &[Vec<(G::F1, usize)>], z: &[G::F1]| -> Vec<G::F1> {
             m.par_iter()
                 .map(|row| {
                     row.par_iter()
-                        .fold(Fq::zero, |acc, (coeff, val)| acc + coeff * &z[*val])
-                        .reduce(Fq::zero, |acc, val| acc + val)
+                        .fold(G::F1::zero, |acc, (coeff, val)| acc + *coeff * z[*val])
+                        .reduce(G::F1::zero, |acc, val| acc + val)
                 })
-                .collect::<Vec<Fq>>()
+                .collect::<Vec<G::F1>>()
         };
 
-        let z = vec![vec![self.u], self.instance.clone(), self.witness.clone()].concat();
+        let z = [vec![self.u], self.instance.clone(), self.witness.clone()].concat();
         (
-            sparse_matrix_vec_product(&self.shape.a, &z),
-            sparse_matrix_vec_product(&self.shape.b, &z),
-            sparse_matrix_vec_product(&self.shape.c, &z),
+            sparse_matrix_vec_product(&self.shape.matrices.a, &z),
+            sparse_matrix_vec_product(&self.shape.matrices.b, &z),
+            sparse_matrix_vec_product(&self.shape.matrices.c, &z),
         )
     }
 }
-
-fn compute_io_hash(
-    constants: &PoseidonConfig<Fq>,
-    cs: &mut ConstraintSystemRef<Fq>,
-    params: &FpVar<Fq>,
-    i: &FpVar<Fq>,
-    pc: &FpVar<Fq>,
-    prev_terms: &[FpVar<Fq>],
-) -> FpVar<Fq> {
-    let mut sponge = PoseidonSpongeVar::<Fq>::new(cs.clone(), constants);
-    sponge.absorb(&params).unwrap();
-    sponge.absorb(&i).unwrap();
-    sponge.absorb(&pc).unwrap();
-    prev_terms.iter().for_each(|v| sponge.absorb(v).unwrap());
-    sponge.squeeze_field_elements(1).unwrap().remove(0)
-}
-
-#[allow(clippy::too_many_arguments)]
-fn compute_r(
-    constants: &PoseidonConfig<Fq>,
-    cs: &mut ConstraintSystemRef<Fq>,
-    params: &FpVar<Fq>,
-    comm_witness: &G1AffineVar<Config>,
-    comm_E: &G1AffineVar<Config>,
-    u: &FpVar<Fq>,
-    hash: &FpVar<Fq>,
-    latest_witness: &G1AffineVar<Config>,
-    latest_hash: &FpVar<Fq>,
-    T: &G1AffineVar<Config>,
-) -> FpVar<Fq> {
-    let mut sponge = PoseidonSpongeVar::<Fq>::new(cs.clone(), constants);
-    sponge.absorb(params).unwrap();
-    sponge
-        .absorb(&comm_witness.to_constraint_field().unwrap())
-        .unwrap();
-    sponge
-        .absorb(&comm_E.to_constraint_field().unwrap())
-        .unwrap();
-    sponge.absorb(u).unwrap();
-    sponge.absorb(hash).unwrap();
-    sponge
-        .absorb(&latest_witness.to_constraint_field().unwrap())
-        .unwrap();
-    sponge.absorb(latest_hash).unwrap();
-    sponge.absorb(&T.to_constraint_field().unwrap()).unwrap();
-    sponge.squeeze_field_elements(1).unwrap().remove(0)
-}
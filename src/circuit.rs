@@ -0,0 +1,855 @@
+//! The augmented circuits which drive the recursion, shared across arithmetizations. The primary
+//! circuit runs a step circuit and folds the latest secondary instance into the running secondary
+//! instance, while the secondary circuit folds the resulting primary instance into the running
+//! instance of the circuit that was invoked.
+//!
+//! Both circuits are written for the cycle `H` of the instances they fold, and are defined over
+//! `H::F2`. This way, the commitments of the folded instances are native to the circuit, and only
+//! their scalars need to be represented non-natively. Each circuit outputs the latest hash of the
+//! other circuit alongside a hash of its own state, so that the other circuit can check it on its
+//! next invocation. Hashes are truncated to fit in both fields of the cycle.
+//!
+//! Instances are folded with the proof of their native folding. Crossterms are folded into the
+//! error commitment, while a multi-folding proof has its sum-check verified in-circuit, with
+//! challenges derived over the field of the circuit.
+
+use crate::{
+    compression::SumcheckTranscript, CurveCycle, FoldingProof, Instance, ProverError, RandomOracle,
+    StepCircuit,
+};
+use ark_ec::short_weierstrass::{Affine, SWCurveConfig};
+use ark_ff::{BigInteger, Field, One, PrimeField, Zero};
+use ark_r1cs_std::{
+    alloc::AllocVar,
+    boolean::Boolean,
+    eq::EqGadget,
+    fields::{fp::FpVar, nonnative::NonNativeFieldVar, FieldVar},
+    groups::{curves::short_weierstrass::ProjectiveVar, CurveVar},
+    select::CondSelectGadget,
+    R1CSVar, ToBitsGadget, ToConstraintFieldGadget,
+};
+use ark_relations::r1cs::{
+    ConstraintSystem, ConstraintSystemRef, OptimizationGoal, SynthesisError,
+};
+
+/// The number of bits that hashes are truncated to, so that they fit in both fields of the cycle.
+pub(crate) const NUM_HASH_BITS: usize = 250;
+
+// The number of bits of the challenges used for folding.
+const NUM_CHALLENGE_BITS: usize = 128;
+
+// The in-circuit representations of the commitments and scalars of the folded instances.
+type PointVar<H> = ProjectiveVar<<H as CurveCycle>::C1, FpVar<<H as CurveCycle>::F2>>;
+type ScalarVar<H> = NonNativeFieldVar<<H as CurveCycle>::F1, <H as CurveCycle>::F2>;
+
+/// Reinterprets the lowest `num_bits` bits of a field element as an element of another field.
+pub(crate) fn truncate<F: PrimeField, T: PrimeField>(v: F, num_bits: usize) -> T {
+    let bits = v.into_bigint().to_bits_le();
+    T::from_bigint(T::BigInt::from_bits_le(&bits[..num_bits])).unwrap()
+}
+
+// Splits an element of the other field into chunks which fit in the native field.
+fn scalar_terms<F: PrimeField, T: PrimeField>(v: F) -> Vec<T> {
+    let bits = v.into_bigint().to_bits_le();
+    bits[..F::MODULUS_BIT_SIZE as usize]
+        .chunks(T::MODULUS_BIT_SIZE as usize - 1)
+        .map(|chunk| T::from_bigint(T::BigInt::from_bits_le(chunk)).unwrap())
+        .collect()
+}
+
+// Returns the coordinates of a point and whether it's at infinity. The point at infinity is
+// encoded as `(0, 1)`, which matches its encoding in-circuit.
+fn point_terms<C: SWCurveConfig>(p: &Affine<C>) -> [C::BaseField; 3] {
+    if p.infinity {
+        [
+            C::BaseField::zero(),
+            C::BaseField::one(),
+            C::BaseField::one(),
+        ]
+    } else {
+        [p.x, p.y, C::BaseField::zero()]
+    }
+}
+
+/// Returns the terms used for hashing an instance in the circuit over the other field.
+pub(crate) fn instance_terms<H: CurveCycle, I: Instance<H>>(instance: &I) -> Vec<H::F2> {
+    let (point, evals) = instance.evaluations();
+    instance
+        .witness_commitments()
+        .into_iter()
+        .chain([instance.error_commitment()])
+        .flat_map(|p| point_terms(&p))
+        .chain(scalar_terms(instance.u()))
+        .chain(scalar_terms(instance.challenge()))
+        .chain(instance.io().iter().flat_map(|v| scalar_terms(*v)))
+        .chain(point.iter().chain(evals).flat_map(|v| scalar_terms(*v)))
+        .collect()
+}
+
+/// Returns the digest of a program, which hashes the parameters of all of its circuits in order,
+/// so that it binds every circuit to its position in the program.
+pub(crate) fn program_digest<F: PrimeField, R: RandomOracle<F>>(oracle: &R, params: &[F]) -> F {
+    oracle.hash(
+        &[F::from(params.len() as u64)]
+            .into_iter()
+            .chain(params.iter().copied())
+            .collect::<Vec<F>>(),
+    )
+}
+
+/// Returns the hash of the state of an augmented circuit, truncated to [`NUM_HASH_BITS`]. This
+/// should match the hash created in-circuit.
+pub(crate) fn hash_state<F: PrimeField, R: RandomOracle<F>>(
+    oracle: &R,
+    params: F,
+    i: usize,
+    pc: usize,
+    terms: &[F],
+) -> F {
+    let hash = oracle.hash(
+        &[params, F::from(i as u64), F::from(pc as u64)]
+            .into_iter()
+            .chain(terms.iter().copied())
+            .collect::<Vec<F>>(),
+    );
+    truncate(hash, NUM_HASH_BITS)
+}
+
+/// Returns the challenge for folding `latest` into `running`. This should match the challenge
+/// computed in-circuit.
+pub(crate) fn challenge<H: CurveCycle, I: Instance<H>, R: RandomOracle<H::F2>>(
+    oracle: &R,
+    params: H::F2,
+    running: &I,
+    latest: &I,
+    comm_T: &Affine<H::C1>,
+) -> H::F1 {
+    let r = oracle.hash(
+        &[params]
+            .into_iter()
+            .chain(running.hash_terms())
+            .chain(latest.hash_terms())
+            .chain(point_terms(comm_T))
+            .collect::<Vec<H::F2>>(),
+    );
+    truncate(r, NUM_CHALLENGE_BITS)
+}
+
+/// A transcript of a multi-folding proof, which derives its challenges over the other field of the
+/// cycle. This should match the transcript of the circuit which verifies the proof.
+pub(crate) struct FoldingTranscript<'a, H: CurveCycle, R: RandomOracle<H::F2>> {
+    oracle: &'a R,
+    state: Vec<H::F2>,
+}
+
+impl<'a, H: CurveCycle, R: RandomOracle<H::F2>> FoldingTranscript<'a, H, R> {
+    /// Starts a transcript of folding `latest` into `running` with the given parameters.
+    pub(crate) fn new<I: Instance<H>>(
+        oracle: &'a R,
+        params: H::F2,
+        running: &I,
+        latest: &I,
+    ) -> Self {
+        Self::with_terms(
+            oracle,
+            [vec![params], running.hash_terms(), latest.hash_terms()].concat(),
+        )
+    }
+
+    /// Starts a transcript which has absorbed the given terms.
+    pub(crate) fn with_terms(oracle: &'a R, terms: Vec<H::F2>) -> Self {
+        Self {
+            oracle,
+            state: terms,
+        }
+    }
+}
+
+impl<H: CurveCycle, R: RandomOracle<H::F2>> SumcheckTranscript<H::F1>
+    for FoldingTranscript<'_, H, R>
+{
+    fn absorb_scalars(&mut self, scalars: &[H::F1]) {
+        self.state
+            .extend(scalars.iter().flat_map(|v| scalar_terms::<_, H::F2>(*v)));
+    }
+
+    fn challenge(&mut self) -> H::F1 {
+        let challenge = self.oracle.hash(&self.state);
+        self.state = vec![challenge];
+        truncate(challenge, NUM_CHALLENGE_BITS)
+    }
+}
+
+/// Returns the challenge of an unfolded instance with the given first witness commitment, which the
+/// rest of its witness may depend on. This should match the challenge checked in-circuit.
+pub(crate) fn witness_challenge<H: CurveCycle, R: RandomOracle<H::F2>>(
+    oracle: &R,
+    comm: &Affine<H::C1>,
+) -> H::F1 {
+    truncate(oracle.hash(&point_terms(comm)), NUM_CHALLENGE_BITS)
+}
+
+/// Synthesizes an invocation of the primary circuit, which checks that the latest instance of the
+/// other circuit hashes the current state, folds it into the running instance, and runs the step
+/// circuit. Returns the constraint system alongside the next program counter and the output. The
+/// optimization goal should suit the arithmetization that the constraint system is compiled into.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(crate) fn synthesize_primary<H: CurveCycle, I: Instance<H>, C, R>(
+    params: H::F2,
+    z0: &[H::F2],
+    z: &[H::F2],
+    pc: usize,
+    i: usize,
+    running: &I,
+    latest: &I,
+    proof: &FoldingProof<H>,
+    oracle: &R,
+    goal: OptimizationGoal,
+    circuit: &C,
+) -> Result<(ConstraintSystemRef<H::F2>, usize, Vec<H::F2>), ProverError>
+where
+    C: StepCircuit<H::F2> + ?Sized,
+    R: RandomOracle<H::F2>,
+{
+    if z.len() != circuit.arity() {
+        return Err(ProverError::ArityMismatch(circuit.arity(), z.len()));
+    }
+
+    let cs = ConstraintSystem::<H::F2>::new_ref();
+    cs.set_optimization_goal(goal);
+    // The program counter of the invoked circuit is a constant, so that the hash check below
+    // can only pass if the previous invocation selected this circuit.
+    let pc = FpVar::<H::F2>::Constant(H::F2::from(pc as u64));
+
+    let params = FpVar::<_>::new_witness(cs.clone(), || Ok(params))?;
+    let i = FpVar::<_>::new_witness(cs.clone(), || Ok(H::F2::from(i as u64)))?;
+    let z0 = new_witnesses(cs.clone(), z0)?;
+    let z = new_witnesses(cs.clone(), z)?;
+    let running = InstanceVar::<H>::new_witness(cs.clone(), running)?;
+    let latest = InstanceVar::<H>::new_witness(cs.clone(), latest)?;
+    let proof = FoldingProofVar::<H>::new_witness(cs.clone(), proof)?;
+    let is_base_case = i.is_eq(&FpVar::zero())?;
+
+    // Check that the latest instance hashes the current state, and that its challenge is derived
+    // from its witness, unless there is none yet.
+    let running_terms = running.terms()?;
+    let terms = [z0.clone(), z.clone(), running_terms.clone()].concat();
+    let hash = hash_state_var(cs.clone(), oracle, &params, &i, &pc, &terms)?;
+    hash_bits::<H>(&latest.io[0])?.conditional_enforce_equal(&hash, &is_base_case.not())?;
+    latest.enforce_challenge(cs.clone(), oracle, &is_base_case.not())?;
+
+    // Fold the latest instance into the running instance. In the base case, the latest instance
+    // is a placeholder, so the running instance is kept as is.
+    let folded_terms = running
+        .fold(cs.clone(), oracle, &params, &latest, &proof)?
+        .terms()?;
+    let running_terms = select_all(&is_base_case, &running_terms, &folded_terms)?;
+
+    // Generate the new output and program counter by running the step circuit, starting from the
+    // initial input in the base case.
+    let input = select_all(&is_base_case, &z0, &z)?;
+    let (new_pc, output) = circuit.synthesize(cs.clone(), &input)?;
+    if output.len() != circuit.arity() {
+        return Err(ProverError::ArityMismatch(circuit.arity(), output.len()));
+    }
+
+    let terms = [z0, output.clone(), running_terms].concat();
+    let hash = hash_state_var(
+        cs.clone(),
+        oracle,
+        &params,
+        &(i + FpVar::one()),
+        &new_pc,
+        &terms,
+    )?;
+    new_hash_inputs(cs.clone(), [hash_bits::<H>(&latest.io[1])?, hash])?;
+
+    // The values are read before finalizing, which inlines symbolic linear combinations, such as
+    // outputs which are sums of other variables.
+    let new_pc = to_pc(new_pc.value()?);
+    let output = output
+        .iter()
+        .map(|v| v.value())
+        .collect::<Result<Vec<_>, _>>()?;
+    cs.finalize();
+
+    if !cs.is_satisfied()? {
+        return Err(ProverError::UnsatisfiedStep);
+    }
+
+    Ok((cs, new_pc, output))
+}
+
+/// Synthesizes an invocation of the secondary circuit, which checks that the latest instance of
+/// the other circuit hashes the running instances of all circuits, and folds it into the running
+/// instance at `pc`. Multi-folding proofs are verified even in the base case, which needs a valid
+/// proof.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn synthesize_secondary<H: CurveCycle, I: Instance<H>, R: RandomOracle<H::F2>>(
+    params: H::F2,
+    i: usize,
+    pc: usize,
+    running: &[I],
+    latest: &I,
+    proof: &FoldingProof<H>,
+    oracle: &R,
+) -> Result<ConstraintSystemRef<H::F2>, SynthesisError> {
+    let cs = ConstraintSystem::<H::F2>::new_ref();
+    // The secondary circuit isn't selected by a program counter, so it hashes a constant instead.
+    let zero = FpVar::<H::F2>::zero();
+
+    let params = FpVar::<_>::new_witness(cs.clone(), || Ok(params))?;
+    let i = FpVar::<_>::new_witness(cs.clone(), || Ok(H::F2::from(i as u64)))?;
+    let pc = FpVar::<_>::new_witness(cs.clone(), || Ok(H::F2::from(pc as u64)))?;
+    let running = running
+        .iter()
+        .map(|instance| InstanceVar::<H>::new_witness(cs.clone(), instance))
+        .collect::<Result<Vec<_>, _>>()?;
+    let latest = InstanceVar::<H>::new_witness(cs.clone(), latest)?;
+    let proof = FoldingProofVar::<H>::new_witness(cs.clone(), proof)?;
+    let is_base_case = i.is_eq(&zero)?;
+
+    // Check that the latest instance hashes the current state, and that its challenge is derived
+    // from its witness, unless there is none yet.
+    let running_terms = running
+        .iter()
+        .map(|instance| instance.terms())
+        .collect::<Result<Vec<_>, _>>()?;
+    let hash = hash_state_var(
+        cs.clone(),
+        oracle,
+        &params,
+        &i,
+        &zero,
+        &running_terms.concat(),
+    )?;
+    hash_bits::<H>(&latest.io[0])?.conditional_enforce_equal(&hash, &is_base_case.not())?;
+    latest.enforce_challenge(cs.clone(), oracle, &is_base_case.not())?;
+
+    // Select the running instance at the program counter. Exactly one of the flags should be set,
+    // which also ensures that the program counter is in range.
+    let flags = (0..running.len())
+        .map(|j| pc.is_eq(&FpVar::Constant(H::F2::from(j as u64))))
+        .collect::<Result<Vec<_>, _>>()?;
+    flags
+        .iter()
+        .fold(FpVar::zero(), |acc, flag| acc + FpVar::from(flag.clone()))
+        .enforce_equal(&FpVar::one())?;
+    let selected = flags
+        .iter()
+        .zip(&running)
+        .skip(1)
+        .try_fold(running[0].clone(), |acc, (flag, instance)| {
+            InstanceVar::select(flag, instance, &acc)
+        })?;
+
+    // Fold the latest instance into the selected running instance, and replace its terms.
+    let folded_terms = selected
+        .fold(cs.clone(), oracle, &params, &latest, &proof)?
+        .terms()?;
+    let terms = flags
+        .iter()
+        .zip(&running_terms)
+        .map(|(flag, terms)| select_all(flag, &folded_terms, terms))
+        .collect::<Result<Vec<_>, _>>()?
+        .concat();
+
+    let hash = hash_state_var(
+        cs.clone(),
+        oracle,
+        &params,
+        &(i + FpVar::one()),
+        &zero,
+        &terms,
+    )?;
+    new_hash_inputs(cs.clone(), [hash_bits::<H>(&latest.io[1])?, hash])?;
+    cs.finalize();
+    Ok(cs)
+}
+
+// An instance of the other circuit, allocated in the circuit over `H::F2`.
+#[derive(Clone)]
+struct InstanceVar<H: CurveCycle> {
+    commitments: Vec<PointVar<H>>,
+    comm_E: PointVar<H>,
+    u: ScalarVar<H>,
+    challenge: ScalarVar<H>,
+    io: Vec<ScalarVar<H>>,
+    point: Vec<ScalarVar<H>>,
+    evals: Vec<ScalarVar<H>>,
+}
+
+impl<H: CurveCycle> InstanceVar<H> {
+    fn new_witness<I: Instance<H>>(
+        cs: ConstraintSystemRef<H::F2>,
+        instance: &I,
+    ) -> Result<Self, SynthesisError> {
+        let (point, evals) = instance.evaluations();
+        Ok(Self {
+            commitments: instance
+                .witness_commitments()
+                .into_iter()
+                .map(|comm| PointVar::<H>::new_witness(cs.clone(), || Ok(comm)))
+                .collect::<Result<_, _>>()?,
+            comm_E: PointVar::<H>::new_witness(cs.clone(), || Ok(instance.error_commitment()))?,
+            u: ScalarVar::<H>::new_witness(cs.clone(), || Ok(instance.u()))?,
+            challenge: ScalarVar::<H>::new_witness(cs.clone(), || Ok(instance.challenge()))?,
+            io: new_scalars::<H>(cs.clone(), instance.io())?,
+            point: new_scalars::<H>(cs.clone(), point)?,
+            evals: new_scalars::<H>(cs, evals)?,
+        })
+    }
+
+    // Returns the terms used for hashing the instance, which match [`instance_terms`].
+    fn terms(&self) -> Result<Vec<FpVar<H::F2>>, SynthesisError> {
+        let mut terms = vec![];
+        for p in self.commitments.iter().chain([&self.comm_E]) {
+            terms.extend(p.to_affine()?.to_constraint_field()?);
+        }
+        for v in [&self.u, &self.challenge]
+            .into_iter()
+            .chain(&self.io)
+            .chain(&self.point)
+            .chain(&self.evals)
+        {
+            terms.extend(scalar_var_terms::<H>(v)?);
+        }
+        Ok(terms)
+    }
+
+    // Folds an unfolded instance into this one with the proof of its native folding, and returns
+    // the folded instance.
+    fn fold<R: RandomOracle<H::F2>>(
+        &self,
+        cs: ConstraintSystemRef<H::F2>,
+        oracle: &R,
+        params: &FpVar<H::F2>,
+        latest: &Self,
+        proof: &FoldingProofVar<H>,
+    ) -> Result<Self, SynthesisError> {
+        match proof {
+            FoldingProofVar::Crossterms(comm_T) => {
+                let (r_bits, r) = challenge_var(cs, oracle, params, self, latest, comm_T)?;
+                self.fold_crossterms(latest, comm_T, &r_bits, &r)
+            }
+            FoldingProofVar::MultiFolding {
+                rounds,
+                sigmas,
+                thetas,
+                multisets,
+                coefficients,
+            } => {
+                // Verify the sum-check, which reduces the claims of this instance and the
+                // constraints of the latest instance to claims at a new point, matching
+                // [`CCSInstance::fold`](crate::ccs::CCSInstance::fold).
+                let t = self.evals.len();
+                if self.point.len() != rounds.len() || sigmas.len() != t || thetas.len() != t {
+                    return Err(SynthesisError::Unsatisfiable);
+                }
+
+                let mut transcript = FoldingTranscriptVar::<H, R>::new(
+                    cs,
+                    oracle,
+                    [vec![params.clone()], self.terms()?, latest.terms()?].concat(),
+                );
+                let (_, gamma) = transcript.challenge()?;
+                let mut gammas = vec![ScalarVar::<H>::one()];
+                for _ in 0..t {
+                    gammas.push(gammas[gammas.len() - 1].clone() * &gamma);
+                }
+                let beta = (0..rounds.len())
+                    .map(|_| Ok(transcript.challenge()?.1))
+                    .collect::<Result<Vec<_>, SynthesisError>>()?;
+
+                let mut claim = inner_product::<H>(&gammas[..t], &self.evals);
+                let mut point = Vec::with_capacity(rounds.len());
+                for evals in rounds {
+                    (&evals[0] + &evals[1]).enforce_equal(&reduce_var::<H>(&claim))?;
+                    transcript.absorb_scalars(evals)?;
+                    let (_, r_i) = transcript.challenge()?;
+                    claim = interpolate_var::<H>(evals, &r_i);
+                    point.push(r_i);
+                }
+
+                let combined = multisets.iter().zip(coefficients).fold(
+                    ScalarVar::<H>::zero(),
+                    |acc, (set, c)| {
+                        acc + set
+                            .iter()
+                            .fold(ScalarVar::<H>::constant(*c), |acc, j| acc * &thetas[*j])
+                    },
+                );
+                let expected = eq_var::<H>(&self.point, &point)
+                    * inner_product::<H>(&gammas[..t], sigmas)
+                    + gammas[t].clone() * eq_var::<H>(&beta, &point) * combined;
+                reduce_var::<H>(&claim).enforce_equal(&reduce_var::<H>(&expected))?;
+
+                transcript.absorb_scalars(sigmas)?;
+                transcript.absorb_scalars(thetas)?;
+                let (rho_bits, rho) = transcript.challenge()?;
+
+                // Fold the commitments, scalars and IO like crossterms are folded, except that the
+                // error commitment is kept as is.
+                Ok(Self {
+                    commitments: self
+                        .commitments
+                        .iter()
+                        .zip(&latest.commitments)
+                        .map(|(comm, other)| Ok(comm.clone() + scalar_mul::<H>(other, &rho_bits)?))
+                        .collect::<Result<_, SynthesisError>>()?,
+                    comm_E: self.comm_E.clone(),
+                    u: &self.u + &rho,
+                    challenge: &self.challenge + &(&rho * &latest.challenge),
+                    io: self
+                        .io
+                        .iter()
+                        .zip(&latest.io)
+                        .map(|(x, other)| x + &(&rho * other))
+                        .collect(),
+                    point,
+                    evals: sigmas
+                        .iter()
+                        .zip(thetas)
+                        .map(|(sigma, theta)| sigma + &(&rho * theta))
+                        .collect(),
+                })
+            }
+        }
+    }
+
+    // Folds an unfolded instance into this one, given the commitment to the crossterms and the
+    // challenge, both as bits and as a scalar.
+    fn fold_crossterms(
+        &self,
+        latest: &Self,
+        comm_T: &PointVar<H>,
+        r_bits: &[Boolean<H::F2>],
+        r: &ScalarVar<H>,
+    ) -> Result<Self, SynthesisError> {
+        Ok(Self {
+            commitments: self
+                .commitments
+                .iter()
+                .zip(&latest.commitments)
+                .map(|(comm, other)| Ok(comm.clone() + scalar_mul::<H>(other, r_bits)?))
+                .collect::<Result<_, SynthesisError>>()?,
+            comm_E: self.comm_E.clone() + scalar_mul::<H>(comm_T, r_bits)?,
+            u: &self.u + r,
+            challenge: &self.challenge + &(r * &latest.challenge),
+            io: self
+                .io
+                .iter()
+                .zip(&latest.io)
+                .map(|(x, other)| x + &(r * other))
+                .collect(),
+            point: self.point.clone(),
+            evals: self.evals.clone(),
+        })
+    }
+
+    // Checks that the challenge of an unfolded instance is derived from its first witness
+    // commitment, matching [`witness_challenge`], if the condition holds.
+    fn enforce_challenge<R: RandomOracle<H::F2>>(
+        &self,
+        cs: ConstraintSystemRef<H::F2>,
+        oracle: &R,
+        cond: &Boolean<H::F2>,
+    ) -> Result<(), SynthesisError> {
+        let inputs = self.commitments[0].to_affine()?.to_constraint_field()?;
+        let hash = oracle.hash_var(cs, &inputs)?.to_bits_le()?;
+        let bits = self.challenge.to_bits_le()?;
+        bits[..NUM_CHALLENGE_BITS].conditional_enforce_equal(&hash[..NUM_CHALLENGE_BITS], cond)?;
+        for bit in &bits[NUM_CHALLENGE_BITS..] {
+            bit.conditional_enforce_equal(&Boolean::FALSE, cond)?;
+        }
+        Ok(())
+    }
+
+    fn select(cond: &Boolean<H::F2>, a: &Self, b: &Self) -> Result<Self, SynthesisError> {
+        Ok(Self {
+            commitments: a
+                .commitments
+                .iter()
+                .zip(&b.commitments)
+                .map(|(a, b)| PointVar::<H>::conditionally_select(cond, a, b))
+                .collect::<Result<_, _>>()?,
+            comm_E: PointVar::<H>::conditionally_select(cond, &a.comm_E, &b.comm_E)?,
+            u: ScalarVar::<H>::conditionally_select(cond, &a.u, &b.u)?,
+            challenge: ScalarVar::<H>::conditionally_select(cond, &a.challenge, &b.challenge)?,
+            io: select_scalars::<H>(cond, &a.io, &b.io)?,
+            point: select_scalars::<H>(cond, &a.point, &b.point)?,
+            evals: select_scalars::<H>(cond, &a.evals, &b.evals)?,
+        })
+    }
+}
+
+// The in-circuit representation of a proof of folding an instance.
+enum FoldingProofVar<H: CurveCycle> {
+    Crossterms(PointVar<H>),
+    MultiFolding {
+        rounds: Vec<Vec<ScalarVar<H>>>,
+        sigmas: Vec<ScalarVar<H>>,
+        thetas: Vec<ScalarVar<H>>,
+        multisets: Vec<Vec<usize>>,
+        coefficients: Vec<H::F1>,
+    },
+}
+
+impl<H: CurveCycle> FoldingProofVar<H> {
+    // Allocates the proof as witnesses. The multisets and coefficients are part of the shape, so
+    // they're constants of the circuit.
+    fn new_witness(
+        cs: ConstraintSystemRef<H::F2>,
+        proof: &FoldingProof<H>,
+    ) -> Result<Self, SynthesisError> {
+        Ok(match proof {
+            FoldingProof::Crossterms(comm_T) => {
+                Self::Crossterms(PointVar::<H>::new_witness(cs, || Ok(*comm_T))?)
+            }
+            FoldingProof::MultiFolding {
+                proof,
+                multisets,
+                coefficients,
+            } => Self::MultiFolding {
+                rounds: proof
+                    .sumcheck
+                    .rounds
+                    .iter()
+                    .map(|evals| new_scalars::<H>(cs.clone(), evals))
+                    .collect::<Result<_, _>>()?,
+                sigmas: new_scalars::<H>(cs.clone(), &proof.sigmas)?,
+                thetas: new_scalars::<H>(cs, &proof.thetas)?,
+                multisets: multisets.clone(),
+                coefficients: coefficients.clone(),
+            },
+        })
+    }
+}
+
+// The in-circuit counterpart of [`FoldingTranscript`].
+struct FoldingTranscriptVar<'a, H: CurveCycle, R: RandomOracle<H::F2>> {
+    cs: ConstraintSystemRef<H::F2>,
+    oracle: &'a R,
+    state: Vec<FpVar<H::F2>>,
+}
+
+impl<'a, H: CurveCycle, R: RandomOracle<H::F2>> FoldingTranscriptVar<'a, H, R> {
+    fn new(cs: ConstraintSystemRef<H::F2>, oracle: &'a R, state: Vec<FpVar<H::F2>>) -> Self {
+        Self { cs, oracle, state }
+    }
+
+    fn absorb_scalars(&mut self, scalars: &[ScalarVar<H>]) -> Result<(), SynthesisError> {
+        for v in scalars {
+            self.state.extend(scalar_var_terms::<H>(v)?);
+        }
+        Ok(())
+    }
+
+    // Returns the next challenge, both as bits and as a scalar.
+    #[allow(clippy::type_complexity)]
+    fn challenge(&mut self) -> Result<(Vec<Boolean<H::F2>>, ScalarVar<H>), SynthesisError> {
+        let challenge = self.oracle.hash_var(self.cs.clone(), &self.state)?;
+        let bits = challenge.to_bits_le()?[..NUM_CHALLENGE_BITS].to_vec();
+        self.state = vec![challenge];
+        let scalar = scalar_from_bits::<H>(self.cs.clone(), &bits)?;
+        Ok((bits, scalar))
+    }
+}
+
+fn new_scalars<H: CurveCycle>(
+    cs: ConstraintSystemRef<H::F2>,
+    values: &[H::F1],
+) -> Result<Vec<ScalarVar<H>>, SynthesisError> {
+    values
+        .iter()
+        .map(|v| ScalarVar::<H>::new_witness(cs.clone(), || Ok(v)))
+        .collect()
+}
+
+fn select_scalars<H: CurveCycle>(
+    cond: &Boolean<H::F2>,
+    a: &[ScalarVar<H>],
+    b: &[ScalarVar<H>],
+) -> Result<Vec<ScalarVar<H>>, SynthesisError> {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| ScalarVar::<H>::conditionally_select(cond, a, b))
+        .collect()
+}
+
+fn inner_product<H: CurveCycle>(a: &[ScalarVar<H>], b: &[ScalarVar<H>]) -> ScalarVar<H> {
+    a.iter()
+        .zip(b)
+        .fold(ScalarVar::<H>::zero(), |acc, (a, b)| acc + a * b)
+}
+
+// Brings a scalar back into normal form by multiplying it with a constant one, as the reduction
+// itself isn't exposed. Equality checks against interpolated scalars are otherwise unsatisfiable
+// for some honest values.
+fn reduce_var<H: CurveCycle>(v: &ScalarVar<H>) -> ScalarVar<H> {
+    v * ScalarVar::<H>::constant(H::F1::one())
+}
+
+// Evaluates eq(a, b) in-circuit, matching [`eq_eval`](crate::compression::polynomial::eq_eval).
+// Every factor `ab + (1 - a)(1 - b)` is computed as `2ab - a - b + 1`, which needs a single
+// multiplication.
+fn eq_var<H: CurveCycle>(a: &[ScalarVar<H>], b: &[ScalarVar<H>]) -> ScalarVar<H> {
+    a.iter().zip(b).fold(ScalarVar::<H>::one(), |acc, (a, b)| {
+        let ab = a * b;
+        acc * (ab.clone() + ab - a - b + H::F1::one())
+    })
+}
+
+// Evaluates the univariate polynomial defined by its evaluations at 0, 1, ..., d at the point `r`
+// in-circuit, matching [`interpolate`](crate::compression::polynomial::interpolate). The
+// denominators of the Lagrange basis are constants.
+fn interpolate_var<H: CurveCycle>(evals: &[ScalarVar<H>], r: &ScalarVar<H>) -> ScalarVar<H> {
+    (0..evals.len()).fold(ScalarVar::<H>::zero(), |acc, k| {
+        let (num, den) = (0..evals.len()).filter(|j| *j != k).fold(
+            (evals[k].clone(), H::F1::one()),
+            |(num, den), j| {
+                let j = H::F1::from(j as u64);
+                (num * (r.clone() - j), den * (H::F1::from(k as u64) - j))
+            },
+        );
+        acc + num * den.inverse().unwrap()
+    })
+}
+
+// Multiplies a point by a scalar given as little-endian bits. The scalar multiplication of
+// arkworks relies on incomplete addition, which is unsatisfiable for the point at infinity, so it
+// is swapped out for the generator, and the result is replaced by the point at infinity after.
+fn scalar_mul<H: CurveCycle>(
+    p: &PointVar<H>,
+    bits: &[Boolean<H::F2>],
+) -> Result<PointVar<H>, SynthesisError> {
+    let infinity = p.is_zero()?;
+    let generator = PointVar::<H>::constant(H::C1::GENERATOR.into());
+    let base = infinity.select(&generator, p)?;
+    infinity.select(&PointVar::<H>::zero(), &base.scalar_mul_le(bits.iter())?)
+}
+
+// Splits a non-native scalar into chunks which fit in the native field, matching
+// [`scalar_terms`].
+fn scalar_var_terms<H: CurveCycle>(v: &ScalarVar<H>) -> Result<Vec<FpVar<H::F2>>, SynthesisError> {
+    v.to_bits_le()?[..H::F1::MODULUS_BIT_SIZE as usize]
+        .chunks(H::F2::MODULUS_BIT_SIZE as usize - 1)
+        .map(Boolean::le_bits_to_fp_var)
+        .collect()
+}
+
+// Returns the bits of a hash output by the other circuit, and ensures that it is truncated.
+fn hash_bits<H: CurveCycle>(v: &ScalarVar<H>) -> Result<Vec<Boolean<H::F2>>, SynthesisError> {
+    let bits = v.to_bits_le()?;
+    for bit in &bits[NUM_HASH_BITS..] {
+        bit.enforce_equal(&Boolean::FALSE)?;
+    }
+    Ok(bits[..NUM_HASH_BITS].to_vec())
+}
+
+// Computes the hash of the state of an augmented circuit in-circuit, and returns its truncated
+// bits.
+fn hash_state_var<F: PrimeField, R: RandomOracle<F>>(
+    cs: ConstraintSystemRef<F>,
+    oracle: &R,
+    params: &FpVar<F>,
+    i: &FpVar<F>,
+    pc: &FpVar<F>,
+    terms: &[FpVar<F>],
+) -> Result<Vec<Boolean<F>>, SynthesisError> {
+    let inputs = [params.clone(), i.clone(), pc.clone()]
+        .into_iter()
+        .chain(terms.iter().cloned())
+        .collect::<Vec<_>>();
+    let hash = oracle.hash_var(cs, &inputs)?;
+    Ok(hash.to_bits_le()?[..NUM_HASH_BITS].to_vec())
+}
+
+// Computes the challenge for folding `latest` into `running` in-circuit. Returns its bits, which
+// are used for scalar multiplication, and the challenge as a scalar of the other field.
+#[allow(clippy::type_complexity)]
+fn challenge_var<H: CurveCycle, R: RandomOracle<H::F2>>(
+    cs: ConstraintSystemRef<H::F2>,
+    oracle: &R,
+    params: &FpVar<H::F2>,
+    running: &InstanceVar<H>,
+    latest: &InstanceVar<H>,
+    comm_T: &PointVar<H>,
+) -> Result<(Vec<Boolean<H::F2>>, ScalarVar<H>), SynthesisError> {
+    let inputs = [
+        vec![params.clone()],
+        running.terms()?,
+        latest.terms()?,
+        comm_T.to_affine()?.to_constraint_field()?,
+    ]
+    .concat();
+    let r = oracle.hash_var(cs.clone(), &inputs)?;
+    let r_bits = r.to_bits_le()?[..NUM_CHALLENGE_BITS].to_vec();
+    let r = scalar_from_bits::<H>(cs, &r_bits)?;
+    Ok((r_bits, r))
+}
+
+// Allocates the scalar of the other field with the given little-endian bits, and ensures it is
+// made up of the same bits.
+fn scalar_from_bits<H: CurveCycle>(
+    cs: ConstraintSystemRef<H::F2>,
+    bits: &[Boolean<H::F2>],
+) -> Result<ScalarVar<H>, SynthesisError> {
+    let scalar = ScalarVar::<H>::new_witness(cs, || {
+        let bits = bits
+            .iter()
+            .map(|bit| bit.value())
+            .collect::<Result<Vec<bool>, _>>()?;
+        H::F1::from_bigint(<H::F1 as PrimeField>::BigInt::from_bits_le(&bits))
+            .ok_or(SynthesisError::AssignmentMissing)
+    })?;
+    let scalar_bits = scalar.to_bits_le()?;
+    scalar_bits[..bits.len()].enforce_equal(bits)?;
+    for bit in &scalar_bits[bits.len()..] {
+        bit.enforce_equal(&Boolean::FALSE)?;
+    }
+    Ok(scalar)
+}
+
+// Allocates the given hashes as the public IO of the circuit.
+fn new_hash_inputs<F: PrimeField>(
+    cs: ConstraintSystemRef<F>,
+    hashes: [Vec<Boolean<F>>; 2],
+) -> Result<(), SynthesisError> {
+    for bits in hashes {
+        let hash = Boolean::le_bits_to_fp_var(&bits)?;
+        FpVar::<F>::new_input(cs.clone(), || hash.value())?.enforce_equal(&hash)?;
+    }
+    Ok(())
+}
+
+fn new_witnesses<F: PrimeField>(
+    cs: ConstraintSystemRef<F>,
+    values: &[F],
+) -> Result<Vec<FpVar<F>>, SynthesisError> {
+    values
+        .iter()
+        .map(|v| FpVar::<F>::new_witness(cs.clone(), || Ok(v)))
+        .collect()
+}
+
+fn select_all<F: PrimeField>(
+    cond: &Boolean<F>,
+    a: &[FpVar<F>],
+    b: &[FpVar<F>],
+) -> Result<Vec<FpVar<F>>, SynthesisError> {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| FpVar::<F>::conditionally_select(cond, a, b))
+        .collect()
+}
+
+// Converts a program counter computed in-circuit back into an index. Values which don't fit are
+// saturated, so that they are caught by the range check during verification.
+fn to_pc<F: PrimeField>(v: F) -> usize {
+    let v = v.into_bigint();
+    if v.as_ref()[1..].iter().any(|limb| *limb != 0) {
+        return usize::MAX;
+    }
+    usize::try_from(v.as_ref()[0]).unwrap_or(usize::MAX)
+}
@@ -1,25 +1,372 @@
 //! Commitment logic used for the creation of committed circuit structures.
+//! Additionally, includes the commitment schemes which circuits can be committed with.
 
-use ark_bls12_381::{Fq, G1Affine, G1Projective};
-use ark_ec::AffineRepr;
-use ark_ff::{PrimeField, UniformRand, Zero};
-use rand_core::OsRng;
+mod ipa;
+mod kzg;
+mod pedersen;
+
+pub use ipa::{IPAKey, IPA};
+pub use kzg::{KZGKey, KZG};
+pub use pedersen::Pedersen;
+
+use crate::{ProverError, RandomOracle};
+use ark_ec::{
+    short_weierstrass::{Affine, Projective, SWCurveConfig},
+    AffineRepr, CurveGroup, VariableBaseMSM,
+};
+use ark_ff::Field;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+
+/// A homomorphic commitment scheme for vectors of scalars. Commitments are always points on the
+/// curve `C`, so that they can be folded and hashed in-circuit the same way regardless of the
+/// scheme, while schemes differ in how their keys are set up and how commitments are opened.
+/// Openings treat the committed vector as the coefficients of a univariate polynomial, and prove
+/// its evaluation at a given point.
+pub trait CommitmentScheme<C: SWCurveConfig>: Clone + Send + Sync + 'static {
+    /// The key used for committing and opening.
+    type Key: Clone + Send + Sync + CanonicalSerialize + CanonicalDeserialize;
+    /// A proof that a commitment opens to a claimed evaluation.
+    type Opening: Clone + CanonicalSerialize + CanonicalDeserialize;
+
+    /// Derives a key for committing to vectors of up to `n` scalars from the label, so that every
+    /// party derives the same key. Fails for schemes which need a trusted setup, whose keys should
+    /// be loaded from an existing reference string instead.
+    fn setup(label: &[u8], n: usize) -> Result<Self::Key, ProverError>;
 
-pub fn create_generators(n: usize) -> Vec<G1Affine> {
-    let cap = n.next_power_of_two();
-    let mut gens: Vec<G1Affine> = Vec::with_capacity(cap);
-    for _ in 0..cap {
-        gens.push(G1Affine::rand(&mut OsRng {}));
+    /// Returns the bases which vectors are committed over.
+    fn bases(key: &Self::Key) -> &[Affine<C>];
+
+    /// Returns a digest of the key, which is bound into the circuit parameters.
+    fn digest(key: &Self::Key) -> [u8; 32] {
+        key_digest(Self::bases(key))
+    }
+
+    /// Commits to a vector of scalars, which should not outnumber the bases of the key.
+    fn commit(key: &Self::Key, scalars: &[C::ScalarField]) -> Result<Affine<C>, ProverError> {
+        commit(Self::bases(key), scalars)
     }
-    gens
+
+    /// Commits to several vectors of scalars at once.
+    fn commit_many(
+        key: &Self::Key,
+        scalars: &[&[C::ScalarField]],
+    ) -> Result<Vec<Affine<C>>, ProverError> {
+        commit_many(Self::bases(key), scalars)
+    }
+
+    /// Returns a commitment to the sum of the vectors committed to by `a` and `b`.
+    fn add(a: &Affine<C>, b: &Affine<C>) -> Affine<C> {
+        (*a + b).into()
+    }
+
+    /// Returns a commitment to the vector committed to by `a`, scaled by `s`.
+    fn scale(a: &Affine<C>, s: C::ScalarField) -> Affine<C> {
+        (*a * s).into()
+    }
+
+    /// Opens the commitment to `scalars` at `point`, and returns the evaluation alongside the
+    /// proof. Interactive openings derive their challenges with the random oracle.
+    fn open<R: RandomOracle<C::ScalarField>>(
+        key: &Self::Key,
+        oracle: &R,
+        comm: &Affine<C>,
+        scalars: &[C::ScalarField],
+        point: C::ScalarField,
+    ) -> Result<(C::ScalarField, Self::Opening), ProverError>;
+
+    /// Verifies that the commitment opens to `eval` at `point`.
+    fn verify<R: RandomOracle<C::ScalarField>>(
+        key: &Self::Key,
+        oracle: &R,
+        comm: &Affine<C>,
+        point: C::ScalarField,
+        eval: C::ScalarField,
+        opening: &Self::Opening,
+    ) -> bool;
 }
 
-pub fn commit(generators: &[G1Affine], scalars: &[Fq]) -> G1Affine {
-    scalars
+/// Deterministically derives `n` generators from a domain separator, so that every party can
+/// reproduce and audit the commitment key. Each generator is found by hashing the label, its
+/// index and a counter to an x-coordinate until it lies on the curve, after which the cofactor
+/// is cleared. Nobody knows the discrete logarithms between the resulting points, and the
+/// generators of a label are a prefix of any larger amount of them.
+pub fn create_generators_from_label<C: SWCurveConfig>(label: &[u8], n: usize) -> Vec<Affine<C>> {
+    extend_generators(label, &[], n)
+}
+
+// Extends the given bases with generators derived from the label until there are `n` of them,
+// continuing at the index of the first missing base. This pads keys for arguments which need a
+// power of two bases.
+pub(crate) fn extend_generators<C: SWCurveConfig>(
+    label: &[u8],
+    bases: &[Affine<C>],
+    n: usize,
+) -> Vec<Affine<C>> {
+    let extension = (bases.len() as u64..n as u64)
+        .into_par_iter()
+        .map(|index| hash_to_curve(label, index))
+        .collect::<Vec<_>>();
+    [bases, &extension].concat()
+}
+
+// Derives the additional generator that inner product arguments bind the inner product to, which
+// is separated from the generators of the same label.
+pub(crate) fn create_inner_product_generator<C: SWCurveConfig>(label: &[u8]) -> Affine<C> {
+    hash_to_curve(&[label, b"/U"].concat(), 0)
+}
+
+/// Returns a digest of a commitment key, which is bound into the circuit parameters so that they
+/// can't be used with a different key.
+pub fn key_digest<C: SWCurveConfig>(generators: &[Affine<C>]) -> [u8; 32] {
+    let mut bytes = vec![];
+    generators.serialize_compressed(&mut bytes).unwrap();
+    Sha256::digest(bytes).into()
+}
+
+/// Commits to a vector of scalars with a multi-scalar multiplication over the generators, using
+/// the bucket method of Pippenger. Fails if the scalars outnumber the generators.
+pub fn commit<C: SWCurveConfig>(
+    generators: &[Affine<C>],
+    scalars: &[C::ScalarField],
+) -> Result<Affine<C>, ProverError> {
+    check_size(generators, scalars)?;
+    Ok(Projective::<C>::msm_unchecked(generators, scalars).into_affine())
+}
+
+/// Commits to several vectors of scalars at once, and normalizes all of the commitments with a
+/// single batch inversion. Fails if any of the vectors outnumbers the generators.
+pub fn commit_many<C: SWCurveConfig>(
+    generators: &[Affine<C>],
+    scalars: &[&[C::ScalarField]],
+) -> Result<Vec<Affine<C>>, ProverError> {
+    for scalars in scalars {
+        check_size(generators, scalars)?;
+    }
+
+    let comms = scalars
         .par_iter()
-        .zip(generators)
-        .map(|(scalar, gen)| gen.mul_bigint(scalar.into_bigint()))
-        .reduce(G1Projective::zero, |a, b| a + b)
-        .into()
+        .map(|scalars| Projective::<C>::msm_unchecked(generators, scalars))
+        .collect::<Vec<Projective<C>>>();
+    Ok(Projective::<C>::normalize_batch(&comms))
+}
+
+// Ensures that there are enough generators to commit to the scalars, as the multi-scalar
+// multiplication would silently drop the scalars that are left over.
+fn check_size<C: SWCurveConfig>(
+    generators: &[Affine<C>],
+    scalars: &[C::ScalarField],
+) -> Result<(), ProverError> {
+    if scalars.len() > generators.len() {
+        return Err(ProverError::KeyTooSmall(scalars.len(), generators.len()));
+    }
+
+    Ok(())
+}
+
+// Evaluates the polynomial with the given coefficients at `point`.
+fn evaluate<F: Field>(coeffs: &[F], point: F) -> F {
+    coeffs
+        .iter()
+        .rev()
+        .fold(F::zero(), |acc, coeff| acc * point + coeff)
+}
+
+// Hashes to a point with try-and-increment. The label is prefixed with its length, so that
+// different labels can never produce the same hash inputs.
+fn hash_to_curve<C: SWCurveConfig>(label: &[u8], index: u64) -> Affine<C> {
+    (0u64..)
+        .find_map(|counter| {
+            let hash = Sha256::new()
+                .chain_update((label.len() as u64).to_le_bytes())
+                .chain_update(label)
+                .chain_update(index.to_le_bytes())
+                .chain_update(counter.to_le_bytes())
+                .finalize();
+            let x = C::BaseField::from_random_bytes(&hash)?;
+            let point = Affine::<C>::get_point_from_x_unchecked(x, hash[31] & 1 == 1)?;
+            let point = point.clear_cofactor();
+            (!point.is_zero()).then_some(point)
+        })
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cycle::{
+            bn254::GrumpkinConfig,
+            pasta::{Fq, PallasConfig},
+        },
+        poseidon_constants,
+    };
+    use ark_bn254::{Bn254, G1Affine, G2Affine};
+    use ark_crypto_primitives::sponge::Absorb;
+    use ark_ec::{AffineRepr, CurveGroup};
+    use ark_ff::{One, UniformRand};
+    use rand_core::OsRng;
+
+    // Samples random generators, which nobody can reproduce, so they're only fit for testing the
+    // commitments themselves.
+    fn create_generators<C: SWCurveConfig>(n: usize) -> Vec<Affine<C>> {
+        (0..n.next_power_of_two())
+            .map(|_| Affine::<C>::rand(&mut OsRng {}))
+            .collect()
+    }
+
+    fn generators_from_label<C: SWCurveConfig>() {
+        let generators = create_generators_from_label::<C>(b"supernova", 100);
+        assert_eq!(generators.len(), 100);
+        assert!(generators
+            .iter()
+            .all(|g| g.is_on_curve() && g.is_in_correct_subgroup_assuming_on_curve()));
+
+        // The same label should always produce the same key, and different labels should not.
+        assert_eq!(
+            generators,
+            create_generators_from_label::<C>(b"supernova", 100)
+        );
+        let other = create_generators_from_label::<C>(b"supernova2", 100);
+        assert!(generators.iter().zip(&other).all(|(a, b)| a != b));
+        assert_eq!(key_digest(&generators), key_digest(&generators.clone()));
+        assert_ne!(key_digest(&generators), key_digest(&other));
+
+        // Extending the generators yields the generators of a larger amount.
+        let extended = extend_generators(b"supernova", &generators, 128);
+        assert_eq!(
+            extended,
+            create_generators_from_label::<C>(b"supernova", 128)
+        );
+    }
+
+    #[test]
+    fn test_commit() {
+        let generators = create_generators::<PallasConfig>(64);
+        let scalars = (0..3)
+            .map(|_| {
+                (0..50)
+                    .map(|_| Fq::rand(&mut OsRng {}))
+                    .collect::<Vec<Fq>>()
+            })
+            .collect::<Vec<Vec<Fq>>>();
+
+        let expected = scalars
+            .iter()
+            .map(|scalars| {
+                scalars
+                    .iter()
+                    .zip(&generators)
+                    .map(|(s, g)| *g * s)
+                    .sum::<Projective<PallasConfig>>()
+                    .into_affine()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(commit(&generators, &scalars[0]).unwrap(), expected[0]);
+        let scalars = scalars.iter().map(Vec::as_slice).collect::<Vec<&[Fq]>>();
+        assert_eq!(commit_many(&generators, &scalars).unwrap(), expected);
+
+        // Scalars which outnumber the generators are rejected rather than truncated.
+        assert!(matches!(
+            commit(&generators[..40], scalars[0]),
+            Err(ProverError::KeyTooSmall(50, 40))
+        ));
+        assert!(matches!(
+            commit_many(&generators[..40], &scalars),
+            Err(ProverError::KeyTooSmall(50, 40))
+        ));
+    }
+
+    fn commitment_scheme<C: SWCurveConfig, S: CommitmentScheme<C>>(key: S::Key)
+    where
+        C::ScalarField: Absorb,
+    {
+        let oracle = poseidon_constants::<C::ScalarField>();
+        let a = (0..50)
+            .map(|_| C::ScalarField::rand(&mut OsRng {}))
+            .collect::<Vec<_>>();
+        let b = (0..50)
+            .map(|_| C::ScalarField::rand(&mut OsRng {}))
+            .collect::<Vec<_>>();
+        let r = C::ScalarField::rand(&mut OsRng {});
+
+        // Commitments should be homomorphic.
+        let (comm_a, comm_b) = (S::commit(&key, &a).unwrap(), S::commit(&key, &b).unwrap());
+        let sum = a
+            .iter()
+            .zip(&b)
+            .map(|(a, b)| *a + r * b)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            S::add(&comm_a, &S::scale(&comm_b, r)),
+            S::commit(&key, &sum).unwrap()
+        );
+        assert_eq!(
+            S::commit_many(&key, &[&a, &b]).unwrap(),
+            vec![comm_a, comm_b]
+        );
+
+        let point = C::ScalarField::rand(&mut OsRng {});
+        let (eval, opening) = S::open(&key, &oracle, &comm_a, &a, point).unwrap();
+        assert_eq!(eval, evaluate(&a, point));
+        assert!(S::verify(&key, &oracle, &comm_a, point, eval, &opening));
+        assert!(!S::verify(&key, &oracle, &comm_b, point, eval, &opening));
+        assert!(!S::verify(
+            &key,
+            &oracle,
+            &comm_a,
+            point,
+            eval + C::ScalarField::one(),
+            &opening
+        ));
+    }
+
+    #[test]
+    fn test_ipa_rounds() {
+        let oracle = poseidon_constants::<Fq>();
+        let key = <IPA as CommitmentScheme<PallasConfig>>::setup(b"supernova", 50).unwrap();
+        let a = (0..50).map(|_| Fq::rand(&mut OsRng {})).collect::<Vec<_>>();
+        let comm = IPA::commit(&key, &a).unwrap();
+        let point = Fq::rand(&mut OsRng {});
+        let (eval, opening) = IPA::open(&key, &oracle, &comm, &a, point).unwrap();
+
+        // Openings with more rounds than the key has generators for are rejected, rather than
+        // overflowing the length of the vector they open.
+        for rounds in [7, 64, 100] {
+            let mut opening = opening.clone();
+            opening.L.resize(rounds, opening.L[0]);
+            opening.R.resize(rounds, opening.R[0]);
+            assert!(!IPA::verify(&key, &oracle, &comm, point, eval, &opening));
+        }
+    }
+
+    #[test]
+    fn test_commitment_schemes() {
+        commitment_scheme::<PallasConfig, Pedersen>(Pedersen::setup(b"supernova", 50).unwrap());
+        commitment_scheme::<PallasConfig, IPA>(IPA::setup(b"supernova", 50).unwrap());
+
+        // KZG can't derive its key, so it's loaded from a reference string with a known `tau`.
+        assert!(matches!(
+            <KZG<Bn254> as CommitmentScheme<ark_bn254::g1::Config>>::setup(b"supernova", 50),
+            Err(ProverError::TrustedSetupRequired)
+        ));
+        let tau = ark_bn254::Fr::rand(&mut OsRng {});
+        let powers = (0..50)
+            .scan(ark_bn254::Fr::one(), |power, _| {
+                let g = (G1Affine::generator() * *power).into_affine();
+                *power *= tau;
+                Some(g)
+            })
+            .collect();
+        let g2 = G2Affine::generator();
+        let key = KZG::<Bn254>::from_srs(powers, g2, (g2 * tau).into_affine());
+        commitment_scheme::<ark_bn254::g1::Config, KZG<Bn254>>(key);
+    }
+
+    #[test]
+    fn test_generators_from_label() {
+        generators_from_label::<PallasConfig>();
+        generators_from_label::<GrumpkinConfig>();
+    }
 }
@@ -0,0 +1,120 @@
+//! Vector-Pedersen commitments which are opened with a Bulletproofs-style inner product argument,
+//! giving logarithmically sized openings without a trusted setup.
+
+use super::{
+    check_size, create_generators_from_label, create_inner_product_generator, evaluate,
+    CommitmentScheme,
+};
+use crate::{
+    compression::{InnerProductProof, Transcript},
+    ProverError, RandomOracle,
+};
+use ark_ec::short_weierstrass::{Affine, Projective, SWCurveConfig};
+use ark_ff::{Field, PrimeField, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+
+/// The commitment scheme which opens vector-Pedersen commitments with an inner product argument.
+#[derive(Clone, Debug)]
+pub struct IPA;
+
+/// The key of the inner product argument, which extends the generators used for committing with
+/// an additional generator for the inner product. The argument halves the committed vector every
+/// round, so the amount of generators is rounded up to a power of two.
+#[derive(CanonicalSerialize, CanonicalDeserialize)]
+pub struct IPAKey<C: SWCurveConfig> {
+    generators: Vec<Affine<C>>,
+    U: Affine<C>,
+}
+
+impl<C: SWCurveConfig> Clone for IPAKey<C> {
+    fn clone(&self) -> Self {
+        Self {
+            generators: self.generators.clone(),
+            U: self.U,
+        }
+    }
+}
+
+impl<C: SWCurveConfig> CommitmentScheme<C> for IPA {
+    type Key = IPAKey<C>;
+    type Opening = InnerProductProof<Projective<C>>;
+
+    fn setup(label: &[u8], n: usize) -> Result<Self::Key, ProverError> {
+        Ok(IPAKey {
+            generators: create_generators_from_label(label, n.next_power_of_two()),
+            U: create_inner_product_generator(label),
+        })
+    }
+
+    fn bases(key: &Self::Key) -> &[Affine<C>] {
+        &key.generators
+    }
+
+    fn open<R: RandomOracle<C::ScalarField>>(
+        key: &Self::Key,
+        oracle: &R,
+        comm: &Affine<C>,
+        scalars: &[C::ScalarField],
+        point: C::ScalarField,
+    ) -> Result<(C::ScalarField, Self::Opening), ProverError> {
+        let mut a = scalars.to_vec();
+        a.resize(scalars.len().next_power_of_two(), C::ScalarField::zero());
+        check_size(&key.generators, &a)?;
+        let b = powers(point, a.len());
+        let eval = evaluate(scalars, point);
+        let opening = InnerProductProof::prove(
+            &key.generators,
+            &key.U,
+            comm,
+            &a,
+            &b,
+            eval,
+            &mut transcript(oracle, point),
+        );
+        Ok((eval, opening))
+    }
+
+    fn verify<R: RandomOracle<C::ScalarField>>(
+        key: &Self::Key,
+        oracle: &R,
+        comm: &Affine<C>,
+        point: C::ScalarField,
+        eval: C::ScalarField,
+        opening: &Self::Opening,
+    ) -> bool {
+        // Every round halves the vector, so there can't be more rounds than the key allows.
+        let rounds = opening.L.len();
+        match key.generators.len().checked_ilog2() {
+            Some(max) if rounds <= max as usize => {}
+            _ => return false,
+        }
+
+        opening.verify(
+            &key.generators,
+            &key.U,
+            comm,
+            &powers(point, 1 << rounds),
+            eval,
+            &mut transcript(oracle, point),
+        )
+    }
+}
+
+// Starts the transcript of an opening at the given point. The inner product argument absorbs the
+// commitment and the evaluation itself.
+fn transcript<F: PrimeField, R: RandomOracle<F>>(oracle: &R, point: F) -> Transcript<'_, F, R> {
+    let mut transcript = Transcript::new(oracle);
+    transcript.absorb_scalars(&[point]);
+    transcript
+}
+
+// Returns the first `n` powers of `x`.
+fn powers<F: Field>(x: F, n: usize) -> Vec<F> {
+    (0..n)
+        .scan(F::one(), |power, _| {
+            let current = *power;
+            *power *= x;
+            Some(current)
+        })
+        .collect()
+}
@@ -0,0 +1,93 @@
+//! KZG commitments over the first group of a pairing, which have constant sized openings at the
+//! cost of a trusted setup.
+
+use super::{check_size, commit, evaluate, CommitmentScheme};
+use crate::{ProverError, RandomOracle};
+use ark_ec::{
+    pairing::Pairing,
+    short_weierstrass::{Affine, Projective, SWCurveConfig},
+    AffineRepr,
+};
+use ark_ff::Field;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use core::marker::PhantomData;
+
+/// The KZG commitment scheme over the pairing `E`, which commits on its first group.
+#[derive(Clone, Debug)]
+pub struct KZG<E>(PhantomData<E>);
+
+/// The structured reference string of KZG, consisting of the powers of a secret `tau` in the
+/// first group, and the generator and `tau` in the second group.
+#[derive(Clone, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct KZGKey<E: Pairing> {
+    powers: Vec<E::G1Affine>,
+    g2: E::G2Affine,
+    tau_g2: E::G2Affine,
+}
+
+impl<E: Pairing> KZG<E> {
+    /// Loads a key from the reference string of a trusted setup ceremony, which consists of the
+    /// powers `tau^i * G` of the generator of the first group, and the generator `H` of the second
+    /// group alongside `tau * H`. The reference string is trusted as is.
+    pub fn from_srs(powers: Vec<E::G1Affine>, g2: E::G2Affine, tau_g2: E::G2Affine) -> KZGKey<E> {
+        KZGKey { powers, g2, tau_g2 }
+    }
+}
+
+impl<E, C> CommitmentScheme<C> for KZG<E>
+where
+    E: Pairing<G1Affine = Affine<C>, G1 = Projective<C>, ScalarField = C::ScalarField>,
+    C: SWCurveConfig,
+{
+    type Key = KZGKey<E>;
+    type Opening = Affine<C>;
+
+    // KZG can't be set up transparently, as whoever knows `tau` can open commitments to anything,
+    // so its keys are loaded with `KZG::from_srs` instead.
+    fn setup(_label: &[u8], _n: usize) -> Result<Self::Key, ProverError> {
+        Err(ProverError::TrustedSetupRequired)
+    }
+
+    fn bases(key: &Self::Key) -> &[Affine<C>] {
+        &key.powers
+    }
+
+    fn open<R: RandomOracle<C::ScalarField>>(
+        key: &Self::Key,
+        _oracle: &R,
+        _comm: &Affine<C>,
+        scalars: &[C::ScalarField],
+        point: C::ScalarField,
+    ) -> Result<(C::ScalarField, Self::Opening), ProverError> {
+        check_size(&key.powers, scalars)?;
+
+        // Divide by `X - point` with synthetic division, which leaves the evaluation as the
+        // remainder.
+        let mut quotient = vec![C::ScalarField::ZERO; scalars.len().saturating_sub(1)];
+        let mut remainder = C::ScalarField::ZERO;
+        for (i, coeff) in scalars.iter().enumerate().rev() {
+            let next = *coeff + remainder * point;
+            if i > 0 {
+                quotient[i - 1] = next;
+            }
+            remainder = next;
+        }
+        debug_assert_eq!(remainder, evaluate(scalars, point));
+
+        Ok((remainder, commit(&key.powers, &quotient)?))
+    }
+
+    fn verify<R: RandomOracle<C::ScalarField>>(
+        key: &Self::Key,
+        _oracle: &R,
+        comm: &Affine<C>,
+        point: C::ScalarField,
+        eval: C::ScalarField,
+        opening: &Self::Opening,
+    ) -> bool {
+        // Check that e(comm - eval * G, H) = e(opening, tau * H - point * H).
+        let lhs = comm.into_group() - Affine::<C>::generator() * eval;
+        let rhs = key.tau_g2.into_group() - key.g2 * point;
+        E::pairing(lhs, key.g2) == E::pairing(*opening, rhs)
+    }
+}
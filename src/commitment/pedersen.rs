@@ -0,0 +1,46 @@
+//! Vector-Pedersen commitments over generators derived from a label, which are the default
+//! commitment scheme.
+
+use super::{check_size, commit, create_generators_from_label, evaluate, CommitmentScheme};
+use crate::{ProverError, RandomOracle};
+use ark_ec::short_weierstrass::{Affine, SWCurveConfig};
+
+/// The vector-Pedersen commitment scheme. Commitments are not succinctly openable, so an opening
+/// consists of the committed vector itself.
+#[derive(Clone, Debug)]
+pub struct Pedersen;
+
+impl<C: SWCurveConfig> CommitmentScheme<C> for Pedersen {
+    type Key = Vec<Affine<C>>;
+    type Opening = Vec<C::ScalarField>;
+
+    fn setup(label: &[u8], n: usize) -> Result<Self::Key, ProverError> {
+        Ok(create_generators_from_label(label, n))
+    }
+
+    fn bases(key: &Self::Key) -> &[Affine<C>] {
+        key
+    }
+
+    fn open<R: RandomOracle<C::ScalarField>>(
+        key: &Self::Key,
+        _oracle: &R,
+        _comm: &Affine<C>,
+        scalars: &[C::ScalarField],
+        point: C::ScalarField,
+    ) -> Result<(C::ScalarField, Self::Opening), ProverError> {
+        check_size(key, scalars)?;
+        Ok((evaluate(scalars, point), scalars.to_vec()))
+    }
+
+    fn verify<R: RandomOracle<C::ScalarField>>(
+        key: &Self::Key,
+        _oracle: &R,
+        comm: &Affine<C>,
+        point: C::ScalarField,
+        eval: C::ScalarField,
+        opening: &Self::Opening,
+    ) -> bool {
+        commit(key, opening).is_ok_and(|opened| opened == *comm) && evaluate(opening, point) == eval
+    }
+}
@@ -0,0 +1,741 @@
+//! A compression step for SuperNova proofs over the R1CS arithmetization, which replaces the
+//! witnesses of a proof with Spartan-style succinct arguments of their satisfiability. For every
+//! committed relaxed R1CS instance of the proof, the prover runs two sum-checks which reduce its
+//! constraints and the constraints on its lookups to a single evaluation of the witness, the
+//! inverses and the error vector, and opens those against their commitments with an inner product
+//! argument. A last opening checks that the inverses of every table sum up.
+//!
+//! The additional generator of the inner product argument is derived from the label of the public
+//! parameters, so that the verifier rebuilds the same key from the parameters alone.
+
+mod ipa;
+pub(crate) mod polynomial;
+mod sumcheck;
+
+pub use ipa::InnerProductProof;
+pub use sumcheck::SumcheckProof;
+pub(crate) use sumcheck::SumcheckTranscript;
+
+use crate::{
+    commitment::{create_inner_product_generator, extend_generators},
+    cycle::Swapped,
+    errors::VerificationError,
+    r1cs::{R1CSInstance, R1CSShape, R1CS},
+    Arithmetization, CommitmentScheme, CurveCycle, Proof, ProofInstance, ProverError, PublicParams,
+    RandomOracle,
+};
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ec::{
+    short_weierstrass::{Affine, Projective, SWCurveConfig},
+    CurveGroup,
+};
+use ark_ff::{Field, One, PrimeField, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use polynomial::{eq_eval, eq_evals, evaluate};
+use rayon::prelude::*;
+// A Fiat-Shamir transcript which derives the verifier challenges with a random oracle. Every
+// challenge hashes everything absorbed since the previous challenge alongside that challenge.
+pub(crate) struct Transcript<'a, F: PrimeField, R: RandomOracle<F>> {
+    oracle: &'a R,
+    state: Vec<F>,
+}
+
+impl<'a, F: PrimeField, R: RandomOracle<F>> Transcript<'a, F, R> {
+    pub(crate) fn new(oracle: &'a R) -> Self {
+        Self {
+            oracle,
+            state: vec![],
+        }
+    }
+
+    pub(crate) fn absorb_point<G: CurveGroup<ScalarField = F>>(&mut self, point: &G::Affine) {
+        let mut bytes = vec![];
+        point.serialize_compressed(&mut bytes).unwrap();
+        self.state
+            .extend(bytes.to_sponge_field_elements_as_vec::<F>());
+    }
+
+    pub(crate) fn absorb_scalars(&mut self, scalars: &[F]) {
+        self.state.extend_from_slice(scalars);
+    }
+
+    pub(crate) fn challenge(&mut self) -> F {
+        let challenge = self.oracle.hash(&self.state);
+        self.state = vec![challenge];
+        challenge
+    }
+}
+
+// The key used for opening the commitments of a compressed proof. The bases the commitments are
+// committed over are extended to the size of the largest layout with generators derived from the
+// label, as the inner product argument needs a power of two of them, and the additional generator
+// of the argument is derived from the label as well.
+struct CompressionKey<C: SWCurveConfig> {
+    generators: Vec<Affine<C>>,
+    U: Affine<C>,
+}
+
+impl<C: SWCurveConfig> CompressionKey<C> {
+    fn new<'a, F: PrimeField + 'a>(
+        label: &[u8],
+        bases: &[Affine<C>],
+        shapes: impl IntoIterator<Item = &'a R1CSShape<F>>,
+    ) -> Self {
+        let size = shapes
+            .into_iter()
+            .map(|shape| Layout::new(shape).size())
+            .max()
+            .unwrap_or(0);
+        Self {
+            generators: extend_generators(label, bases, size),
+            U: create_inner_product_generator(label),
+        }
+    }
+}
+
+/// A Spartan proof of satisfiability of a single committed relaxed R1CS instance, including the
+/// constraints on its lookups.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct SpartanProof<G: CurveCycle> {
+    outer: SumcheckProof<G::F1>,
+    claims: Vec<G::F1>,
+    inner: SumcheckProof<G::F1>,
+    eval_witness: G::F1,
+    eval_lookup: G::F1,
+    opening_witness: InnerProductProof<Projective<G::C1>>,
+    opening_lookup: InnerProductProof<Projective<G::C1>>,
+    opening_E: InnerProductProof<Projective<G::C1>>,
+    opening_balance: InnerProductProof<Projective<G::C1>>,
+}
+
+impl<G: CurveCycle> SpartanProof<G> {
+    // Proves that the instance-witness pair satisfies its shape, whose digest in the public
+    // parameters is `digest`. Fails if the key has fewer bases than the padded witness or error
+    // vector.
+    fn prove<P: CommitmentScheme<G::C1>, R: RandomOracle<G::F1>>(
+        key: &CompressionKey<G::C1>,
+        oracle: &R,
+        digest: G::F1,
+        pair: &R1CS<G, P>,
+    ) -> Result<Self, ProverError> {
+        let layout = Layout::new(&pair.shape);
+        let required = layout.size();
+        if required > key.generators.len() {
+            return Err(ProverError::KeyTooSmall(required, key.generators.len()));
+        }
+
+        let instance = pair.instance();
+        let mut transcript = Transcript::new(oracle);
+        absorb_instance(&mut transcript, digest, &instance);
+
+        // Run the first sum-check, which proves that
+        // sum_x eq(tau, x) * (Az(x) * Bz(x) - u * Cz(x) - E(x)) = 0.
+        let tau = (0..layout.log_m)
+            .map(|_| transcript.challenge())
+            .collect::<Vec<_>>();
+        let z = layout.z(pair);
+        let (az, bz, cz) = layout.eval_r1cs(&z);
+        let E = pad(&pair.E, 1 << layout.log_m);
+        let u = pair.u;
+        let (outer, rx, claims) = SumcheckProof::prove(
+            vec![eq_evals(&tau), az, bz, cz, E.clone()],
+            3,
+            |v| v[0] * (v[1] * v[2] - u * v[3] - v[4]),
+            &mut transcript,
+        );
+        let claims = claims[1..].to_vec();
+        transcript.absorb_scalars(&claims);
+
+        // Run the second sum-check, which proves that the claimed evaluations of Az, Bz and Cz at
+        // rx are correct, by batching them into
+        // sum_y (r_A * A(rx, y) + r_B * B(rx, y) + r_C * C(rx, y)) * z(y).
+        let (r_A, r_B, r_C) = (
+            transcript.challenge(),
+            transcript.challenge(),
+            transcript.challenge(),
+        );
+        let M = layout.bind_rows(&eq_evals(&rx), [r_A, r_B, r_C]);
+        let (inner, ry, _) = SumcheckProof::prove(vec![M, z], 2, |v| v[0] * v[1], &mut transcript);
+
+        // Open the witness, the inverses and the error vector at the points the sum-checks reduced
+        // to.
+        let W = pad(
+            &[pair.witness.as_slice(), &pair.multiplicities].concat(),
+            layout.n,
+        );
+        let inverses = pad(&pair.inverses, layout.n);
+        let eq_ry = eq_evals(&ry[2..]);
+        let eval_witness = evaluate(&W, &ry[2..]);
+        let eval_lookup = evaluate(&inverses, &ry[2..]);
+        transcript.absorb_scalars(&[eval_witness, eval_lookup]);
+        let opening_witness = InnerProductProof::prove(
+            &key.generators,
+            &key.U,
+            &pair.comm_witness,
+            &W,
+            &eq_ry,
+            eval_witness,
+            &mut transcript,
+        );
+        let opening_lookup = InnerProductProof::prove(
+            &key.generators,
+            &key.U,
+            &pair.comm_lookup,
+            &inverses,
+            &eq_ry,
+            eval_lookup,
+            &mut transcript,
+        );
+        let opening_E = InnerProductProof::prove(
+            &key.generators,
+            &key.U,
+            &pair.comm_E,
+            &E,
+            &eq_evals(&rx),
+            claims[3],
+            &mut transcript,
+        );
+
+        // Open a random combination of the sums of every table, which are all zero.
+        let rho = transcript.challenge();
+        let opening_balance = InnerProductProof::prove(
+            &key.generators,
+            &key.U,
+            &pair.comm_lookup,
+            &inverses,
+            &layout.balance(rho),
+            G::F1::zero(),
+            &mut transcript,
+        );
+
+        Ok(Self {
+            outer,
+            claims,
+            inner,
+            eval_witness,
+            eval_lookup,
+            opening_witness,
+            opening_lookup,
+            opening_E,
+            opening_balance,
+        })
+    }
+
+    // Verifies that the instance satisfies the given shape, whose digest in the public parameters
+    // is `digest`. The proof only fails with errors that carry no field elements, so they are
+    // returned over whichever field the caller reports in.
+    fn verify<F: Field, R: RandomOracle<G::F1>>(
+        &self,
+        key: &CompressionKey<G::C1>,
+        oracle: &R,
+        digest: G::F1,
+        shape: &R1CSShape<G::F1>,
+        instance: &R1CSInstance<G>,
+    ) -> Result<(), VerificationError<F>> {
+        let layout = Layout::new(shape);
+        if instance.io.len() + 1 != layout.num_public || self.claims.len() != 4 {
+            return Err(VerificationError::InvalidSumcheck);
+        }
+        if layout.size() > key.generators.len() {
+            return Err(VerificationError::InvalidOpening);
+        }
+
+        let mut transcript = Transcript::new(oracle);
+        absorb_instance(&mut transcript, digest, instance);
+
+        let tau = (0..layout.log_m)
+            .map(|_| transcript.challenge())
+            .collect::<Vec<_>>();
+        let (claim, rx) = self
+            .outer
+            .verify(G::F1::zero(), layout.log_m, 3, &mut transcript)
+            .ok_or(VerificationError::InvalidSumcheck)?;
+        let (az, bz, cz, E) = (
+            self.claims[0],
+            self.claims[1],
+            self.claims[2],
+            self.claims[3],
+        );
+        if claim != eq_eval(&tau, &rx) * (az * bz - instance.u * cz - E) {
+            return Err(VerificationError::InvalidSumcheck);
+        }
+        transcript.absorb_scalars(&self.claims);
+
+        let (r_A, r_B, r_C) = (
+            transcript.challenge(),
+            transcript.challenge(),
+            transcript.challenge(),
+        );
+        let (claim, ry) = self
+            .inner
+            .verify(
+                r_A * az + r_B * bz + r_C * cz,
+                layout.log_n + 2,
+                2,
+                &mut transcript,
+            )
+            .ok_or(VerificationError::InvalidSumcheck)?;
+
+        // Evaluate the matrices and z at the final point, which only requires the public parts
+        // of z, and the claimed evaluations of the witness and the inverses.
+        let eq_ry = eq_evals(&ry[2..]);
+        let M = layout.bind_rows(&eq_evals(&rx), [r_A, r_B, r_C]);
+        let eval_M = evaluate(&M, &ry);
+        let eval_public = [instance.u]
+            .iter()
+            .chain(&instance.io)
+            .chain([&instance.challenge])
+            .zip(&eq_ry)
+            .map(|(x, e)| *x * e)
+            .sum::<G::F1>();
+        let (r_0, r_1) = (ry[0], ry[1]);
+        let one = G::F1::one();
+        let eval_z = (one - r_0) * (one - r_1) * self.eval_witness
+            + (one - r_0) * r_1 * self.eval_lookup
+            + r_0 * (one - r_1) * eval_public;
+        if claim != eval_M * eval_z {
+            return Err(VerificationError::InvalidSumcheck);
+        }
+
+        transcript.absorb_scalars(&[self.eval_witness, self.eval_lookup]);
+        let openings = [
+            (
+                &self.opening_witness,
+                &instance.comm_witness,
+                &eq_ry,
+                self.eval_witness,
+            ),
+            (
+                &self.opening_lookup,
+                &instance.comm_lookup,
+                &eq_ry,
+                self.eval_lookup,
+            ),
+            (&self.opening_E, &instance.comm_E, &eq_evals(&rx), E),
+        ];
+        for (opening, comm, b, v) in openings {
+            if !opening.verify(&key.generators, &key.U, comm, b, v, &mut transcript) {
+                return Err(VerificationError::InvalidOpening);
+            }
+        }
+
+        let rho = transcript.challenge();
+        if !self.opening_balance.verify(
+            &key.generators,
+            &key.U,
+            &instance.comm_lookup,
+            &layout.balance(rho),
+            G::F1::zero(),
+            &mut transcript,
+        ) {
+            return Err(VerificationError::InvalidOpening);
+        }
+
+        Ok(())
+    }
+}
+
+/// A compressed SuperNova proof over the R1CS arithmetization, which holds the public part of the
+/// proof alongside a Spartan proof for each of the folded circuits, and for the running and latest
+/// instances of the secondary circuit.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct CompressedProof<G: CurveCycle, P: CommitmentScheme<G::C1> = crate::Pedersen> {
+    instance: ProofInstance<G, R1CS<G, P>>,
+    folded: Vec<SpartanProof<G>>,
+    folded_secondary: SpartanProof<Swapped<G>>,
+    latest_secondary: SpartanProof<Swapped<G>>,
+}
+
+impl<G: CurveCycle, P: CommitmentScheme<G::C1>, S: CommitmentScheme<G::C2>>
+    Proof<G, R1CS<G, P>, S>
+{
+    /// Compresses the proof into its public part and a Spartan proof of every instance-witness
+    /// pair, which can be verified without any witnesses. The keys of the proofs are derived from
+    /// the public parameters the proof was created with.
+    pub fn compress<R1: RandomOracle<G::F1>, R2: RandomOracle<G::F2>>(
+        &self,
+        params: &PublicParams<G, R1CS<G, P>, S, R1, R2>,
+    ) -> Result<CompressedProof<G, P>, ProverError> {
+        let key = CompressionKey::new(&params.label, P::bases(&params.key), &params.shapes);
+        let secondary_key = CompressionKey::new(
+            &params.label,
+            S::bases(&params.secondary_key),
+            [&params.secondary_shape],
+        );
+        let folded = self
+            .folded
+            .par_iter()
+            .zip(&params.digests)
+            .map(|(pair, &digest)| SpartanProof::prove(&key, &params.oracle, digest, pair))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(CompressedProof {
+            instance: self.instance(),
+            folded,
+            folded_secondary: SpartanProof::prove(
+                &secondary_key,
+                &params.secondary_oracle,
+                params.secondary_digest,
+                &self.folded_secondary,
+            )?,
+            latest_secondary: SpartanProof::prove(
+                &secondary_key,
+                &params.secondary_oracle,
+                params.secondary_digest,
+                &self.latest_secondary,
+            )?,
+        })
+    }
+}
+
+impl<G: CurveCycle, P: CommitmentScheme<G::C1>> CompressedProof<G, P> {
+    /// Returns the public part of the compressed proof, which holds the input and output of the
+    /// program.
+    pub fn instance(&self) -> &ProofInstance<G, R1CS<G, P>> {
+        &self.instance
+    }
+
+    /// Verify a compressed SuperNova proof against the public parameters it was created with,
+    /// which checks the public part of the proof like [`ProofInstance::verify`], and that all of
+    /// its instances are satisfied against the shapes of the parameters.
+    pub fn verify<S: CommitmentScheme<G::C2>, R1: RandomOracle<G::F1>, R2: RandomOracle<G::F2>>(
+        &self,
+        params: &PublicParams<G, R1CS<G, P>, S, R1, R2>,
+    ) -> Result<(), VerificationError<G::F2>> {
+        self.instance.verify(&params.verifier_key())?;
+
+        // If no computation has been folded yet, we can skip the other checks.
+        if self.instance.i == 0 {
+            return Ok(());
+        }
+
+        if self.folded.len() != params.shapes.len()
+            || self.instance.folded.len() != params.shapes.len()
+        {
+            return Err(VerificationError::UnsatisfiedCircuit);
+        }
+
+        let key = CompressionKey::new(&params.label, P::bases(&params.key), &params.shapes);
+        self.folded
+            .iter()
+            .zip(params.shapes.iter().zip(&params.digests))
+            .zip(&self.instance.folded)
+            .try_for_each(|((proof, (shape, &digest)), instance)| {
+                proof.verify(&key, &params.oracle, digest, shape, instance)
+            })?;
+
+        let secondary_key = CompressionKey::new(
+            &params.label,
+            S::bases(&params.secondary_key),
+            [&params.secondary_shape],
+        );
+        self.folded_secondary.verify(
+            &secondary_key,
+            &params.secondary_oracle,
+            params.secondary_digest,
+            &params.secondary_shape,
+            &self.instance.folded_secondary,
+        )?;
+        self.latest_secondary.verify(
+            &secondary_key,
+            &params.secondary_oracle,
+            params.secondary_digest,
+            &params.secondary_shape,
+            &self.instance.latest_secondary,
+        )
+    }
+}
+
+// Absorbs the instance alongside the digest of its shape, which binds the proof to the public
+// parameters it was created with.
+fn absorb_instance<G: CurveCycle, R: RandomOracle<G::F1>>(
+    transcript: &mut Transcript<G::F1, R>,
+    digest: G::F1,
+    instance: &R1CSInstance<G>,
+) {
+    transcript.absorb_scalars(&[digest]);
+    transcript.absorb_point::<Projective<G::C1>>(&instance.comm_witness);
+    transcript.absorb_point::<Projective<G::C1>>(&instance.comm_lookup);
+    transcript.absorb_point::<Projective<G::C1>>(&instance.comm_E);
+    transcript.absorb_scalars(&[instance.u, instance.challenge]);
+    transcript.absorb_scalars(&instance.io);
+}
+
+fn pad<F: Field>(v: &[F], n: usize) -> Vec<F> {
+    let mut v = v.to_vec();
+    v.resize(n, F::zero());
+    v
+}
+
+// Describes how a shape is laid out for the sum-checks. The rows are the constraints followed by
+// the constraints on the inverses, padded to a power of two `2^log_m`. z consists of four blocks
+// of a power of two `n`: the witness alongside the multiplicities, the inverses, the public values
+// u, the public inputs and the challenge, and zeros. The constraints on the inverses are written
+// as rows over z, which turns them into relaxed R1CS constraints.
+struct Layout<F: PrimeField> {
+    log_m: usize,
+    log_n: usize,
+    n: usize,
+    num_public: usize,
+    rows: [Vec<Vec<(F, usize)>>; 3],
+    // The table of every inverse, and the number of inverses of values looked up.
+    tables: Vec<usize>,
+    num_lookups: usize,
+}
+
+impl<F: PrimeField> Layout<F> {
+    fn new(shape: &R1CSShape<F>) -> Self {
+        let (matrices, lookups) = (&shape.matrices, &shape.lookups);
+        let num_rows = matrices.num_constraints + lookups.num_rows();
+        let m = num_rows.next_power_of_two();
+        let n = (matrices.num_witness_variables + lookups.num_entries())
+            .max(lookups.num_rows())
+            .max(matrices.num_instance_variables + 1)
+            .next_power_of_two();
+        let num_public = matrices.num_instance_variables;
+
+        // Maps an arkworks variable index, where public inputs come first, to an index into z.
+        let index = |var: usize| {
+            if var < num_public {
+                2 * n + var
+            } else {
+                var - num_public
+            }
+        };
+        let [mut a, mut b, mut c] = [&matrices.a, &matrices.b, &matrices.c].map(|m| {
+            m.iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|(coeff, var)| (*coeff, index(*var)))
+                        .collect()
+                })
+                .collect::<Vec<Vec<_>>>()
+        });
+
+        // A value looked up is constrained by h * (beta + w) = u * u + E, and a table entry by
+        // g * (beta + u * t) = u * m + E.
+        let (u, challenge) = (2 * n, 2 * n + num_public);
+        let mut tables = vec![];
+        for (k, (table, var)) in lookups.lookups.iter().enumerate() {
+            a.push(vec![(F::one(), n + k)]);
+            b.push(vec![(F::one(), challenge), (F::one(), *var)]);
+            c.push(vec![(F::one(), u)]);
+            tables.push(*table);
+        }
+        let entries = lookups
+            .tables
+            .iter()
+            .enumerate()
+            .flat_map(|(table, values)| values.iter().map(move |t| (table, t)));
+        for (e, (table, t)) in entries.enumerate() {
+            let (g, m) = (
+                n + lookups.lookups.len() + e,
+                matrices.num_witness_variables + e,
+            );
+            a.push(vec![(F::one(), g)]);
+            b.push(vec![(F::one(), challenge), (*t, u)]);
+            c.push(vec![(F::one(), m)]);
+            tables.push(table);
+        }
+
+        Self {
+            log_m: m.trailing_zeros() as usize,
+            log_n: n.trailing_zeros() as usize,
+            n,
+            num_public,
+            rows: [a, b, c],
+            tables,
+            num_lookups: lookups.lookups.len(),
+        }
+    }
+
+    // Returns the amount of generators needed for opening the padded witness and error vector.
+    fn size(&self) -> usize {
+        self.n.max(1 << self.log_m)
+    }
+
+    fn z<G: CurveCycle<F1 = F>, P: CommitmentScheme<G::C1>>(&self, pair: &R1CS<G, P>) -> Vec<F> {
+        let n = self.n;
+        let witness = [pair.witness.as_slice(), &pair.multiplicities].concat();
+        let mut z = vec![F::zero(); 4 * n];
+        z[..witness.len()].copy_from_slice(&witness);
+        z[n..n + pair.inverses.len()].copy_from_slice(&pair.inverses);
+        z[2 * n] = pair.u;
+        z[2 * n + 1..2 * n + self.num_public].copy_from_slice(&pair.instance);
+        z[2 * n + self.num_public] = pair.challenge;
+        z
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn eval_r1cs(&self, z: &[F]) -> (Vec<F>, Vec<F>, Vec<F>) {
+        let [a, b, c] = self.rows.each_ref().map(|m| {
+            let mut v = m
+                .par_iter()
+                .map(|row| row.iter().map(|(coeff, i)| *coeff * z[*i]).sum())
+                .collect::<Vec<F>>();
+            v.resize(1 << self.log_m, F::zero());
+            v
+        });
+        (a, b, c)
+    }
+
+    // Binds the rows of the batched matrix r_A * A + r_B * B + r_C * C to the given evaluations
+    // of eq(rx, .), returning a vector over the columns.
+    fn bind_rows(&self, eq_rx: &[F], r: [F; 3]) -> Vec<F> {
+        let mut M = vec![F::zero(); 4 * self.n];
+        for (m, r) in self.rows.iter().zip(r) {
+            for (row, eq) in m.iter().zip(eq_rx) {
+                for (coeff, i) in row {
+                    M[*i] += r * eq * coeff;
+                }
+            }
+        }
+        M
+    }
+
+    // Returns the vector whose inner product with the inverses sums the values looked up in every
+    // table minus its entries, weighted by powers of rho, which is zero if every table balances.
+    fn balance(&self, rho: F) -> Vec<F> {
+        let mut b = vec![F::zero(); self.n];
+        for (k, table) in self.tables.iter().enumerate() {
+            let weight = rho.pow([*table as u64]);
+            b[k] = if k < self.num_lookups {
+                weight
+            } else {
+                -weight
+            };
+        }
+        b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cycle::pasta::{Fq, PallasVesta},
+        Pedersen, PublicParamsBuilder, StepCircuit, Table,
+    };
+    use ark_r1cs_std::fields::fp::FpVar;
+    use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+
+    // Range checks the input in a table of nibbles, and increments it.
+    struct NibbleCircuit {
+        table: Table<Fq>,
+    }
+
+    impl StepCircuit<Fq> for NibbleCircuit {
+        fn arity(&self) -> usize {
+            1
+        }
+
+        fn synthesize(
+            &self,
+            cs: ConstraintSystemRef<Fq>,
+            z: &[FpVar<Fq>],
+        ) -> Result<(FpVar<Fq>, Vec<FpVar<Fq>>), SynthesisError> {
+            self.table.lookup(cs.clone(), &z[0])?;
+            Ok((
+                FpVar::Constant(Fq::zero()),
+                vec![&z[0] + FpVar::Constant(Fq::one())],
+            ))
+        }
+    }
+
+    fn build_params(circuit: &NibbleCircuit) -> PublicParams<PallasVesta, R1CS<PallasVesta>> {
+        PublicParamsBuilder::new(b"supernova", vec![Fq::one()])
+            .circuit(circuit)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_compression() {
+        let circuit = NibbleCircuit {
+            table: Table::range(4),
+        };
+        let params = build_params(&circuit);
+        let folded = R1CS::new(&params, 0, vec![Fq::one()], &circuit).unwrap();
+        let mut proof = Proof::new(&params, vec![folded], vec![Fq::one()]).unwrap();
+        let base = proof.instance();
+        for _ in 0..3 {
+            proof.update(&params, &circuit).unwrap();
+        }
+
+        // The verifier rebuilds the keys of the compressed proof from the parameters alone.
+        let compressed = proof.compress(&params).unwrap();
+        let mut bytes = vec![];
+        compressed.serialize_compressed(&mut bytes).unwrap();
+        let compressed =
+            CompressedProof::<PallasVesta>::deserialize_compressed(bytes.as_slice()).unwrap();
+        compressed.verify(&build_params(&circuit)).unwrap();
+        assert_eq!(compressed.instance().output(), [Fq::from(4u64)]);
+
+        // A base case which claims to have computed anything is rejected, as it isn't bound to
+        // any hash.
+        let mut tampered = compressed.clone();
+        tampered.instance = base;
+        tampered.verify(&params).unwrap();
+        tampered.instance.output = vec![Fq::from(4u64)];
+        assert!(matches!(
+            tampered.verify(&params),
+            Err(VerificationError::ExpectedBaseCase)
+        ));
+
+        // Changing the output breaks the hash of the latest secondary instance.
+        let mut tampered = compressed.clone();
+        tampered.instance.output = vec![Fq::from(5u64)];
+        assert!(matches!(
+            tampered.verify(&params),
+            Err(VerificationError::HashMismatch(_, _))
+        ));
+
+        // Inverses which satisfy their constraints through the error vector, but don't balance,
+        // are only caught by the balance check.
+        let key = CompressionKey::new(&params.label, Pedersen::bases(&params.key), &params.shapes);
+        let mut pair = proof.folded[0].clone();
+        let (_, index) = pair.shape.lookups.lookups[0];
+        let offset = pair.shape.matrices.num_constraints;
+        pair.inverses[0] += Fq::one();
+        pair.E[offset] += pair.challenge + pair.witness[index];
+        pair.comm_lookup = Pedersen::commit(&params.key, &pair.inverses).unwrap();
+        pair.comm_E = Pedersen::commit(&params.key, &pair.E).unwrap();
+        let digest = params.digests[0];
+        let spartan = SpartanProof::prove(&key, &params.oracle, digest, &pair).unwrap();
+        assert!(matches!(
+            spartan.verify::<Fq, _>(&key, &params.oracle, digest, &pair.shape, &pair.instance()),
+            Err(VerificationError::InvalidOpening)
+        ));
+
+        // Proofs are bound to the digest of the shape they were created for, so they can't be
+        // replayed against other parameters.
+        assert!(compressed.folded[0]
+            .verify::<Fq, _>(
+                &key,
+                &params.oracle,
+                digest + Fq::one(),
+                &params.shapes[0],
+                &compressed.instance.folded[0],
+            )
+            .is_err());
+        compressed.folded[0]
+            .verify::<Fq, _>(
+                &key,
+                &params.oracle,
+                digest,
+                &params.shapes[0],
+                &compressed.instance.folded[0],
+            )
+            .unwrap();
+
+        // The proof of either secondary instance doesn't hold for the other.
+        let mut tampered = compressed.clone();
+        tampered.latest_secondary = compressed.folded_secondary.clone();
+        assert!(matches!(
+            tampered.verify(&params),
+            Err(VerificationError::InvalidSumcheck)
+        ));
+    }
+}
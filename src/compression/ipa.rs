@@ -0,0 +1,135 @@
+//! An inner product argument in the style of Bulletproofs, which is used to open a vector-Pedersen
+//! commitment to a multilinear polynomial at a given point.
+
+use super::Transcript;
+use crate::RandomOracle;
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ff::{Field, PrimeField};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use rayon::prelude::*;
+
+/// A proof that a committed vector `a` satisfies `<a, b> = v` for a public vector `b`.
+#[derive(Clone, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct InnerProductProof<G: CurveGroup> {
+    pub(crate) L: Vec<G::Affine>,
+    pub(crate) R: Vec<G::Affine>,
+    pub(crate) a: G::ScalarField,
+}
+
+impl<G: CurveGroup> InnerProductProof<G> {
+    // Proves that `comm` commits to `a` under `generators`, and that `<a, b> = v`. The length of
+    // `a` and `b` needs to be a power of two.
+    pub(crate) fn prove<R: RandomOracle<G::ScalarField>>(
+        generators: &[G::Affine],
+        U: &G::Affine,
+        comm: &G::Affine,
+        a: &[G::ScalarField],
+        b: &[G::ScalarField],
+        v: G::ScalarField,
+        transcript: &mut Transcript<G::ScalarField, R>,
+    ) -> Self {
+        transcript.absorb_point::<G>(comm);
+        transcript.absorb_scalars(&[v]);
+        let U = U.mul_bigint(transcript.challenge().into_bigint());
+
+        let mut a = a.to_vec();
+        let mut b = b.to_vec();
+        let mut gens = generators[..a.len()].to_vec();
+        let mut L = vec![];
+        let mut R = vec![];
+        while a.len() > 1 {
+            let half = a.len() / 2;
+            let (a_L, a_R) = a.split_at(half);
+            let (b_L, b_R) = b.split_at(half);
+            let (G_L, G_R) = gens.split_at(half);
+
+            let L_i = G::msm_unchecked(G_R, a_L) + U * inner_product(a_L, b_R);
+            let R_i = G::msm_unchecked(G_L, a_R) + U * inner_product(a_R, b_L);
+            let [L_i, R_i] = [L_i.into_affine(), R_i.into_affine()];
+            transcript.absorb_point::<G>(&L_i);
+            transcript.absorb_point::<G>(&R_i);
+            let x = transcript.challenge();
+            let x_inv = x.inverse().unwrap();
+
+            a = fold(a_L, a_R, x, x_inv);
+            b = fold(b_L, b_R, x_inv, x);
+            gens = G::normalize_batch(
+                &G_L.par_iter()
+                    .zip(G_R)
+                    .map(|(l, r)| *l * x_inv + *r * x)
+                    .collect::<Vec<G>>(),
+            );
+            L.push(L_i);
+            R.push(R_i);
+        }
+
+        Self { L, R, a: a[0] }
+    }
+
+    // Verifies the proof against the commitment, the public vector and the claimed inner product.
+    pub(crate) fn verify<R: RandomOracle<G::ScalarField>>(
+        &self,
+        generators: &[G::Affine],
+        U: &G::Affine,
+        comm: &G::Affine,
+        b: &[G::ScalarField],
+        v: G::ScalarField,
+        transcript: &mut Transcript<G::ScalarField, R>,
+    ) -> bool {
+        if !b.len().is_power_of_two()
+            || b.len().trailing_zeros() as usize != self.L.len()
+            || self.L.len() != self.R.len()
+        {
+            return false;
+        }
+
+        transcript.absorb_point::<G>(comm);
+        transcript.absorb_scalars(&[v]);
+        let U = U.mul_bigint(transcript.challenge().into_bigint());
+
+        let mut P = comm.into_group() + U * v;
+        let mut challenges = Vec::with_capacity(self.L.len());
+        for (L_i, R_i) in self.L.iter().zip(&self.R) {
+            transcript.absorb_point::<G>(L_i);
+            transcript.absorb_point::<G>(R_i);
+            let x = transcript.challenge();
+            let x_inv = x.inverse().unwrap();
+            P += L_i.mul_bigint(x.square().into_bigint())
+                + R_i.mul_bigint(x_inv.square().into_bigint());
+            challenges.push((x, x_inv));
+        }
+
+        // Every generator and entry of `b` ends up being scaled by the product of either the
+        // challenge or its inverse of each round, depending on the half it was in.
+        let s = (0..b.len())
+            .map(|i| {
+                challenges
+                    .iter()
+                    .enumerate()
+                    .map(|(j, (x, x_inv))| {
+                        if (i >> (challenges.len() - 1 - j)) & 1 == 1 {
+                            *x
+                        } else {
+                            *x_inv
+                        }
+                    })
+                    .product()
+            })
+            .collect::<Vec<G::ScalarField>>();
+        let G_final = G::msm_unchecked(&generators[..b.len()], &s);
+        let b_final = inner_product(&s, b);
+
+        P == G_final * self.a + U * (self.a * b_final)
+    }
+}
+
+fn fold<F: Field>(left: &[F], right: &[F], l: F, r: F) -> Vec<F> {
+    left.iter()
+        .zip(right)
+        .map(|(a, b)| *a * l + *b * r)
+        .collect::<Vec<F>>()
+}
+
+fn inner_product<F: Field>(a: &[F], b: &[F]) -> F {
+    a.iter().zip(b).map(|(a, b)| *a * b).sum()
+}
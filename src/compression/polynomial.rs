@@ -0,0 +1,60 @@
+//! Helpers for working with multilinear polynomials, which are represented by their evaluations
+//! over the boolean hypercube. The first variable always corresponds to the most significant bit
+//! of an evaluation index.
+
+use ark_ff::PrimeField;
+
+// Returns the evaluations of eq(r, x) for every x in the boolean hypercube.
+pub(crate) fn eq_evals<F: PrimeField>(r: &[F]) -> Vec<F> {
+    let mut evals = vec![F::one()];
+    for r_i in r {
+        evals = evals
+            .iter()
+            .flat_map(|e| [*e * (F::one() - r_i), *e * r_i])
+            .collect::<Vec<F>>();
+    }
+    evals
+}
+
+// Evaluates eq(a, b) for two points of the same length.
+pub(crate) fn eq_eval<F: PrimeField>(a: &[F], b: &[F]) -> F {
+    a.iter()
+        .zip(b)
+        .map(|(a_i, b_i)| *a_i * b_i + (F::one() - a_i) * (F::one() - b_i))
+        .product()
+}
+
+// Fixes the first variable of a multilinear polynomial to `r`, halving the amount of evaluations.
+pub(crate) fn bind<F: PrimeField>(evals: &mut Vec<F>, r: F) {
+    let half = evals.len() / 2;
+    for i in 0..half {
+        evals[i] = evals[i] + r * (evals[i + half] - evals[i]);
+    }
+    evals.truncate(half);
+}
+
+// Evaluates a multilinear polynomial at the given point.
+pub(crate) fn evaluate<F: PrimeField>(evals: &[F], r: &[F]) -> F {
+    let mut evals = evals.to_vec();
+    r.iter().for_each(|r_i| bind(&mut evals, *r_i));
+    evals[0]
+}
+
+// Evaluates the univariate polynomial defined by its evaluations at 0, 1, ..., d at the point `r`,
+// using Lagrange interpolation.
+pub(crate) fn interpolate<F: PrimeField>(evals: &[F], r: F) -> F {
+    (0..evals.len())
+        .map(|k| {
+            let (num, den) =
+                (0..evals.len())
+                    .filter(|j| *j != k)
+                    .fold((F::one(), F::one()), |(num, den), j| {
+                        (
+                            num * (r - F::from(j as u64)),
+                            den * (F::from(k as u64) - F::from(j as u64)),
+                        )
+                    });
+            evals[k] * num * den.inverse().unwrap()
+        })
+        .sum()
+}
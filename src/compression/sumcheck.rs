@@ -0,0 +1,106 @@
+//! An implementation of the sum-check protocol over multilinear polynomials, which is used to
+//! reduce a claim about a sum over the boolean hypercube to a claim about a single point.
+
+use super::{
+    polynomial::{bind, interpolate},
+    Transcript,
+};
+use crate::RandomOracle;
+use ark_ff::PrimeField;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+
+// A transcript which the round polynomials are absorbed into, and which derives the challenges of
+// every round, so that a sum-check can be verified in a circuit over the other field of a cycle.
+pub(crate) trait SumcheckTranscript<F> {
+    fn absorb_scalars(&mut self, scalars: &[F]);
+
+    fn challenge(&mut self) -> F;
+}
+
+impl<F: PrimeField, R: RandomOracle<F>> SumcheckTranscript<F> for Transcript<'_, F, R> {
+    fn absorb_scalars(&mut self, scalars: &[F]) {
+        Transcript::absorb_scalars(self, scalars)
+    }
+
+    fn challenge(&mut self) -> F {
+        Transcript::challenge(self)
+    }
+}
+
+/// A sum-check proof, consisting of the evaluations of every round polynomial at the points
+/// 0, 1, ..., d, where d is the degree of the combining function.
+#[derive(Clone, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct SumcheckProof<F: PrimeField> {
+    pub(crate) rounds: Vec<Vec<F>>,
+}
+
+impl<F: PrimeField> SumcheckProof<F> {
+    // Proves that the sum of `comb` applied to the given polynomials over the boolean hypercube
+    // equals the claim. Returns the proof, the random point the claim was reduced to and the
+    // evaluations of each polynomial at that point.
+    pub(crate) fn prove<C: Fn(&[F]) -> F + Sync, T: SumcheckTranscript<F>>(
+        mut polys: Vec<Vec<F>>,
+        degree: usize,
+        comb: C,
+        transcript: &mut T,
+    ) -> (Self, Vec<F>, Vec<F>) {
+        let num_rounds = polys[0].len().trailing_zeros() as usize;
+        let mut rounds = Vec::with_capacity(num_rounds);
+        let mut r = Vec::with_capacity(num_rounds);
+        for _ in 0..num_rounds {
+            let half = polys[0].len() / 2;
+            let evals = (0..=degree)
+                .map(|t| {
+                    let t = F::from(t as u64);
+                    (0..half)
+                        .map(|i| {
+                            let point = polys
+                                .iter()
+                                .map(|p| p[i] + t * (p[i + half] - p[i]))
+                                .collect::<Vec<F>>();
+                            comb(&point)
+                        })
+                        .sum()
+                })
+                .collect::<Vec<F>>();
+
+            transcript.absorb_scalars(&evals);
+            let r_i = transcript.challenge();
+            polys.iter_mut().for_each(|p| bind(p, r_i));
+            rounds.push(evals);
+            r.push(r_i);
+        }
+
+        let evals = polys.iter().map(|p| p[0]).collect::<Vec<F>>();
+        (Self { rounds }, r, evals)
+    }
+
+    // Verifies the round polynomials against the claim, and returns the claim about the final
+    // random point along with the point itself.
+    pub(crate) fn verify<T: SumcheckTranscript<F>>(
+        &self,
+        claim: F,
+        num_rounds: usize,
+        degree: usize,
+        transcript: &mut T,
+    ) -> Option<(F, Vec<F>)> {
+        if self.rounds.len() != num_rounds {
+            return None;
+        }
+
+        let mut claim = claim;
+        let mut r = Vec::with_capacity(num_rounds);
+        for evals in &self.rounds {
+            if evals.len() != degree + 1 || evals[0] + evals[1] != claim {
+                return None;
+            }
+
+            transcript.absorb_scalars(evals);
+            let r_i = transcript.challenge();
+            claim = interpolate(evals, r_i);
+            r.push(r_i);
+        }
+
+        Some((claim, r))
+    }
+}
@@ -0,0 +1,37 @@
+//! Defines the cycles of elliptic curves which the SuperNova protocol can be instantiated over.
+//! Additionally, includes instantiations for the Pallas/Vesta and BN254/Grumpkin cycles.
+
+pub mod bn254;
+pub mod pasta;
+
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ec::short_weierstrass::SWCurveConfig;
+use ark_ff::PrimeField;
+use core::marker::PhantomData;
+
+/// A pair of curves where the scalar field of each curve is the base field of the other. Step
+/// circuits are defined over the scalar field of the primary curve and committed to on it, while
+/// a secondary circuit over the scalar field of the secondary curve verifies their folding, so
+/// that the points of either curve are only ever operated on natively in-circuit.
+pub trait CurveCycle: Clone + Send + Sync + 'static {
+    /// The scalar field of the primary curve, over which the step circuits are defined.
+    type F1: PrimeField + Absorb;
+    /// The scalar field of the secondary curve.
+    type F2: PrimeField + Absorb;
+    /// The primary curve.
+    type C1: SWCurveConfig<ScalarField = Self::F1, BaseField = Self::F2> + Clone;
+    /// The secondary curve, whose points have coordinates in the primary circuit field.
+    type C2: SWCurveConfig<ScalarField = Self::F2, BaseField = Self::F1> + Clone;
+}
+
+/// The cycle `G` with the roles of its curves swapped, over which the secondary circuit and its
+/// instances are defined.
+#[derive(Clone, Debug)]
+pub struct Swapped<G>(PhantomData<G>);
+
+impl<G: CurveCycle> CurveCycle for Swapped<G> {
+    type F1 = G::F2;
+    type F2 = G::F1;
+    type C1 = G::C2;
+    type C2 = G::C1;
+}
@@ -0,0 +1,69 @@
+//! The BN254/Grumpkin cycle of curves. Grumpkin is defined by `y^2 = x^3 - 17` over the scalar
+//! field of BN254, and its group order is the base field modulus of BN254.
+
+use super::CurveCycle;
+use ark_bn254::{Fq, Fr};
+use ark_ec::{
+    models::CurveConfig,
+    short_weierstrass::{Affine, Projective, SWCurveConfig},
+};
+use ark_ff::{Field, MontFp};
+
+pub type GrumpkinAffine = Affine<GrumpkinConfig>;
+pub type GrumpkinProjective = Projective<GrumpkinConfig>;
+
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct GrumpkinConfig;
+
+impl CurveConfig for GrumpkinConfig {
+    type BaseField = Fr;
+    type ScalarField = Fq;
+
+    const COFACTOR: &'static [u64] = &[0x1];
+    const COFACTOR_INV: Fq = Fq::ONE;
+}
+
+impl SWCurveConfig for GrumpkinConfig {
+    const COEFF_A: Fr = Fr::ZERO;
+    const COEFF_B: Fr = MontFp!("-17");
+    const GENERATOR: GrumpkinAffine = GrumpkinAffine::new_unchecked(
+        MontFp!("1"),
+        MontFp!("17631683881184975370165255887551781615748388533673675138860"),
+    );
+
+    #[inline(always)]
+    fn mul_by_a(_: Self::BaseField) -> Self::BaseField {
+        Self::BaseField::ZERO
+    }
+}
+
+/// The BN254/Grumpkin cycle, with BN254 as the primary curve. Step circuits are defined over the
+/// scalar field of BN254, and operate on Grumpkin points in-circuit.
+#[derive(Clone, Debug)]
+pub struct Bn254Grumpkin;
+
+impl CurveCycle for Bn254Grumpkin {
+    type F1 = Fr;
+    type F2 = Fq;
+    type C1 = ark_bn254::g1::Config;
+    type C2 = GrumpkinConfig;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ec::{AffineRepr, CurveGroup};
+    use ark_ff::PrimeField;
+
+    #[test]
+    fn test_grumpkin() {
+        assert!(GrumpkinAffine::generator().is_on_curve());
+
+        // Grumpkin has prime order, so the generator should be annihilated by the modulus of the
+        // BN254 base field.
+        assert!(GrumpkinAffine::generator()
+            .mul_bigint(Fq::MODULUS)
+            .into_affine()
+            .is_zero());
+    }
+}
@@ -0,0 +1,114 @@
+//! The Pallas/Vesta cycle of curves. Both curves are defined by `y^2 = x^3 + 5`, have prime order
+//! and use each other's base field as their scalar field.
+
+// The `MontConfig` derive of arkworks 0.4 implements the field configuration inside a function.
+#![allow(non_local_definitions)]
+
+use super::CurveCycle;
+use ark_ec::{
+    models::CurveConfig,
+    short_weierstrass::{Affine, Projective, SWCurveConfig},
+};
+use ark_ff::{
+    fields::{Fp256, MontBackend, MontConfig},
+    Field, MontFp,
+};
+
+#[derive(MontConfig)]
+#[modulus = "28948022309329048855892746252171976963363056481941560715954676764349967630337"]
+#[generator = "5"]
+pub struct FpMontConfig;
+/// The base field of Pallas, which is the scalar field of Vesta.
+pub type Fp = Fp256<MontBackend<FpMontConfig, 4>>;
+
+#[derive(MontConfig)]
+#[modulus = "28948022309329048855892746252171976963363056481941647379679742748393362948097"]
+#[generator = "5"]
+pub struct FqMontConfig;
+/// The base field of Vesta, which is the scalar field of Pallas.
+pub type Fq = Fp256<MontBackend<FqMontConfig, 4>>;
+
+pub type PallasAffine = Affine<PallasConfig>;
+pub type PallasProjective = Projective<PallasConfig>;
+pub type VestaAffine = Affine<VestaConfig>;
+pub type VestaProjective = Projective<VestaConfig>;
+
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct PallasConfig;
+
+impl CurveConfig for PallasConfig {
+    type BaseField = Fp;
+    type ScalarField = Fq;
+
+    const COFACTOR: &'static [u64] = &[0x1];
+    const COFACTOR_INV: Fq = Fq::ONE;
+}
+
+impl SWCurveConfig for PallasConfig {
+    const COEFF_A: Fp = Fp::ZERO;
+    const COEFF_B: Fp = MontFp!("5");
+    const GENERATOR: PallasAffine = PallasAffine::new_unchecked(MontFp!("-1"), MontFp!("2"));
+
+    #[inline(always)]
+    fn mul_by_a(_: Self::BaseField) -> Self::BaseField {
+        Self::BaseField::ZERO
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct VestaConfig;
+
+impl CurveConfig for VestaConfig {
+    type BaseField = Fq;
+    type ScalarField = Fp;
+
+    const COFACTOR: &'static [u64] = &[0x1];
+    const COFACTOR_INV: Fp = Fp::ONE;
+}
+
+impl SWCurveConfig for VestaConfig {
+    const COEFF_A: Fq = Fq::ZERO;
+    const COEFF_B: Fq = MontFp!("5");
+    const GENERATOR: VestaAffine = VestaAffine::new_unchecked(MontFp!("-1"), MontFp!("2"));
+
+    #[inline(always)]
+    fn mul_by_a(_: Self::BaseField) -> Self::BaseField {
+        Self::BaseField::ZERO
+    }
+}
+
+/// The Pallas/Vesta cycle, with Pallas as the primary curve. Step circuits are defined over the
+/// scalar field of Pallas, and operate on Vesta points in-circuit.
+#[derive(Clone, Debug)]
+pub struct PallasVesta;
+
+impl CurveCycle for PallasVesta {
+    type F1 = Fq;
+    type F2 = Fp;
+    type C1 = PallasConfig;
+    type C2 = VestaConfig;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ec::{AffineRepr, CurveGroup};
+    use ark_ff::PrimeField;
+
+    #[test]
+    fn test_pasta_cycle() {
+        assert!(PallasAffine::generator().is_on_curve());
+        assert!(VestaAffine::generator().is_on_curve());
+
+        // Both curves have prime order, so the generators should be annihilated by the modulus of
+        // the scalar field.
+        assert!(PallasAffine::generator()
+            .mul_bigint(Fq::MODULUS)
+            .into_affine()
+            .is_zero());
+        assert!(VestaAffine::generator()
+            .mul_bigint(Fp::MODULUS)
+            .into_affine()
+            .is_zero());
+    }
+}
@@ -1,6 +1,84 @@
 use ark_ff::Field;
+use ark_relations::r1cs::SynthesisError;
+use ark_serialize::SerializationError;
 use std::fmt::{Display, Formatter, Result};
 
+/// A list of possible errors that can occur while proving.
+#[derive(Debug)]
+pub enum ProverError {
+    Synthesis(SynthesisError),
+    Serialization(SerializationError),
+    UnsatisfiedStep,
+    PCOutOfRange(usize, usize),
+    ArityMismatch(usize, usize),
+    KeyTooSmall(usize, usize),
+    ShapeMismatch(usize),
+    ArithmetizationMismatch,
+    OpcodeMismatch(usize, usize),
+    UnsupportedLookup,
+    TrustedSetupRequired,
+    StepLimit(usize),
+    AssignmentMismatch(usize, usize),
+}
+
+impl From<SynthesisError> for ProverError {
+    fn from(e: SynthesisError) -> Self {
+        ProverError::Synthesis(e)
+    }
+}
+
+impl From<SerializationError> for ProverError {
+    fn from(e: SerializationError) -> Self {
+        ProverError::Serialization(e)
+    }
+}
+
+impl Display for ProverError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            ProverError::Synthesis(e) => write!(f, "ERROR: synthesis failed\n{e}"),
+            ProverError::Serialization(e) => write!(f, "ERROR: serialization failed\n{e}"),
+            ProverError::UnsatisfiedStep => write!(f, "ERROR: unsatisfied step circuit"),
+            ProverError::PCOutOfRange(counter, limit) => write!(
+                f,
+                "ERROR: program counter out of range\ncounter: {counter}\nlimit: {limit}"
+            ),
+            ProverError::ArityMismatch(arity, len) => write!(
+                f,
+                "ERROR: step circuit arity mismatch\narity: {arity}\nlength: {len}"
+            ),
+            ProverError::KeyTooSmall(required, available) => write!(
+                f,
+                "ERROR: commitment key too small\nrequired: {required}\navailable: {available}"
+            ),
+            ProverError::ShapeMismatch(pc) => write!(
+                f,
+                "ERROR: circuit does not match the public parameters\ncounter: {pc}"
+            ),
+            ProverError::ArithmetizationMismatch => {
+                write!(f, "ERROR: folded circuits of different arithmetizations")
+            }
+            ProverError::OpcodeMismatch(counter, opcode) => write!(
+                f,
+                "ERROR: program counter does not match the bytecode\ncounter: {counter}\nopcode: {opcode}"
+            ),
+            ProverError::UnsupportedLookup => {
+                write!(f, "ERROR: the arithmetization does not support lookups")
+            }
+            ProverError::TrustedSetupRequired => {
+                write!(f, "ERROR: the commitment scheme needs a trusted setup")
+            }
+            ProverError::StepLimit(limit) => {
+                write!(f, "ERROR: program did not halt within the step limit\nlimit: {limit}")
+            }
+            ProverError::AssignmentMismatch(expected, len) => write!(
+                f,
+                "ERROR: assignment does not match the shape\nexpected: {expected}\nlength: {len}"
+            ),
+        }
+    }
+}
+
 /// A list of possible errors that can occur during proof verification.
 #[derive(Debug)]
 pub enum VerificationError<F: Field> {
@@ -9,6 +87,12 @@ pub enum VerificationError<F: Field> {
     PCOutOfRange(usize, usize),
     UnexpectedCrossterms,
     UnsatisfiedCircuit,
+    CommitmentMismatch,
+    InvalidSumcheck,
+    InvalidOpening,
+    MemoryMismatch,
+    ShapeMismatch,
+    InstructionPointerOutOfRange,
 }
 
 impl<F: Field> Display for VerificationError<F> {
@@ -29,6 +113,25 @@ impl<F: Field> Display for VerificationError<F> {
                 write!(f, "ERROR: unexpected crossterms in unfolded circuit")
             }
             VerificationError::UnsatisfiedCircuit => write!(f, "ERROR: unsatisfied circuit"),
+            VerificationError::CommitmentMismatch => {
+                write!(f, "ERROR: commitments do not match the witness")
+            }
+            VerificationError::InvalidSumcheck => write!(f, "ERROR: invalid sum-check proof"),
+            VerificationError::InvalidOpening => {
+                write!(f, "ERROR: invalid commitment opening")
+            }
+            VerificationError::MemoryMismatch => {
+                write!(f, "ERROR: memory accesses are inconsistent")
+            }
+            VerificationError::ShapeMismatch => {
+                write!(f, "ERROR: circuits do not match the public parameters")
+            }
+            VerificationError::InstructionPointerOutOfRange => {
+                write!(
+                    f,
+                    "ERROR: execution did not start at the program or halt past it"
+                )
+            }
         }
     }
 }
@@ -5,176 +5,430 @@
 
 pub mod arithmetization;
 pub use arithmetization::*;
+mod circuit;
 mod commitment;
 pub use commitment::*;
+pub mod compression;
+pub mod cycle;
+pub use cycle::CurveCycle;
 mod errors;
-use errors::VerificationError;
+pub use errors::{ProverError, VerificationError};
+mod lookup;
+pub use lookup::*;
+mod machine;
+pub use machine::*;
+mod memory;
+pub use memory::*;
+mod oracle;
+pub use oracle::{poseidon_config, RandomOracle};
+mod params;
+pub use params::*;
+mod serialization;
+pub use serialization::SERIALIZATION_VERSION;
 
-use ark_bls12_381::{Fq, G1Affine};
-use ark_crypto_primitives::sponge::{
-    poseidon::{find_poseidon_ark_and_mds, PoseidonConfig, PoseidonSponge},
-    CryptographicSponge, FieldBasedCryptographicSponge,
-};
+use ark_crypto_primitives::sponge::poseidon::PoseidonConfig;
+use ark_ec::short_weierstrass::Affine;
 use ark_ff::{PrimeField, Zero};
+use circuit::{hash_state, program_digest, synthesize_secondary, truncate, NUM_HASH_BITS};
+use cycle::Swapped;
+use r1cs::{R1CSInstance, R1CS};
 
-/// A SuperNova proof, which keeps track of a variable amount of loose circuits,
-/// a most recent instance-witness pair, a program counter and the iteration
-/// that the proof is currently at.
-pub struct Proof<A: Arithmetization, const L: usize> {
-    constants: PoseidonConfig<Fq>,
-    generators: Vec<G1Affine>,
-    folded: [A; L],
-    latest: A,
-    prev_pc: usize,
+/// A SuperNova proof over the curve cycle `G`, which keeps track of the loose circuits on the
+/// primary curve, a running and a most recent instance-witness pair of the secondary circuit, a
+/// program counter and the iteration that the proof is currently at. The amount of circuits is
+/// only known at runtime, and they are kept in order of their program counter. The secondary
+/// circuit is committed to with the scheme `S`. The setup material of the proof is held
+/// by the [`PublicParams`] it is created with, which should be passed to every later call.
+pub struct Proof<G: CurveCycle, A: Arithmetization<G>, S: CommitmentScheme<G::C2> = Pedersen> {
+    folded: Vec<A>,
+    // The secondary circuit folds the primary instances, and has its own instances folded by the
+    // primary circuits in turn.
+    folded_secondary: R1CS<Swapped<G>, S>,
+    latest_secondary: R1CS<Swapped<G>, S>,
+    z0: Vec<G::F1>,
+    output: Vec<G::F1>,
     pc: usize,
     i: usize,
 }
 
-impl<A: Arithmetization, const L: usize> Proof<A, L> {
-    /// Instantiate a SuperNova proof by giving it the set of circuits
-    /// it should track.
-    pub fn new(folded: [A; L], latest: A, generators: Vec<G1Affine>) -> Self {
-        let (ark, mds) =
-            find_poseidon_ark_and_mds(Fq::MODULUS.const_num_bits() as u64, 2, 8, 43, 0);
-        Self {
-            constants: PoseidonConfig {
-                full_rounds: 8,
-                partial_rounds: 43,
-                alpha: 5,
-                ark,
-                mds,
-                rate: 2,
-                capacity: 1,
-            },
-            generators,
+impl<G: CurveCycle, A: Arithmetization<G>, S: CommitmentScheme<G::C2>> Proof<G, A, S> {
+    /// Instantiate a SuperNova proof by giving it the public parameters of the program, the set of
+    /// circuits it should track, and the initial input of the program. The program starts at the
+    /// circuit at program counter zero. Fails if the circuits don't match the ones the parameters
+    /// were built with.
+    pub fn new<R1: RandomOracle<G::F1>, R2: RandomOracle<G::F2>>(
+        params: &PublicParams<G, A, S, R1, R2>,
+        folded: Vec<A>,
+        z0: Vec<G::F1>,
+    ) -> Result<Self, ProverError> {
+        if let Some(pc) = (0..folded.len().max(params.digests.len()))
+            .find(|&pc| folded.get(pc).map(A::params) != params.digests.get(pc).copied())
+        {
+            return Err(ProverError::ShapeMismatch(pc));
+        }
+
+        // Synthesize the base case of the secondary circuit, which serves as its initial running
+        // instance. The latest primary instance is a placeholder, as nothing is checked in the base
+        // case, apart from the proofs of arithmetizations which are folded with one.
+        let running = folded.iter().map(A::instance).collect::<Vec<_>>();
+        let (latest, proof) =
+            folded[0].base_folding(&params.secondary_oracle, &params.key, G::F2::zero())?;
+        let cs = synthesize_secondary::<G, _, _>(
+            G::F2::zero(),
+            0,
+            0,
+            &running,
+            &latest,
+            &proof,
+            &params.secondary_oracle,
+        )?;
+        let mut folded_secondary =
+            R1CS::<Swapped<G>, S>::from_cs(cs, 0, vec![], &params.oracle, &params.secondary_key)?;
+        folded_secondary.param = params.secondary_digest;
+
+        Ok(Self {
             folded,
-            latest,
-            prev_pc: 0,
+            latest_secondary: folded_secondary.clone(),
+            folded_secondary,
+            output: z0.clone(),
+            z0,
             pc: 0,
-            i: 1,
-        }
+            i: 0,
+        })
     }
 
-    /// Update a SuperNova proof with a new invocation of the augmented step circuit.
-    pub fn update<C: Fn(A::ConstraintSystem, &[A::Input]) -> Vec<A::Input>>(
+    /// Returns the program counter, which is the index of the circuit that should be
+    /// passed to the next call to [`Proof::update`].
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Returns the initial input of the program.
+    pub fn z0(&self) -> &[G::F1] {
+        &self.z0
+    }
+
+    /// Returns the output of the latest step of the program, which is the initial input if no
+    /// steps have been folded yet.
+    pub fn output(&self) -> &[G::F1] {
+        &self.output
+    }
+
+    /// Returns the number of steps folded into the proof.
+    pub fn i(&self) -> usize {
+        self.i
+    }
+
+    /// Update a SuperNova proof with a new invocation of the augmented step circuit. The
+    /// circuit should be the one selected by the current program counter, and returns the
+    /// next program counter alongside its output. The proof is left as is if the step circuit
+    /// fails or selects a program counter out of range.
+    pub fn update<
+        C: StepCircuit<G::F1> + ?Sized,
+        R1: RandomOracle<G::F1>,
+        R2: RandomOracle<G::F2>,
+    >(
         &mut self,
-        pc: usize,
-        circuit: C,
-    ) {
-        // Fold in-circuit to produce new Arithmetization.
-        let new_latest = self.folded[self.pc].synthesize(
-            self.params(),
-            self.folded[self.prev_pc].hash_terms(),
-            self.latest.witness_commitment(),
-            self.latest.hash(),
+        params: &PublicParams<G, A, S, R1, R2>,
+        circuit: &C,
+    ) -> Result<(), ProverError> {
+        // Fold the latest secondary instance-witness pair natively, unless there is none yet, in
+        // which case the crossterms are discarded by the circuit.
+        let running_secondary = self.folded_secondary.instance();
+        let latest_secondary = self.latest_secondary.instance();
+        let mut folded_secondary = self.folded_secondary.clone();
+        let proof = if self.i == 0 {
+            FoldingProof::Crossterms(Affine::identity())
+        } else {
+            folded_secondary.fold(
+                &self.latest_secondary,
+                &params.oracle,
+                &params.secondary_key,
+                params.program_digest(),
+            )?
+        };
+
+        // Invoke the primary circuit, which verifies the folding of the secondary instance.
+        let shape = params
+            .shapes
+            .get(self.pc)
+            .ok_or(ProverError::PCOutOfRange(self.pc, params.shapes.len()))?;
+        let latest = A::synthesize(
+            params.program_digest(),
+            &self.z0,
+            &self.output,
             self.pc,
-            pc,
             self.i,
-            &self.constants,
-            &self.generators,
+            &running_secondary,
+            &latest_secondary,
+            &proof,
+            &params.oracle,
+            &params.secondary_oracle,
+            &params.key,
+            shape,
             circuit,
-        );
-        // Fold natively.
-        self.folded[self.pc].fold(
-            &self.latest,
-            &self.constants,
-            &self.generators,
-            self.params(),
-        );
-        self.latest = new_latest;
-        self.prev_pc = self.pc;
-        self.pc = pc;
+        )?;
+        if latest.pc() >= self.folded.len() {
+            return Err(ProverError::PCOutOfRange(latest.pc(), self.folded.len()));
+        }
+
+        // Fold the primary instance-witness pair natively into the invoked circuit, and invoke the
+        // secondary circuit to verify it. The pair is folded into a copy, which only replaces the
+        // running pair once nothing can fail anymore.
+        let running = self.folded.iter().map(A::instance).collect::<Vec<_>>();
+        let mut folded = self.folded[self.pc].clone();
+        let proof = folded.fold(
+            &latest,
+            &params.secondary_oracle,
+            &params.key,
+            folded_secondary.params(),
+        )?;
+        let cs = synthesize_secondary::<G, _, _>(
+            folded_secondary.params(),
+            self.i,
+            self.pc,
+            &running,
+            &latest.instance(),
+            &proof,
+            &params.secondary_oracle,
+        )?;
+        let mut latest_secondary =
+            R1CS::from_cs(cs, 0, vec![], &params.oracle, &params.secondary_key)?;
+        latest_secondary.param = folded_secondary.params();
+
+        self.folded[self.pc] = folded;
+        self.folded_secondary = folded_secondary;
+        self.latest_secondary = latest_secondary;
+        self.output = latest.output().to_vec();
+        self.pc = latest.pc();
         self.i += 1;
+        Ok(())
     }
 
-    /// Verify a SuperNova proof.
-    pub fn verify(&self) -> Result<(), VerificationError<Fq>> {
-        // If this is only the first iteration, we can skip the other checks, as no computation has
-        // been folded.
-        if self.i == 1 {
-            if self.folded.iter().any(|pair| pair.has_crossterms()) {
-                return Err(VerificationError::ExpectedBaseCase);
-            }
+    /// Returns the public part of the proof, which can be verified without any witnesses.
+    pub fn instance(&self) -> ProofInstance<G, A> {
+        ProofInstance {
+            folded: self.folded.iter().map(A::instance).collect(),
+            folded_secondary: self.folded_secondary.instance(),
+            latest_secondary: self.latest_secondary.instance(),
+            z0: self.z0.clone(),
+            output: self.output.clone(),
+            pc: self.pc,
+            i: self.i,
+        }
+    }
 
-            if self.latest.has_crossterms() {
-                return Err(VerificationError::ExpectedBaseCase);
-            }
+    /// Verify a SuperNova proof against the public parameters it was created with.
+    pub fn verify<R1: RandomOracle<G::F1>, R2: RandomOracle<G::F2>>(
+        &self,
+        params: &PublicParams<G, A, S, R1, R2>,
+    ) -> Result<(), VerificationError<G::F2>> {
+        self.instance().verify(&params.verifier_key())?;
 
+        // If no computation has been folded yet, we can skip the other checks.
+        if self.i == 0 {
             return Ok(());
         }
 
-        // Check that the public IO of the latest instance includes the correct hash.
-        let hash = self.hash_public_io();
-        if self.latest.hash() != hash {
-            return Err(VerificationError::HashMismatch(hash, self.latest.hash()));
-        }
-
-        // Ensure PC is within range.
-        if self.pc > self.folded.len() {
-            return Err(VerificationError::PCOutOfRange(self.pc, self.folded.len()));
+        // Ensure all instance/witness pairs have the shapes the parameters were built with, as
+        // they are checked against the shapes they carry.
+        if self.folded.len() != params.shapes.len()
+            || self
+                .folded
+                .iter()
+                .zip(&params.shapes)
+                .any(|(pair, shape)| !pair.has_shape(shape))
+            || !self.folded_secondary.has_shape(&params.secondary_shape)
+            || !self.latest_secondary.has_shape(&params.secondary_shape)
+        {
+            return Err(VerificationError::ShapeMismatch);
         }
 
-        // Ensure the latest instance has no crossterms.
-        if self.latest.has_crossterms() {
-            return Err(VerificationError::UnexpectedCrossterms);
+        // Ensure the commitments of all instance/witness pairs match their witnesses.
+        if self
+            .folded
+            .iter()
+            .any(|pair| !pair.commitments_match(&params.key))
+            || !self
+                .folded_secondary
+                .commitments_match(&params.secondary_key)
+            || !self
+                .latest_secondary
+                .commitments_match(&params.secondary_key)
+        {
+            return Err(VerificationError::CommitmentMismatch);
         }
 
         // Ensure all folded instance/witness pairs are satisfied.
         if self
             .folded
             .iter()
-            .any(|pair| !pair.is_satisfied(&self.generators))
+            .any(|pair| !pair.is_satisfied(&params.key))
         {
             return Err(VerificationError::UnsatisfiedCircuit);
         }
 
-        // Ensure the latest instance/witness pair is satisfied.
-        if !self.latest.is_satisfied(&self.generators) {
+        // Ensure the secondary instance/witness pairs are satisfied.
+        if !self.folded_secondary.is_satisfied(&params.secondary_key)
+            || !self.latest_secondary.is_satisfied(&params.secondary_key)
+        {
             return Err(VerificationError::UnsatisfiedCircuit);
         }
 
         Ok(())
     }
+}
 
-    // Returns a sum of the parameter hashes of all circuits.
-    fn params(&self) -> Fq {
-        self.folded
-            .iter()
-            .map(|p| p.params())
-            .fold(Fq::zero(), |acc, x| acc + x)
+// Returns the Poseidon constants used for hashing over the given field by default.
+pub(crate) fn poseidon_constants<F: PrimeField>() -> PoseidonConfig<F> {
+    poseidon_config(2, 8, 43, 5)
+}
+
+/// The key for verifying a [`ProofInstance`], which holds the parameter hashes of all circuits
+/// and the random oracles used for hashing over either field of the cycle.
+#[derive(Clone)]
+pub struct VerifierKey<
+    G: CurveCycle,
+    R1: RandomOracle<G::F1> = PoseidonConfig<<G as CurveCycle>::F1>,
+    R2: RandomOracle<G::F2> = PoseidonConfig<<G as CurveCycle>::F2>,
+> {
+    params: Vec<G::F1>,
+    secondary_params: G::F2,
+    oracle: R1,
+    secondary_oracle: R2,
+}
+
+/// The public part of a SuperNova proof, which holds the instances of all circuits without their
+/// witnesses, along with the input and output of the program, the program counter and the
+/// iteration that the proof is currently at.
+#[derive(Clone)]
+pub struct ProofInstance<G: CurveCycle, A: Arithmetization<G>> {
+    folded: Vec<A::Instance>,
+    folded_secondary: R1CSInstance<Swapped<G>>,
+    latest_secondary: R1CSInstance<Swapped<G>>,
+    z0: Vec<G::F1>,
+    output: Vec<G::F1>,
+    pc: usize,
+    i: usize,
+}
+
+impl<G: CurveCycle, A: Arithmetization<G>> ProofInstance<G, A> {
+    /// Returns the initial input of the program.
+    pub fn z0(&self) -> &[G::F1] {
+        &self.z0
     }
 
-    // Returns a hash of the 'public IO' for verification purposes. This hash should match the hash
-    // created in the augmented step circuit.
-    fn hash_public_io(&self) -> Fq {
-        let mut sponge = PoseidonSponge::<Fq>::new(&self.constants);
-        sponge.absorb(
-            &[self
-                .folded
-                .iter()
-                .fold(Fq::zero(), |acc, pair| acc + pair.params())]
-            .into_iter()
-            .chain([Fq::from(self.i as u64)])
-            .chain([Fq::from(self.pc as u64)])
-            .chain(self.folded[self.prev_pc].z0())
-            .chain(self.folded[self.prev_pc].output().to_vec())
-            .chain([
-                self.folded[self.prev_pc].witness_commitment().x,
-                self.folded[self.prev_pc].witness_commitment().y,
-                Fq::from(self.folded[self.prev_pc].witness_commitment().infinity),
-            ])
-            .chain(self.folded[self.prev_pc].crossterms())
-            .chain([self.folded[self.prev_pc].hash()])
-            .collect::<Vec<Fq>>(),
+    /// Returns the output of the latest step of the program. This is only what the program
+    /// computed once the instance has been verified.
+    pub fn output(&self) -> &[G::F1] {
+        &self.output
+    }
+
+    /// Returns the program counter, which selects the circuit of the next step.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Returns the number of steps folded into the proof.
+    pub fn i(&self) -> usize {
+        self.i
+    }
+
+    /// Verify the public part of a SuperNova proof. This checks everything except for the
+    /// satisfiability of the witnesses.
+    pub fn verify<R1: RandomOracle<G::F1>, R2: RandomOracle<G::F2>>(
+        &self,
+        key: &VerifierKey<G, R1, R2>,
+    ) -> Result<(), VerificationError<G::F2>> {
+        // Ensure PC is within range.
+        if self.pc >= self.folded.len() {
+            return Err(VerificationError::PCOutOfRange(self.pc, self.folded.len()));
+        }
+
+        // If no computation has been folded yet, we can skip the other checks. The base case isn't
+        // bound to any hash, so it can't claim to have computed anything.
+        if self.i == 0 {
+            if self.output != self.z0
+                || self.pc != 0
+                || self.folded.iter().any(|instance| instance.has_crossterms())
+                || self.folded_secondary.has_crossterms()
+            {
+                return Err(VerificationError::ExpectedBaseCase);
+            }
+
+            return Ok(());
+        }
+
+        // Check that the public IO of the latest secondary instance includes the correct hashes
+        // of the states of both circuits.
+        let (primary_hash, secondary_hash) = self.hash_public_io(key);
+        if self.latest_secondary.io[0] != primary_hash {
+            return Err(VerificationError::HashMismatch(
+                primary_hash,
+                self.latest_secondary.io[0],
+            ));
+        }
+
+        if self.latest_secondary.io[1] != secondary_hash {
+            return Err(VerificationError::HashMismatch(
+                secondary_hash,
+                self.latest_secondary.io[1],
+            ));
+        }
+
+        // Ensure the latest instance has no crossterms.
+        if self.latest_secondary.has_crossterms() {
+            return Err(VerificationError::UnexpectedCrossterms);
+        }
+
+        Ok(())
+    }
+
+    // Returns the hashes of the states of the primary and secondary circuits, both as elements of
+    // the secondary field. These should match the hashes created in the augmented circuits.
+    fn hash_public_io<R1: RandomOracle<G::F1>, R2: RandomOracle<G::F2>>(
+        &self,
+        key: &VerifierKey<G, R1, R2>,
+    ) -> (G::F2, G::F2) {
+        let params = program_digest(&key.oracle, &key.params);
+        let terms = [
+            self.z0.clone(),
+            self.output.clone(),
+            self.folded_secondary.hash_terms(),
+        ]
+        .concat();
+        let primary_hash = hash_state(&key.oracle, params, self.i, self.pc, &terms);
+
+        let terms = self
+            .folded
+            .iter()
+            .flat_map(|instance| instance.hash_terms())
+            .collect::<Vec<G::F2>>();
+        let secondary_hash = hash_state(
+            &key.secondary_oracle,
+            key.secondary_params,
+            self.i,
+            0,
+            &terms,
         );
-        sponge.squeeze_native_field_elements(1)[0]
+
+        (truncate(primary_hash, NUM_HASH_BITS), secondary_hash)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::r1cs::R1CS;
+    use crate::{
+        ccs::CCS,
+        cycle::{
+            bn254::Bn254Grumpkin,
+            pasta::{Fq, PallasVesta},
+        },
+        either::Either,
+        plonk::Plonk,
+        r1cs::{R1CSShape, SerializableShape},
+    };
     use ark_ff::One;
     use ark_r1cs_std::{
         alloc::AllocVar,
@@ -182,109 +436,656 @@ mod tests {
         fields::{fp::FpVar, FieldVar},
         R1CSVar,
     };
-    use ark_relations::r1cs::ConstraintSystemRef;
+    use ark_relations::r1cs::{ConstraintMatrices, ConstraintSystemRef, SynthesisError};
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError, Valid};
     use core::ops::{Add, Mul};
 
-    fn cubic_circuit(cs: ConstraintSystemRef<Fq>, z: &[FpVar<Fq>]) -> Vec<FpVar<Fq>> {
-        // Consider a cubic equation: `x^3 + x + 5 = y`, where `x` and `y` are respectively the
-        // input and output.
-        let x = FpVar::<_>::new_input(cs.clone(), || Ok(z[0].value().unwrap())).unwrap();
-        let x_sq = x.square().unwrap();
-        let x_cu = x_sq.mul(&x);
-        let y = FpVar::<_>::new_witness(cs.clone(), || {
-            Ok(x_cu.value().unwrap() + x.value().unwrap() + Fq::from(5u64))
-        })
-        .unwrap();
-        x_cu.add(&x)
-            .add(&FpVar::<_>::one())
-            .add(&FpVar::<_>::one())
-            .add(&FpVar::<_>::one())
-            .add(&FpVar::<_>::one())
-            .add(&FpVar::<_>::one())
-            .enforce_equal(&y)
+    // Consider a cubic equation: `x^3 + x + 5 = y`, where `x` and `y` are respectively the input
+    // and output. The circuit selects the circuit at `next_pc` next.
+    struct CubicCircuit {
+        next_pc: u64,
+    }
+
+    impl<F: PrimeField> StepCircuit<F> for CubicCircuit {
+        fn arity(&self) -> usize {
+            1
+        }
+
+        fn synthesize(
+            &self,
+            cs: ConstraintSystemRef<F>,
+            z: &[FpVar<F>],
+        ) -> Result<(FpVar<F>, Vec<FpVar<F>>), SynthesisError> {
+            let x = FpVar::<_>::new_witness(cs.clone(), || z[0].value())?;
+            let x_sq = x.square()?;
+            let x_cu = x_sq.mul(&x);
+            let y = FpVar::<_>::new_witness(cs.clone(), || {
+                Ok(x_cu.value()? + x.value()? + F::from(5u64))
+            })?;
+            x_cu.add(&x)
+                .add(&FpVar::<_>::one())
+                .add(&FpVar::<_>::one())
+                .add(&FpVar::<_>::one())
+                .add(&FpVar::<_>::one())
+                .add(&FpVar::<_>::one())
+                .enforce_equal(&y)?;
+
+            Ok((FpVar::Constant(F::from(self.next_pc)), vec![y]))
+        }
+    }
+
+    fn single_circuit_r1cs<G: CurveCycle>() {
+        let params = PublicParamsBuilder::<G, R1CS<G>>::new(b"supernova", vec![G::F1::one()])
+            .circuit(&CubicCircuit { next_pc: 0 })
+            .unwrap()
+            .build()
+            .unwrap();
+        let folded =
+            R1CS::<G>::new(&params, 0, vec![G::F1::one()], &CubicCircuit { next_pc: 0 }).unwrap();
+
+        // Synthesizing the same circuit yields the same commitments.
+        let other =
+            R1CS::<G>::new(&params, 0, vec![G::F1::one()], &CubicCircuit { next_pc: 0 }).unwrap();
+        let (mut bytes, mut other_bytes) = (vec![], vec![]);
+        folded.serialize_compressed(&mut bytes).unwrap();
+        other.serialize_compressed(&mut other_bytes).unwrap();
+        assert_eq!(bytes, other_bytes);
+
+        let mut proof =
+            Proof::<G, R1CS<G>>::new(&params, vec![folded], vec![G::F1::one()]).unwrap();
+        // Check base case verification.
+        proof.verify(&params).unwrap();
+
+        // The base case isn't bound to any hash, so it's rejected unless it outputs its input at
+        // the first circuit.
+        let mut base = proof.instance();
+        base.output = vec![G::F1::from(2u64)];
+        assert!(matches!(
+            base.verify(&params.verifier_key()),
+            Err(VerificationError::ExpectedBaseCase)
+        ));
+        let mut base = proof.instance();
+        base.pc = 1;
+        base.folded.push(base.folded[0].clone());
+        assert!(matches!(
+            base.verify(&params.verifier_key()),
+            Err(VerificationError::ExpectedBaseCase)
+        ));
+
+        // Fold and verify two steps of computation.
+        for _ in 0..2 {
+            proof.update(&params, &CubicCircuit { next_pc: 0 }).unwrap();
+            proof.verify(&params).unwrap();
+        }
+
+        // Checkpoint the proof and its public part, and ensure they can be resumed.
+        let mut bytes = vec![];
+        proof.serialize_compressed(&mut bytes).unwrap();
+        let mut proof = Proof::<G, R1CS<G>>::deserialize_compressed(&bytes[..]).unwrap();
+        proof.verify(&params).unwrap();
+
+        // A proof carries the shapes of its pairs, which are rejected unless they match the ones
+        // the parameters were built with.
+        let mut tampered = Proof::<G, R1CS<G>>::deserialize_compressed(&bytes[..]).unwrap();
+        tampered.folded[0].shape.matrices.a[0].clear();
+        assert!(matches!(
+            tampered.verify(&params),
+            Err(VerificationError::ShapeMismatch)
+        ));
+        let mut tampered = Proof::<G, R1CS<G>>::deserialize_compressed(&bytes[..]).unwrap();
+        tampered.latest_secondary.shape.matrices.c[0].clear();
+        assert!(matches!(
+            tampered.verify(&params),
+            Err(VerificationError::ShapeMismatch)
+        ));
+
+        // So is a proof whose secondary pair doesn't output both hashes, which is caught when
+        // it's loaded.
+        let mut tampered = Proof::<G, R1CS<G>>::deserialize_compressed(&bytes[..]).unwrap();
+        tampered.latest_secondary.instance.pop();
+        assert!(matches!(
+            tampered.check(),
+            Err(SerializationError::InvalidData)
+        ));
+
+        let mut bytes = vec![];
+        proof.instance().serialize_compressed(&mut bytes).unwrap();
+        let instance = ProofInstance::<G, R1CS<G>>::deserialize_compressed(&bytes[..]).unwrap();
+        assert_eq!(instance.z0(), proof.z0());
+        assert_eq!(instance.output(), proof.output());
+        assert_eq!((instance.pc(), instance.i()), (0, 2));
+        let mut bytes = vec![];
+        params
+            .verifier_key()
+            .serialize_compressed(&mut bytes)
             .unwrap();
+        let key = VerifierKey::<G>::deserialize_compressed(&bytes[..]).unwrap();
+        instance.verify(&key).unwrap();
+
+        proof.update(&params, &CubicCircuit { next_pc: 0 }).unwrap();
+        proof.verify(&params).unwrap();
 
-        vec![y]
+        // A tampered witness is rejected, even if the error vector is adjusted to keep the
+        // constraints satisfied.
+        let pair = &mut proof.folded[0];
+        pair.witness[0] += G::F1::one();
+        let (az, bz, cz) = pair.eval_r1cs();
+        pair.E = (0..az.len())
+            .map(|i| az[i] * bz[i] - pair.u * cz[i])
+            .collect();
+        assert!(matches!(
+            proof.verify(&params),
+            Err(VerificationError::CommitmentMismatch)
+        ));
     }
 
     #[test]
     fn test_single_circuit_r1cs() {
-        // TODO: can we infer generator size
-        let generators = create_generators(30000);
-        let (ark, mds) =
-            find_poseidon_ark_and_mds(Fq::MODULUS.const_num_bits() as u64, 2, 8, 43, 0);
-        let constants = PoseidonConfig {
-            full_rounds: 8,
-            partial_rounds: 43,
-            alpha: 5,
-            ark,
-            mds,
-            rate: 2,
-            capacity: 1,
-        };
-        let (folded, base) = R1CS::new(vec![Fq::one()], &cubic_circuit, &constants, &generators);
+        single_circuit_r1cs::<PallasVesta>();
+        single_circuit_r1cs::<Bn254Grumpkin>();
+    }
+
+    fn single_circuit_plonk<G: CurveCycle>() {
+        let params = PublicParamsBuilder::<G, Plonk<G>>::new(b"supernova", vec![G::F1::one()])
+            .circuit(&CubicCircuit { next_pc: 0 })
+            .unwrap()
+            .build()
+            .unwrap();
+        let folded =
+            Plonk::<G>::new(&params, 0, vec![G::F1::one()], &CubicCircuit { next_pc: 0 }).unwrap();
 
-        let folded = [folded.clone(); 1];
-        let mut proof = Proof::<R1CS, 1>::new(folded, base, generators);
+        let mut proof =
+            Proof::<G, Plonk<G>>::new(&params, vec![folded], vec![G::F1::one()]).unwrap();
         // Check base case verification.
-        proof.verify().unwrap();
+        proof.verify(&params).unwrap();
 
         // Fold and verify two steps of computation.
         for _ in 0..2 {
-            proof.update(0, &cubic_circuit);
-            proof.verify().unwrap();
+            proof.update(&params, &CubicCircuit { next_pc: 0 }).unwrap();
+            proof.verify(&params).unwrap();
         }
+
+        // Checkpoint the proof, and ensure it can be resumed.
+        let mut bytes = vec![];
+        proof.serialize_compressed(&mut bytes).unwrap();
+        let mut proof = Proof::<G, Plonk<G>>::deserialize_compressed(&bytes[..]).unwrap();
+        proof.verify(&params).unwrap();
+
+        // A wire which breaks a copy constraint is rejected, as it no longer matches its
+        // commitment.
+        proof.folded[0].wires[2][0] += G::F1::one();
+        assert!(matches!(
+            proof.verify(&params),
+            Err(VerificationError::CommitmentMismatch)
+        ));
     }
 
-    fn square_circuit(cs: ConstraintSystemRef<Fq>, z: &[FpVar<Fq>]) -> Vec<FpVar<Fq>> {
-        // Consider a square equation: `x^2 + x + 5 = y`, where `x` and `y` are respectively the
-        // input and output.
-        let x = FpVar::<_>::new_input(cs.clone(), || Ok(z[0].value().unwrap())).unwrap();
-        let x_sq = x.square().unwrap();
-        let y = FpVar::<_>::new_witness(cs.clone(), || {
-            Ok(x_sq.value().unwrap() + x.value().unwrap() + Fq::from(5u64))
-        })
-        .unwrap();
-        x_sq.add(&x)
-            .add(&FpVar::<_>::one())
-            .add(&FpVar::<_>::one())
-            .add(&FpVar::<_>::one())
-            .add(&FpVar::<_>::one())
-            .add(&FpVar::<_>::one())
-            .enforce_equal(&y)
-            .unwrap();
+    #[test]
+    fn test_single_circuit_plonk() {
+        single_circuit_plonk::<PallasVesta>();
+    }
+
+    // Consider a square equation: `x^2 + x + 5 = y`, where `x` and `y` are respectively the input
+    // and output. The circuit selects the circuit at `next_pc` next.
+    struct SquareCircuit {
+        next_pc: u64,
+    }
+
+    impl<F: PrimeField> StepCircuit<F> for SquareCircuit {
+        fn arity(&self) -> usize {
+            1
+        }
+
+        fn synthesize(
+            &self,
+            cs: ConstraintSystemRef<F>,
+            z: &[FpVar<F>],
+        ) -> Result<(FpVar<F>, Vec<FpVar<F>>), SynthesisError> {
+            let x = FpVar::<_>::new_witness(cs.clone(), || z[0].value())?;
+            let x_sq = x.square()?;
+            let y = FpVar::<_>::new_witness(cs.clone(), || {
+                Ok(x_sq.value()? + x.value()? + F::from(5u64))
+            })?;
+            x_sq.add(&x)
+                .add(&FpVar::<_>::one())
+                .add(&FpVar::<_>::one())
+                .add(&FpVar::<_>::one())
+                .add(&FpVar::<_>::one())
+                .add(&FpVar::<_>::one())
+                .enforce_equal(&y)?;
+
+            Ok((FpVar::Constant(F::from(self.next_pc)), vec![y]))
+        }
+    }
+
+    // A circuit which claims that the output of the cubic circuit is zero, which is never the case
+    // for the inputs used in the tests.
+    struct ZeroCircuit;
 
-        vec![y]
+    impl<F: PrimeField> StepCircuit<F> for ZeroCircuit {
+        fn arity(&self) -> usize {
+            1
+        }
+
+        fn synthesize(
+            &self,
+            cs: ConstraintSystemRef<F>,
+            z: &[FpVar<F>],
+        ) -> Result<(FpVar<F>, Vec<FpVar<F>>), SynthesisError> {
+            let (pc, output) = CubicCircuit { next_pc: 0 }.synthesize(cs, z)?;
+            output[0].enforce_equal(&FpVar::zero())?;
+            Ok((pc, output))
+        }
+    }
+
+    // A cubic circuit which selects the circuit at `next_pc` by a witness rather than a constant,
+    // so that it has the same shape whichever circuit it selects.
+    struct JumpCircuit {
+        next_pc: u64,
+    }
+
+    impl<F: PrimeField> StepCircuit<F> for JumpCircuit {
+        fn arity(&self) -> usize {
+            1
+        }
+
+        fn synthesize(
+            &self,
+            cs: ConstraintSystemRef<F>,
+            z: &[FpVar<F>],
+        ) -> Result<(FpVar<F>, Vec<FpVar<F>>), SynthesisError> {
+            let (_, output) = CubicCircuit { next_pc: 0 }.synthesize(cs.clone(), z)?;
+            let pc = FpVar::new_witness(cs, || Ok(F::from(self.next_pc)))?;
+            Ok((pc, output))
+        }
     }
 
     #[test]
     fn test_multi_circuit_r1cs() {
-        let generators = create_generators(30000);
-        let (ark, mds) =
-            find_poseidon_ark_and_mds(Fq::MODULUS.const_num_bits() as u64, 2, 8, 43, 0);
-        let constants = PoseidonConfig {
-            full_rounds: 8,
-            partial_rounds: 43,
-            alpha: 5,
-            ark,
-            mds,
-            rate: 2,
-            capacity: 1,
-        };
-        let (folded1, base) = R1CS::new(vec![Fq::one()], &cubic_circuit, &constants, &generators);
-        let (folded2, _) = R1CS::new(vec![Fq::one()], &square_circuit, &constants, &generators);
+        // The cubic circuit selects the square circuit next, and vice versa. Both are committed
+        // to with the inner product argument scheme on both curves.
+        let cubic = CubicCircuit { next_pc: 1 };
+        let square = SquareCircuit { next_pc: 0 };
+        let params = PublicParamsBuilder::<PallasVesta, R1CS<PallasVesta, IPA>, IPA>::new(
+            b"supernova",
+            vec![Fq::one()],
+        )
+        .circuit(&cubic)
+        .unwrap()
+        .circuit(&square)
+        .unwrap()
+        .build()
+        .unwrap();
+        let folded1 = R1CS::new(&params, 0, vec![Fq::one()], &cubic).unwrap();
+        let folded2 = R1CS::new(&params, 1, vec![Fq::one()], &square).unwrap();
+
+        // The circuits have to be given in the order the parameters were built with.
+        assert!(matches!(
+            R1CS::new(&params, 0, vec![Fq::one()], &square),
+            Err(ProverError::ShapeMismatch(0))
+        ));
+        assert!(matches!(
+            Proof::<PallasVesta, R1CS<PallasVesta, IPA>, IPA>::new(
+                &params,
+                vec![folded2.clone(), folded1.clone()],
+                vec![Fq::one()]
+            ),
+            Err(ProverError::ShapeMismatch(0))
+        ));
 
-        let folded: [R1CS; 2] = [folded1, folded2];
-        let mut proof = Proof::<R1CS, 2>::new(folded, base, generators);
+        let mut proof = Proof::<PallasVesta, R1CS<PallasVesta, IPA>, IPA>::new(
+            &params,
+            vec![folded1, folded2],
+            vec![Fq::one()],
+        )
+        .unwrap();
         // Check base case verification.
-        proof.verify().unwrap();
+        proof.verify(&params).unwrap();
 
         // Fold and verify two steps of computation for each circuit, in interlocked fashion.
         for _ in 0..2 {
-            proof.update(0, &cubic_circuit);
-            proof.verify().unwrap();
-            proof.update(1, &square_circuit);
-            proof.verify().unwrap();
+            assert_eq!(proof.pc(), 0);
+            proof.update(&params, &cubic).unwrap();
+            proof.verify(&params).unwrap();
+            assert_eq!(proof.pc(), 1);
+            proof.update(&params, &square).unwrap();
+            proof.verify(&params).unwrap();
+        }
+
+        // The public part of the proof verifies on its own.
+        let (instance, mut key) = (proof.instance(), params.verifier_key());
+        instance.verify(&key).unwrap();
+
+        // The circuits are bound to their position in the program.
+        key.params.swap(0, 1);
+        assert!(matches!(
+            instance.verify(&key),
+            Err(VerificationError::HashMismatch(_, _))
+        ));
+
+        // A program counter other than the one computed in-circuit is rejected.
+        proof.pc = 1;
+        assert!(matches!(
+            proof.verify(&params),
+            Err(VerificationError::HashMismatch(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_multi_circuit_ccs() {
+        // The circuits differ in size, so the running instances of both are linearized at points
+        // which fit the larger one.
+        let cubic = CubicCircuit { next_pc: 1 };
+        let square = SquareCircuit { next_pc: 0 };
+        let params = PublicParamsBuilder::<PallasVesta, CCS<PallasVesta>>::new(
+            b"supernova",
+            vec![Fq::one()],
+        )
+        .circuit(&cubic)
+        .unwrap()
+        .circuit(&square)
+        .unwrap()
+        .build()
+        .unwrap();
+        let folded1 = CCS::new(&params, 0, vec![Fq::one()], &cubic).unwrap();
+        let folded2 = CCS::new(&params, 1, vec![Fq::one()], &square).unwrap();
+        assert_eq!(folded1.r_x.len(), folded2.r_x.len());
+
+        let mut proof = Proof::<PallasVesta, CCS<PallasVesta>>::new(
+            &params,
+            vec![folded1, folded2],
+            vec![Fq::one()],
+        )
+        .unwrap();
+        // Check base case verification.
+        proof.verify(&params).unwrap();
+
+        // Fold and verify two steps of computation for each circuit, in interlocked fashion. The
+        // multi-folding of every step is verified by the secondary circuit.
+        for _ in 0..2 {
+            proof.update(&params, &cubic).unwrap();
+            proof.verify(&params).unwrap();
+            proof.update(&params, &square).unwrap();
+            proof.verify(&params).unwrap();
         }
+
+        // The claimed evaluations are hashed alongside the rest of the running instances, so
+        // tampering with them is rejected.
+        proof.folded[0].v[0] += Fq::one();
+        assert!(matches!(
+            proof.verify(&params),
+            Err(VerificationError::HashMismatch(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_circuit_table() {
+        // The circuits form a table which is only known at runtime, such as when an instruction
+        // set is loaded from configuration, and are invoked in a cycle.
+        let table: Vec<Box<dyn StepCircuit<Fq>>> = vec![
+            Box::new(CubicCircuit { next_pc: 1 }),
+            Box::new(SquareCircuit { next_pc: 2 }),
+            Box::new(CubicCircuit { next_pc: 0 }),
+        ];
+        let params = table
+            .iter()
+            .try_fold(
+                PublicParamsBuilder::<PallasVesta, R1CS<PallasVesta>>::new(
+                    b"supernova",
+                    vec![Fq::one()],
+                ),
+                |builder, circuit| builder.circuit(circuit.as_ref()),
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        let folded = table
+            .iter()
+            .enumerate()
+            .map(|(pc, circuit)| R1CS::new(&params, pc, vec![Fq::one()], circuit.as_ref()))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let mut proof = Proof::new(&params, folded, vec![Fq::one()]).unwrap();
+        for pc in [0, 1, 2, 0] {
+            assert_eq!(proof.pc(), pc);
+            proof.update(&params, table[proof.pc()].as_ref()).unwrap();
+            proof.verify(&params).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_multi_circuit_mixed() {
+        // The cubic circuit is arithmetized as R1CS, and the square circuit as Plonk.
+        type A = Either<R1CS<PallasVesta>, Plonk<PallasVesta>>;
+        let cubic = CubicCircuit { next_pc: 1 };
+        let square = SquareCircuit { next_pc: 0 };
+        let params = PublicParamsBuilder::<PallasVesta, A>::new(b"supernova", vec![Fq::one()])
+            .circuit(&cubic)
+            .unwrap()
+            .right_circuit(&square)
+            .unwrap()
+            .build()
+            .unwrap();
+        let folded1 = A::new(&params, 0, vec![Fq::one()], &cubic).unwrap();
+        let folded2 = A::new(&params, 1, vec![Fq::one()], &square).unwrap();
+        assert!(matches!(folded1, Either::Left(_)));
+        assert!(matches!(folded2, Either::Right(_)));
+
+        // Pairs of different arithmetizations can't be folded into each other.
+        assert!(matches!(
+            folded1.clone().fold(
+                &folded2,
+                params.secondary_oracle(),
+                params.key(),
+                <PallasVesta as CurveCycle>::F2::zero()
+            ),
+            Err(ProverError::ArithmetizationMismatch)
+        ));
+
+        let mut proof = Proof::new(&params, vec![folded1, folded2], vec![Fq::one()]).unwrap();
+        proof.verify(&params).unwrap();
+        for _ in 0..2 {
+            proof.update(&params, &cubic).unwrap();
+            proof.verify(&params).unwrap();
+            proof.update(&params, &square).unwrap();
+            proof.verify(&params).unwrap();
+        }
+
+        // The proof can be checkpointed like any other.
+        let mut bytes = vec![];
+        proof.serialize_compressed(&mut bytes).unwrap();
+        let proof = Proof::<PallasVesta, A>::deserialize_compressed(&bytes[..]).unwrap();
+        proof.verify(&params).unwrap();
+    }
+
+    #[test]
+    fn test_prover_errors() {
+        // Building parameters fails without any circuits, or with a circuit that doesn't match
+        // the arity of the initial input.
+        assert!(matches!(
+            PublicParamsBuilder::<PallasVesta, R1CS<PallasVesta>>::new(b"supernova", vec![])
+                .build(),
+            Err(ProverError::PCOutOfRange(0, 0))
+        ));
+        assert!(matches!(
+            PublicParamsBuilder::<PallasVesta, R1CS<PallasVesta>>::new(b"supernova", vec![])
+                .circuit(&CubicCircuit { next_pc: 0 }),
+            Err(ProverError::ArityMismatch(1, 0))
+        ));
+
+        let mut params = PublicParamsBuilder::<PallasVesta, R1CS<PallasVesta>>::new(
+            b"supernova",
+            vec![Fq::one()],
+        )
+        .circuit(&JumpCircuit { next_pc: 0 })
+        .unwrap()
+        .build()
+        .unwrap();
+
+        // The key fits the circuit, but a single base less than it needs is too small.
+        let required = R1CS::<PallasVesta>::key_size(&params.shapes()[0]);
+        let key = params.key().clone();
+        assert!(required <= key.len());
+        params.key.truncate(required - 1);
+        assert!(matches!(
+            R1CS::new(&params, 0, vec![Fq::one()], &JumpCircuit { next_pc: 0 }),
+            Err(ProverError::KeyTooSmall(n, m)) if n == required && m == required - 1
+        ));
+        params.key = key;
+
+        // So is an input which doesn't match the arity of the circuit, and a circuit which the
+        // parameters weren't built with.
+        assert!(matches!(
+            R1CS::new(&params, 0, vec![Fq::one(); 2], &JumpCircuit { next_pc: 0 }),
+            Err(ProverError::ArityMismatch(1, 2))
+        ));
+        assert!(matches!(
+            R1CS::new(&params, 1, vec![Fq::one()], &JumpCircuit { next_pc: 0 }),
+            Err(ProverError::PCOutOfRange(1, 1))
+        ));
+
+        let folded = R1CS::new(&params, 0, vec![Fq::one()], &JumpCircuit { next_pc: 0 }).unwrap();
+        assert!(matches!(
+            Proof::<PallasVesta, R1CS<PallasVesta>>::new(
+                &params,
+                vec![folded.clone(), folded.clone()],
+                vec![Fq::one()]
+            ),
+            Err(ProverError::ShapeMismatch(1))
+        ));
+        let mut proof =
+            Proof::<PallasVesta, R1CS<PallasVesta>>::new(&params, vec![folded], vec![Fq::one()])
+                .unwrap();
+
+        // A step which doesn't satisfy its own constraints is rejected.
+        assert!(matches!(
+            proof.update(&params, &ZeroCircuit),
+            Err(ProverError::UnsatisfiedStep)
+        ));
+
+        // So is a step which selects a circuit that doesn't exist.
+        assert!(matches!(
+            proof.update(&params, &JumpCircuit { next_pc: 1 }),
+            Err(ProverError::PCOutOfRange(1, 1))
+        ));
+
+        // So is a circuit which doesn't match the one at the program counter, as it can't be
+        // folded into its pairs, even if it only differs in a constant.
+        assert!(matches!(
+            proof.update(&params, &CubicCircuit { next_pc: 0 }),
+            Err(ProverError::ShapeMismatch(0))
+        ));
+
+        // So is a step which can't be committed to on the secondary curve, which is only found out
+        // once its pair has been folded.
+        let secondary_key = params.secondary_key.clone();
+        params.secondary_key.truncate(1);
+        assert!(matches!(
+            proof.update(&params, &JumpCircuit { next_pc: 0 }),
+            Err(ProverError::KeyTooSmall(_, 1))
+        ));
+        params.secondary_key = secondary_key;
+
+        // The proof is left as is, and can still be updated.
+        proof.verify(&params).unwrap();
+        proof.update(&params, &JumpCircuit { next_pc: 0 }).unwrap();
+        proof.verify(&params).unwrap();
+    }
+
+    #[test]
+    fn test_wide_oracle() {
+        type G = PallasVesta;
+        let params = PublicParamsBuilder::<G, R1CS<G>>::with_oracles(
+            b"supernova",
+            vec![Fq::one()],
+            poseidon_config(4, 8, 56, 5),
+            poseidon_config(4, 8, 56, 5),
+        )
+        .circuit(&CubicCircuit { next_pc: 0 })
+        .unwrap()
+        .build()
+        .unwrap();
+        let folded =
+            R1CS::<G>::new(&params, 0, vec![Fq::one()], &CubicCircuit { next_pc: 0 }).unwrap();
+        let mut proof = Proof::<G, R1CS<G>>::new(&params, vec![folded], vec![Fq::one()]).unwrap();
+        proof.update(&params, &CubicCircuit { next_pc: 0 }).unwrap();
+        proof.verify(&params).unwrap();
+
+        // The oracle is part of the circuit digests, so the default parameters reject the proof.
+        let other = PublicParamsBuilder::<G, R1CS<G>>::new(b"supernova", vec![Fq::one()])
+            .circuit(&CubicCircuit { next_pc: 0 })
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_ne!(params.program_digest(), other.program_digest());
+        assert!(proof.verify(&other).is_err());
+    }
+
+    #[test]
+    fn test_shape_digest() {
+        let constants = poseidon_constants::<Fq>();
+        let key = [0u8; 32];
+        // Constrains `x * x = y`, with `x` in the given column.
+        let shape = |col| R1CSShape {
+            matrices: ConstraintMatrices::<Fq> {
+                num_instance_variables: 2,
+                num_witness_variables: 2,
+                num_constraints: 1,
+                a_num_non_zero: 1,
+                b_num_non_zero: 1,
+                c_num_non_zero: 1,
+                a: vec![vec![(Fq::one(), col)]],
+                b: vec![vec![(Fq::one(), col)]],
+                c: vec![vec![(Fq::one(), 3)]],
+            },
+            lookups: Lookups::default(),
+        };
+        let digest = SerializableShape::from(&shape(2))
+            .digest(&constants, &key)
+            .unwrap();
+        assert_eq!(
+            digest,
+            SerializableShape::from(&shape(2))
+                .digest(&constants, &key)
+                .unwrap()
+        );
+
+        // The same coefficients in other columns yield another digest.
+        assert_ne!(
+            digest,
+            SerializableShape::from(&shape(1))
+                .digest(&constants, &key)
+                .unwrap()
+        );
+
+        // So do lookups.
+        let mut lookups = shape(2);
+        lookups.lookups = Lookups {
+            tables: vec![vec![Fq::zero(), Fq::one()]],
+            lookups: vec![(0, 0)],
+        };
+        assert_ne!(
+            digest,
+            SerializableShape::from(&lookups)
+                .digest(&constants, &key)
+                .unwrap()
+        );
+
+        // So do another commitment key and other Poseidon constants.
+        assert_ne!(
+            digest,
+            SerializableShape::from(&shape(2))
+                .digest(&constants, &[1u8; 32])
+                .unwrap()
+        );
+        let mut other = constants.clone();
+        other.alpha = 17;
+        assert_ne!(
+            digest,
+            SerializableShape::from(&shape(2))
+                .digest(&other, &key)
+                .unwrap()
+        );
     }
 }
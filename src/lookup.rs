@@ -0,0 +1,428 @@
+//! Lookup arguments, which let step circuits check that a value is an entry of a fixed table with
+//! a single lookup, rather than with many constraints, such as when decomposing it into bits for
+//! a range check. Lookups are recorded in the constraint system by [`Table::lookup`], and are
+//! checked by the R1CS arithmetization alongside its constraints, which survives folding.
+//!
+//! The lookups are checked with LogUp. Given the values `w_i` looked up in a table and the
+//! multiplicities `m_j` of its entries `t_j`, every value is an entry if and only if
+//! `sum_i 1 / (beta + w_i) = sum_j m_j / (beta + t_j)`, except with negligible probability over
+//! the challenge `beta`. The challenge is derived from a commitment to the witness and the
+//! multiplicities, after which the inverses `h_i = 1 / (beta + w_i)` and
+//! `g_j = m_j / (beta + t_j)` are committed to separately. The inverses are checked by the
+//! relaxed constraints `h_i * (beta + w_i) = u^2 + E` and `g_j * (beta + u * t_j) = u * m_j + E`,
+//! which are homogeneous of degree two like the relaxed R1CS constraints, and the sums of every
+//! table by a linear constraint, which holds exactly under folding.
+
+use ark_ff::{batch_inversion, PrimeField};
+use ark_r1cs_std::{
+    alloc::AllocVar,
+    eq::EqGadget,
+    fields::fp::{AllocatedFp, FpVar},
+    R1CSVar,
+};
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError, Variable};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use std::{any::TypeId, collections::HashMap, sync::Arc};
+
+/// The largest number of bits that [`Table::range`] builds a table for, which keeps the table
+/// and the multiplicities of its entries at a size that can be committed to.
+pub const MAX_RANGE_BITS: u32 = 20;
+
+/// A table of values over the field `F`, which step circuits can look values up in. Clones share
+/// their values, so that lookups into a table can find it among the tables used before.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Table<F: PrimeField> {
+    values: Arc<Vec<F>>,
+}
+
+impl<F: PrimeField> Table<F> {
+    /// Returns a table of the given values.
+    pub fn new(values: Vec<F>) -> Self {
+        Self {
+            values: Arc::new(values),
+        }
+    }
+
+    /// Returns a table of all values of up to `bits` bits, which range checks a value with a
+    /// single lookup. Every entry is kept in the table and committed to with a multiplicity, so
+    /// `bits` may be at most [`MAX_RANGE_BITS`]; wider values should be decomposed into limbs
+    /// which are looked up separately.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits` exceeds [`MAX_RANGE_BITS`].
+    pub fn range(bits: u32) -> Self {
+        assert!(
+            bits <= MAX_RANGE_BITS,
+            "range tables are limited to {MAX_RANGE_BITS} bits, but {bits} were requested"
+        );
+        Self::new((0..1u64 << bits).map(F::from).collect())
+    }
+
+    /// Returns the values of the table.
+    pub fn values(&self) -> &[F] {
+        &self.values
+    }
+
+    /// Checks that the given value is an entry of the table. Lookups are recorded in the
+    /// constraint system, and are only checked once it is arithmetized, so they don't affect
+    /// whether the constraint system itself is satisfied. Arithmetizations without support for
+    /// lookups reject constraint systems with any.
+    pub fn lookup(
+        &self,
+        cs: ConstraintSystemRef<F>,
+        value: &FpVar<F>,
+    ) -> Result<(), SynthesisError> {
+        // The value is looked up by its witness variable, so other values are allocated first.
+        let variable = match value {
+            FpVar::Var(v) if v.variable.is_witness() => v.variable,
+            _ => {
+                let v = AllocatedFp::new_witness(cs.clone(), || value.value())?;
+                FpVar::Var(v.clone()).enforce_equal(value)?;
+                v.variable
+            }
+        };
+        let Variable::Witness(index) = variable else {
+            return Err(SynthesisError::MissingCS);
+        };
+
+        let cs = cs.borrow().ok_or(SynthesisError::MissingCS)?;
+        let mut cache = cs.cache_map.borrow_mut();
+        let recorded = cache
+            .entry(TypeId::of::<Recorded<F>>())
+            .or_insert_with(|| Box::new(Recorded::<F>::default()))
+            .downcast_mut::<Recorded<F>>()
+            .ok_or(SynthesisError::MissingCS)?;
+        let table = match recorded
+            .tables
+            .iter()
+            .position(|table| Arc::ptr_eq(&table.values, &self.values) || table == self)
+        {
+            Some(table) => table,
+            None => {
+                recorded.tables.push(self.clone());
+                recorded.tables.len() - 1
+            }
+        };
+        recorded.lookups.push((table, index));
+        Ok(())
+    }
+}
+
+// The lookups recorded in a constraint system so far, which are kept in its cache.
+#[derive(Default)]
+struct Recorded<F: PrimeField> {
+    tables: Vec<Table<F>>,
+    lookups: Vec<(usize, usize)>,
+}
+
+/// The lookups of a circuit, which are part of its shape. These are the tables that values are
+/// looked up in, in order of their first lookup, and the table and witness variable of every
+/// value looked up.
+#[derive(Clone, Debug, Default, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Lookups<F: PrimeField> {
+    pub tables: Vec<Vec<F>>,
+    pub lookups: Vec<(usize, usize)>,
+}
+
+// An assignment to the lookups of a circuit, which is evaluated against them.
+pub(crate) struct LookupAssignment<'a, F: PrimeField> {
+    pub(crate) u: F,
+    pub(crate) challenge: F,
+    pub(crate) witness: &'a [F],
+    pub(crate) multiplicities: &'a [F],
+    pub(crate) inverses: &'a [F],
+}
+
+impl<F: PrimeField> Lookups<F> {
+    // Returns the lookups recorded in a synthesized constraint system.
+    pub(crate) fn from_cs(cs: &ConstraintSystemRef<F>) -> Result<Self, SynthesisError> {
+        let cs = cs.borrow().ok_or(SynthesisError::MissingCS)?;
+        let cache = cs.cache_map.borrow();
+        Ok(cache
+            .get(&TypeId::of::<Recorded<F>>())
+            .and_then(|recorded| recorded.downcast_ref::<Recorded<F>>())
+            .map_or_else(Self::default, |recorded| Self {
+                tables: recorded
+                    .tables
+                    .iter()
+                    .map(|table| table.values().to_vec())
+                    .collect(),
+                lookups: recorded.lookups.clone(),
+            }))
+    }
+
+    /// Returns whether no values are looked up.
+    pub fn is_empty(&self) -> bool {
+        self.lookups.is_empty()
+    }
+
+    // Returns the number of entries of all tables, which each have a multiplicity.
+    pub(crate) fn num_entries(&self) -> usize {
+        self.tables.iter().map(Vec::len).sum()
+    }
+
+    // Returns the number of constraints on the inverses, which are laid out like the inverses
+    // themselves: one for every value looked up, followed by one for every table entry.
+    pub(crate) fn num_rows(&self) -> usize {
+        self.lookups.len() + self.num_entries()
+    }
+
+    // Ensures that every lookup refers to a table and to one of the given number of witness
+    // variables.
+    pub(crate) fn is_valid(&self, num_witness_variables: usize) -> bool {
+        self.lookups
+            .iter()
+            .all(|(table, index)| *table < self.tables.len() && *index < num_witness_variables)
+    }
+
+    // Returns how often every table entry is looked up, with the entries of all tables laid out
+    // in order. Values which appear in a table more than once are counted at their first entry.
+    // Returns `None` if a value isn't an entry of its table.
+    pub(crate) fn multiplicities(&self, witness: &[F]) -> Option<Vec<F>> {
+        let entries = self
+            .tables
+            .iter()
+            .map(|table| {
+                let mut entries = HashMap::new();
+                for (j, v) in table.iter().enumerate().rev() {
+                    entries.insert(*v, j);
+                }
+                entries
+            })
+            .collect::<Vec<_>>();
+
+        let offsets = self.offsets();
+        let mut multiplicities = vec![F::zero(); self.num_entries()];
+        for (table, index) in &self.lookups {
+            let j = entries[*table].get(&witness[*index])?;
+            multiplicities[offsets[*table] + j] += F::one();
+        }
+        Some(multiplicities)
+    }
+
+    // Returns the inverses of the values looked up, followed by the multiplicities of the table
+    // entries over their inverses, both offset by the challenge. Returns `None` if any of them
+    // isn't invertible, which only happens with negligible probability over the challenge.
+    pub(crate) fn inverses(
+        &self,
+        witness: &[F],
+        multiplicities: &[F],
+        challenge: F,
+    ) -> Option<Vec<F>> {
+        let mut inverses = self
+            .lookups
+            .iter()
+            .map(|(_, index)| challenge + witness[*index])
+            .chain(self.tables.iter().flatten().map(|t| challenge + t))
+            .collect::<Vec<F>>();
+        if inverses.iter().any(|v| v.is_zero()) {
+            return None;
+        }
+
+        batch_inversion(&mut inverses);
+        let n = self.lookups.len();
+        inverses[n..]
+            .iter_mut()
+            .zip(multiplicities)
+            .for_each(|(g, m)| *g *= m);
+        Some(inverses)
+    }
+
+    // Evaluates the relaxed constraints on the inverses as a bilinear form over two assignments.
+    // Given the same assignment twice, this evaluates the constraints over it, and summing both
+    // orders of two assignments yields the crossterms of folding them.
+    pub(crate) fn eval(&self, a: &LookupAssignment<F>, b: &LookupAssignment<F>) -> Vec<F> {
+        let n = self.lookups.len();
+        let lookups = self
+            .lookups
+            .iter()
+            .zip(a.inverses)
+            .map(|((_, index), h)| *h * (b.challenge + b.witness[*index]) - a.u * b.u);
+        let entries = self
+            .tables
+            .iter()
+            .flatten()
+            .zip(&a.inverses[n..])
+            .zip(b.multiplicities)
+            .map(|((t, g), m)| *g * (b.challenge + b.u * t) - a.u * m);
+        lookups.chain(entries).collect()
+    }
+
+    // Checks that the inverses of the values looked up in every table sum up to those of its
+    // entries.
+    pub(crate) fn is_balanced(&self, inverses: &[F]) -> bool {
+        let n = self.lookups.len();
+        let mut sums = vec![F::zero(); self.tables.len()];
+        for ((table, _), h) in self.lookups.iter().zip(inverses) {
+            sums[*table] += h;
+        }
+
+        let offsets = self.offsets();
+        sums.iter()
+            .zip(&self.tables)
+            .zip(offsets)
+            .all(|((sum, table), offset)| {
+                *sum == inverses[n + offset..n + offset + table.len()]
+                    .iter()
+                    .sum::<F>()
+            })
+    }
+
+    // Returns the offset of the entries of every table among the entries of all tables.
+    fn offsets(&self) -> Vec<usize> {
+        self.tables
+            .iter()
+            .scan(0, |offset, table| {
+                let start = *offset;
+                *offset += table.len();
+                Some(start)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cycle::pasta::{Fp, Fq, PallasVesta},
+        plonk::Plonk,
+        poseidon_constants,
+        r1cs::R1CS,
+        Arithmetization, CommitmentScheme, Pedersen, Proof, ProverError, PublicParamsBuilder,
+        StepCircuit,
+    };
+    use ark_ff::{Field, One, Zero};
+    use ark_relations::r1cs::ConstraintSystem;
+
+    // Range checks the input as a byte, and adds a step to it.
+    struct ByteCircuit {
+        table: Table<Fq>,
+        step: u64,
+    }
+
+    impl StepCircuit<Fq> for ByteCircuit {
+        fn arity(&self) -> usize {
+            1
+        }
+
+        fn synthesize(
+            &self,
+            cs: ConstraintSystemRef<Fq>,
+            z: &[FpVar<Fq>],
+        ) -> Result<(FpVar<Fq>, Vec<FpVar<Fq>>), SynthesisError> {
+            self.table.lookup(cs.clone(), &z[0])?;
+            Ok((
+                FpVar::Constant(Fq::zero()),
+                vec![&z[0] + FpVar::Constant(Fq::from(self.step))],
+            ))
+        }
+    }
+
+    // Returns an unfolded pair which looks up the given values in a table of bytes, and another
+    // table of small values.
+    fn pair(values: &[u64]) -> Result<R1CS<PallasVesta>, ProverError> {
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let (bytes, small) = (Table::range(8), Table::range(2));
+        for v in values {
+            let v = FpVar::new_witness(cs.clone(), || Ok(Fq::from(*v)))?;
+            bytes.lookup(cs.clone(), &v)?;
+            small.lookup(cs.clone(), &(v - FpVar::Constant(Fq::from(200u64))))?;
+        }
+        cs.finalize();
+        assert!(cs.is_satisfied()?);
+
+        let key = Pedersen::setup(b"supernova", 1024).unwrap();
+        R1CS::from_cs(cs, 0, vec![], &poseidon_constants::<Fp>(), &key)
+    }
+
+    #[test]
+    fn test_lookup() {
+        let key = Pedersen::setup(b"supernova", 1024).unwrap();
+        let oracle = poseidon_constants::<Fp>();
+
+        // Every lookup into a table is recorded once its table is.
+        let mut running = pair(&[200, 203, 200]).unwrap();
+        assert_eq!(running.shape.lookups.tables.len(), 2);
+        assert_eq!(running.shape.lookups.lookups.len(), 6);
+        assert_eq!(running.multiplicities[200], Fq::from(2u64));
+        assert!(running.is_satisfied(&key));
+
+        // Values which aren't in their table have no multiplicity.
+        assert!(matches!(
+            pair(&[200, 204]),
+            Err(ProverError::UnsatisfiedStep)
+        ));
+
+        // The lookups remain satisfied when folded.
+        let latest = pair(&[201, 202, 203]).unwrap();
+        assert!(latest.is_satisfied(&key));
+        let _ = running.fold(&latest, &oracle, &key, Fp::one()).unwrap();
+        assert!(running.has_crossterms());
+        assert!(running.is_satisfied(&key));
+
+        // Replacing a value looked up breaks its inverse, even if the inverse is replaced too.
+        let mut forged = latest.clone();
+        let (_, index) = forged.shape.lookups.lookups[0];
+        forged.witness[index] = Fq::from(256u64);
+        assert!(!forged.is_satisfied(&key));
+        forged.inverses[0] = (forged.challenge + forged.witness[index])
+            .inverse()
+            .unwrap();
+        assert!(!forged.is_satisfied(&key));
+    }
+
+    #[test]
+    fn test_range() {
+        assert_eq!(Table::<Fq>::range(0).values(), [Fq::zero()]);
+        assert_eq!(Table::<Fq>::range(2).values().len(), 4);
+        assert_eq!(
+            Table::<Fq>::range(MAX_RANGE_BITS).values().last(),
+            Some(&Fq::from((1u64 << MAX_RANGE_BITS) - 1))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "range tables are limited")]
+    fn test_range_too_wide() {
+        Table::<Fq>::range(64);
+    }
+
+    #[test]
+    fn test_lookup_proof() {
+        let circuit = ByteCircuit {
+            table: Table::range(8),
+            step: 100,
+        };
+        let z0 = vec![Fq::from(100u64)];
+
+        // Plonk doesn't support lookups.
+        assert!(matches!(
+            PublicParamsBuilder::<PallasVesta, Plonk<PallasVesta>>::new(b"supernova", z0.clone())
+                .circuit(&circuit),
+            Err(ProverError::UnsupportedLookup)
+        ));
+
+        let params =
+            PublicParamsBuilder::<PallasVesta, R1CS<PallasVesta>>::new(b"supernova", z0.clone())
+                .circuit(&circuit)
+                .unwrap()
+                .build()
+                .unwrap();
+        let folded = R1CS::new(&params, 0, z0.clone(), &circuit).unwrap();
+        let mut proof = Proof::new(&params, vec![folded], z0).unwrap();
+        for _ in 0..2 {
+            proof.update(&params, &circuit).unwrap();
+            proof.verify(&params).unwrap();
+        }
+        assert_eq!(proof.output(), [Fq::from(300u64)]);
+
+        // The next input is out of range, which fails to look up.
+        assert!(matches!(
+            proof.update(&params, &circuit),
+            Err(ProverError::UnsatisfiedStep)
+        ));
+        proof.verify(&params).unwrap();
+    }
+}
@@ -0,0 +1,567 @@
+//! A machine which executes bytecode programs, and proves their execution with SuperNova. The
+//! instructions of the machine are step circuits registered by opcode, and the program is
+//! committed to in the public parameters as a lookup table of its words. Every step looks up the
+//! word at the instruction pointer it is given, and is executed by the instruction that the
+//! program counter computed in the previous step selects, so that branching is proven in-circuit.
+
+use crate::{
+    poseidon_constants, Arithmetization, CommitmentScheme, CurveCycle, Pedersen, Proof,
+    ProverError, PublicParams, PublicParamsBuilder, RandomOracle, StepCircuit, Table,
+    VerificationError,
+};
+use ark_crypto_primitives::sponge::poseidon::PoseidonConfig;
+use ark_ff::{BigInteger, One, PrimeField, Zero};
+use ark_r1cs_std::{alloc::AllocVar, fields::fp::FpVar, R1CSVar};
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+use std::collections::BTreeMap;
+
+/// An instruction of a [`Machine`] over the field `F`. Instructions are step circuits which are
+/// also given the operands of the word being executed, and its instruction pointer.
+pub trait Instruction<F: PrimeField> {
+    /// Returns the number of inputs of the instruction, which equals its number of outputs.
+    fn arity(&self) -> usize;
+
+    /// Returns the number of operands of every word with the opcode of the instruction.
+    fn num_operands(&self) -> usize;
+
+    /// Synthesizes the instruction over the given input and operands, and returns the next
+    /// instruction pointer alongside the output. Instructions which don't branch should return
+    /// `ip + 1`, which points at the next word of the bytecode. Instructions should only allocate
+    /// witnesses, as the public IO of the constraint system is reserved for the augmented circuit.
+    #[allow(clippy::type_complexity)]
+    fn synthesize(
+        &self,
+        cs: ConstraintSystemRef<F>,
+        z: &[FpVar<F>],
+        operands: &[FpVar<F>],
+        ip: &FpVar<F>,
+    ) -> Result<(FpVar<F>, Vec<FpVar<F>>), SynthesisError>;
+}
+
+/// A word of a bytecode program, which consists of an opcode and its operands.
+#[derive(Clone, Debug)]
+pub struct Word<F: PrimeField> {
+    pub opcode: usize,
+    pub operands: Vec<F>,
+}
+
+/// A step of the execution trace of a program, which holds the instruction pointer and opcode of
+/// the executed word, alongside the input and the output of its instruction.
+#[derive(Clone, Debug)]
+pub struct Step<F: PrimeField> {
+    pub ip: usize,
+    pub opcode: usize,
+    pub input: Vec<F>,
+    pub output: Vec<F>,
+}
+
+// A program committed to as a table of the hashes of its words alongside their instruction
+// pointers. Every opcode also has a word of zero operands at the instruction pointer -1, which
+// the base cases of the instructions execute, as execution starts at zero.
+struct Program<F: PrimeField> {
+    words: Vec<Word<F>>,
+    table: Table<F>,
+}
+
+impl<F: PrimeField> Program<F> {
+    fn new<R: RandomOracle<F>>(words: Vec<Word<F>>, num_operands: &[usize], oracle: &R) -> Self {
+        let hash = |ip: F, opcode: usize, operands: &[F]| {
+            oracle.hash(&[&[ip, F::from(opcode as u64)], operands].concat())
+        };
+        let padding = num_operands
+            .iter()
+            .enumerate()
+            .map(|(opcode, n)| hash(-F::one(), opcode, &vec![F::zero(); *n]));
+        let table = words
+            .iter()
+            .enumerate()
+            .map(|(ip, word)| hash(F::from(ip as u64), word.opcode, &word.operands))
+            .chain(padding)
+            .collect();
+
+        Self {
+            words,
+            table: Table::new(table),
+        }
+    }
+
+    // Returns the index of the word that an instruction pointer points at, if any.
+    fn position(&self, ip: F) -> Option<usize> {
+        let ip = ip.into_bigint();
+        (ip.num_bits() <= 64)
+            .then(|| ip.as_ref()[0] as usize)
+            .filter(|ip| *ip < self.words.len())
+    }
+
+    // Checks if an instruction pointer points past the end of the program, where execution halts.
+    // The padding words at -1 are before the program rather than past it.
+    fn is_past_end(&self, ip: F) -> bool {
+        let ip = ip.into_bigint();
+        ip.num_bits() <= 64 && ip.as_ref()[0] as usize >= self.words.len()
+    }
+}
+
+// The step circuit which executes a single word with its instruction. The first input is the
+// instruction pointer, followed by the input of the instruction.
+struct WordCircuit<'a, F: PrimeField, R: RandomOracle<F>> {
+    instruction: &'a dyn Instruction<F>,
+    opcode: usize,
+    operands: &'a [F],
+    program: &'a Program<F>,
+    oracle: &'a R,
+}
+
+impl<F: PrimeField, R: RandomOracle<F>> StepCircuit<F> for WordCircuit<'_, F, R> {
+    fn arity(&self) -> usize {
+        self.instruction.arity() + 1
+    }
+
+    fn synthesize(
+        &self,
+        cs: ConstraintSystemRef<F>,
+        z: &[FpVar<F>],
+    ) -> Result<(FpVar<F>, Vec<FpVar<F>>), SynthesisError> {
+        // Look up the word at the instruction pointer, which has the opcode of this instruction.
+        let ip = &z[0];
+        let operands = self
+            .operands
+            .iter()
+            .map(|v| FpVar::new_witness(cs.clone(), || Ok(*v)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let opcode = FpVar::Constant(F::from(self.opcode as u64));
+        let word = self.oracle.hash_var(
+            cs.clone(),
+            &[&[ip.clone(), opcode], operands.as_slice()].concat(),
+        )?;
+        self.program.table.lookup(cs.clone(), &word)?;
+
+        // The next program counter is the opcode of the word at the next instruction pointer,
+        // which the next step checks by looking that word up. Past the end of the program, it
+        // keeps the current opcode, as there is no next step.
+        let (next_ip, output) = self
+            .instruction
+            .synthesize(cs.clone(), &z[1..], &operands, ip)?;
+        let pc = FpVar::new_witness(cs, || {
+            let opcode = self
+                .program
+                .position(next_ip.value()?)
+                .map_or(self.opcode, |ip| self.program.words[ip].opcode);
+            Ok(F::from(opcode as u64))
+        })?;
+        Ok((pc, [vec![next_ip], output].concat()))
+    }
+}
+
+/// A builder for a [`Machine`], which registers the instructions of the machine by opcode, and
+/// the program it executes. The opcodes are the program counters which select the instructions,
+/// so they should be numbered from zero onwards without gaps. The program is looked up in a
+/// table, so the machine needs an arithmetization with support for lookups.
+pub struct MachineBuilder<
+    G: CurveCycle,
+    A: Arithmetization<G>,
+    S: CommitmentScheme<G::C2> = Pedersen,
+    R1: RandomOracle<G::F1> = PoseidonConfig<<G as CurveCycle>::F1>,
+    R2: RandomOracle<G::F2> = PoseidonConfig<<G as CurveCycle>::F2>,
+> {
+    params: PublicParamsBuilder<G, A, S, R1, R2>,
+    oracle: R1,
+    arity: usize,
+    instructions: BTreeMap<usize, Box<dyn Instruction<G::F1>>>,
+    program: Vec<Word<G::F1>>,
+}
+
+impl<G: CurveCycle, A: Arithmetization<G>, S: CommitmentScheme<G::C2>> MachineBuilder<G, A, S> {
+    /// Starts building a machine whose instructions take `arity` inputs, and whose keys are
+    /// derived from `label`. Hashing is done with the default Poseidon sponges.
+    pub fn new(label: &[u8], arity: usize) -> Self {
+        Self::with_oracles(label, arity, poseidon_constants(), poseidon_constants())
+    }
+}
+
+impl<
+        G: CurveCycle,
+        A: Arithmetization<G>,
+        S: CommitmentScheme<G::C2>,
+        R1: RandomOracle<G::F1>,
+        R2: RandomOracle<G::F2>,
+    > MachineBuilder<G, A, S, R1, R2>
+{
+    /// Starts building a machine like [`MachineBuilder::new`], but hashes with the given random
+    /// oracles over either field of the cycle.
+    pub fn with_oracles(label: &[u8], arity: usize, oracle: R1, secondary_oracle: R2) -> Self {
+        // The shapes are synthesized on the input of the base cases.
+        let z0 = [vec![-G::F1::one()], vec![G::F1::zero(); arity]].concat();
+        Self {
+            params: PublicParamsBuilder::with_oracles(label, z0, oracle.clone(), secondary_oracle),
+            oracle,
+            arity,
+            instructions: BTreeMap::new(),
+            program: vec![],
+        }
+    }
+
+    /// Registers an instruction under the given opcode, replacing any instruction registered
+    /// under it before.
+    pub fn instruction(mut self, opcode: usize, instruction: Box<dyn Instruction<G::F1>>) -> Self {
+        self.instructions.insert(opcode, instruction);
+        self
+    }
+
+    /// Sets the program that the machine executes, replacing any program set before.
+    pub fn program(mut self, program: Vec<Word<G::F1>>) -> Self {
+        self.program = program;
+        self
+    }
+
+    /// Sets up the public parameters of the machine, which commit to its program. The shapes of
+    /// the instructions are derived from their base cases. Fails if the opcodes have gaps, if a
+    /// word has an unknown opcode or not as many operands as its instruction, if the program
+    /// doesn't start with opcode zero, as execution starts at program counter zero, or if an
+    /// instruction can't be synthesized.
+    pub fn build(self) -> Result<Machine<G, A, S, R1, R2>, ProverError> {
+        let count = self.instructions.len();
+        if let Some((_, &opcode)) = self
+            .instructions
+            .keys()
+            .enumerate()
+            .find(|(pc, opcode)| pc != *opcode)
+        {
+            return Err(ProverError::PCOutOfRange(opcode, count));
+        }
+
+        let num_operands = self
+            .instructions
+            .values()
+            .map(|instruction| instruction.num_operands())
+            .collect::<Vec<_>>();
+        for word in &self.program {
+            let n = *num_operands
+                .get(word.opcode)
+                .ok_or(ProverError::PCOutOfRange(word.opcode, count))?;
+            if word.operands.len() != n {
+                return Err(ProverError::ArityMismatch(n, word.operands.len()));
+            }
+        }
+        if let Some(word) = self.program.first().filter(|word| word.opcode != 0) {
+            return Err(ProverError::OpcodeMismatch(0, word.opcode));
+        }
+
+        let program = Program::new(self.program, &num_operands, &self.oracle);
+        let mut params = self.params;
+        for (opcode, instruction) in self.instructions.values().enumerate() {
+            let operands = vec![G::F1::zero(); instruction.num_operands()];
+            params = params.circuit(&WordCircuit {
+                instruction: instruction.as_ref(),
+                opcode,
+                operands: &operands,
+                program: &program,
+                oracle: &self.oracle,
+            })?;
+        }
+
+        Ok(Machine {
+            params: params.build()?,
+            oracle: self.oracle,
+            arity: self.arity,
+            instructions: self.instructions.into_values().collect(),
+            program,
+        })
+    }
+}
+
+/// A machine over the curve cycle `G`, which executes its program with its instructions, and
+/// proves the execution.
+pub struct Machine<
+    G: CurveCycle,
+    A: Arithmetization<G>,
+    S: CommitmentScheme<G::C2> = Pedersen,
+    R1: RandomOracle<G::F1> = PoseidonConfig<<G as CurveCycle>::F1>,
+    R2: RandomOracle<G::F2> = PoseidonConfig<<G as CurveCycle>::F2>,
+> {
+    params: PublicParams<G, A, S, R1, R2>,
+    oracle: R1,
+    arity: usize,
+    instructions: Vec<Box<dyn Instruction<G::F1>>>,
+    program: Program<G::F1>,
+}
+
+impl<
+        G: CurveCycle,
+        A: Arithmetization<G>,
+        S: CommitmentScheme<G::C2>,
+        R1: RandomOracle<G::F1>,
+        R2: RandomOracle<G::F2>,
+    > Machine<G, A, S, R1, R2>
+{
+    /// Returns the public parameters of the machine, which commit to its program, and which
+    /// proofs of its execution are verified against.
+    pub fn params(&self) -> &PublicParams<G, A, S, R1, R2> {
+        &self.params
+    }
+
+    /// Executes the program on the initial input `z0` for at most `max_steps` steps, and returns
+    /// the proof of its execution alongside the execution trace. The input and output of the
+    /// proof are preceded by the instruction pointer, which starts at zero, and execution halts
+    /// once it points past the program, which [`Machine::verify`] checks. Fails if an instruction
+    /// fails, or if the program doesn't halt within `max_steps` steps.
+    #[allow(clippy::type_complexity)]
+    pub fn run(
+        &self,
+        z0: Vec<G::F1>,
+        max_steps: usize,
+    ) -> Result<(Proof<G, A, S>, Vec<Step<G::F1>>), ProverError> {
+        let mut proof = self.start(z0)?;
+        let mut trace = vec![];
+        while let Some(ip) = self.program.position(proof.output()[0]) {
+            if trace.len() == max_steps {
+                return Err(ProverError::StepLimit(max_steps));
+            }
+
+            let word = &self.program.words[ip];
+            let input = proof.output()[1..].to_vec();
+            proof.update(
+                &self.params,
+                &WordCircuit {
+                    instruction: self.instructions[proof.pc()].as_ref(),
+                    opcode: proof.pc(),
+                    operands: &word.operands,
+                    program: &self.program,
+                    oracle: &self.oracle,
+                },
+            )?;
+            trace.push(Step {
+                ip,
+                opcode: word.opcode,
+                input,
+                output: proof.output()[1..].to_vec(),
+            });
+        }
+
+        Ok((proof, trace))
+    }
+
+    /// Verifies a proof of the execution of the program, which checks the proof against the
+    /// public parameters of the machine, and that execution started at the first word of the
+    /// program and halted past its end. Words outside the program can't be looked up, except for
+    /// the padding words which the base cases execute, so no other words can have been executed.
+    pub fn verify(&self, proof: &Proof<G, A, S>) -> Result<(), VerificationError<G::F2>> {
+        proof.verify(&self.params)?;
+
+        if proof.z0().first() != Some(&G::F1::zero())
+            || !proof
+                .output()
+                .first()
+                .is_some_and(|ip| self.program.is_past_end(*ip))
+        {
+            return Err(VerificationError::InstructionPointerOutOfRange);
+        }
+
+        Ok(())
+    }
+
+    // Returns the base case of a proof of the execution of the program on the initial input `z0`,
+    // which starts at instruction pointer zero. Fails if `z0` doesn't match the arity of the
+    // machine.
+    fn start(&self, z0: Vec<G::F1>) -> Result<Proof<G, A, S>, ProverError> {
+        if z0.len() != self.arity {
+            return Err(ProverError::ArityMismatch(self.arity, z0.len()));
+        }
+
+        // The base cases of the instructions execute their padding words.
+        let base = [vec![-G::F1::one()], z0.clone()].concat();
+        let folded = self
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(opcode, instruction)| {
+                let operands = vec![G::F1::zero(); instruction.num_operands()];
+                A::new(
+                    &self.params,
+                    opcode,
+                    base.clone(),
+                    &WordCircuit {
+                        instruction: instruction.as_ref(),
+                        opcode,
+                        operands: &operands,
+                        program: &self.program,
+                        oracle: &self.oracle,
+                    },
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Proof::new(&self.params, folded, [vec![G::F1::zero()], z0].concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cycle::pasta::{Fq, PallasVesta},
+        r1cs::R1CS,
+    };
+    use ark_r1cs_std::{eq::EqGadget, fields::FieldVar, select::CondSelectGadget};
+
+    // Multiplies the accumulator by the counter.
+    struct MulInstruction;
+
+    impl<F: PrimeField> Instruction<F> for MulInstruction {
+        fn arity(&self) -> usize {
+            2
+        }
+
+        fn num_operands(&self) -> usize {
+            0
+        }
+
+        fn synthesize(
+            &self,
+            cs: ConstraintSystemRef<F>,
+            z: &[FpVar<F>],
+            _: &[FpVar<F>],
+            ip: &FpVar<F>,
+        ) -> Result<(FpVar<F>, Vec<FpVar<F>>), SynthesisError> {
+            let y = FpVar::new_witness(cs, || Ok(z[0].value()? * z[1].value()?))?;
+            (&z[0] * &z[1]).enforce_equal(&y)?;
+            Ok((ip + F::one(), vec![y, z[1].clone()]))
+        }
+    }
+
+    // Adds its operand to the counter.
+    struct AddInstruction;
+
+    impl<F: PrimeField> Instruction<F> for AddInstruction {
+        fn arity(&self) -> usize {
+            2
+        }
+
+        fn num_operands(&self) -> usize {
+            1
+        }
+
+        fn synthesize(
+            &self,
+            _: ConstraintSystemRef<F>,
+            z: &[FpVar<F>],
+            operands: &[FpVar<F>],
+            ip: &FpVar<F>,
+        ) -> Result<(FpVar<F>, Vec<FpVar<F>>), SynthesisError> {
+            Ok((ip + F::one(), vec![z[0].clone(), &z[1] + &operands[0]]))
+        }
+    }
+
+    // Jumps to its operand unless the counter is zero.
+    struct JumpInstruction;
+
+    impl<F: PrimeField> Instruction<F> for JumpInstruction {
+        fn arity(&self) -> usize {
+            2
+        }
+
+        fn num_operands(&self) -> usize {
+            1
+        }
+
+        fn synthesize(
+            &self,
+            _: ConstraintSystemRef<F>,
+            z: &[FpVar<F>],
+            operands: &[FpVar<F>],
+            ip: &FpVar<F>,
+        ) -> Result<(FpVar<F>, Vec<FpVar<F>>), SynthesisError> {
+            let is_zero = z[1].is_eq(&FpVar::zero())?;
+            let next = FpVar::conditionally_select(&is_zero, &(ip + F::one()), &operands[0])?;
+            Ok((next, z.to_vec()))
+        }
+    }
+
+    fn word(opcode: usize, operands: &[i64]) -> Word<Fq> {
+        Word {
+            opcode,
+            operands: operands.iter().map(|v| Fq::from(*v)).collect(),
+        }
+    }
+
+    fn builder() -> MachineBuilder<PallasVesta, R1CS<PallasVesta>> {
+        MachineBuilder::new(b"supernova", 2)
+            .instruction(2, Box::new(JumpInstruction))
+            .instruction(0, Box::new(MulInstruction))
+            .instruction(1, Box::new(AddInstruction))
+    }
+
+    #[test]
+    fn test_machine() {
+        // Opcodes have to be numbered without gaps, and words have to match their instruction.
+        assert!(matches!(
+            MachineBuilder::<PallasVesta, R1CS<PallasVesta>>::new(b"supernova", 2)
+                .instruction(0, Box::new(AddInstruction))
+                .instruction(2, Box::new(MulInstruction))
+                .build(),
+            Err(ProverError::PCOutOfRange(2, 2))
+        ));
+        assert!(matches!(
+            builder().program(vec![word(3, &[])]).build(),
+            Err(ProverError::PCOutOfRange(3, 3))
+        ));
+        assert!(matches!(
+            builder().program(vec![word(0, &[1])]).build(),
+            Err(ProverError::ArityMismatch(0, 1))
+        ));
+        assert!(matches!(
+            builder().program(vec![word(1, &[1])]).build(),
+            Err(ProverError::OpcodeMismatch(0, 1))
+        ));
+
+        // Multiplies the accumulator by every value of the counter down to one.
+        let machine = builder()
+            .program(vec![word(0, &[]), word(1, &[-1]), word(2, &[0])])
+            .build()
+            .unwrap();
+        let z0 = vec![Fq::from(3u64), Fq::from(2u64)];
+        assert!(matches!(
+            machine.run(z0.clone(), 5),
+            Err(ProverError::StepLimit(5))
+        ));
+        let (mut proof, trace) = machine.run(z0.clone(), 6).unwrap();
+        machine.verify(&proof).unwrap();
+        assert_eq!(
+            trace.iter().map(|step| step.ip).collect::<Vec<_>>(),
+            [0, 1, 2, 0, 1, 2]
+        );
+        assert_eq!(trace[3].input, [Fq::from(6u64), Fq::from(1u64)]);
+        assert_eq!(proof.z0(), [Fq::zero(), Fq::from(3u64), Fq::from(2u64)]);
+        assert_eq!(proof.output(), [Fq::from(3u64), Fq::from(6u64), Fq::zero()]);
+
+        // Execution halts past the end of the program, where no word can be looked up.
+        assert!(matches!(
+            proof.update(
+                machine.params(),
+                &WordCircuit {
+                    instruction: &JumpInstruction,
+                    opcode: 2,
+                    operands: &[Fq::zero()],
+                    program: &machine.program,
+                    oracle: &machine.oracle,
+                },
+            ),
+            Err(ProverError::UnsatisfiedStep)
+        ));
+        machine.verify(&proof).unwrap();
+
+        // A proof is rejected unless execution started at the first word and has halted, even if
+        // it's a valid base case.
+        let mut base = machine.start(z0).unwrap();
+        base.verify(machine.params()).unwrap();
+        assert!(matches!(
+            machine.verify(&base),
+            Err(VerificationError::InstructionPointerOutOfRange)
+        ));
+        base.z0[0] = Fq::from(3u64);
+        base.output[0] = Fq::from(3u64);
+        base.verify(machine.params()).unwrap();
+        assert!(matches!(
+            machine.verify(&base),
+            Err(VerificationError::InstructionPointerOutOfRange)
+        ));
+    }
+}
@@ -0,0 +1,457 @@
+//! Offline memory checking, which lets step circuits read from and write to a large memory
+//! without carrying it in the state of the program. Every cell holds a value and the timestamp of
+//! its last access. Instead of the cells, the state carries fingerprints of the multisets of all
+//! cells read and written, which are checked against each other once the program is done.
+//!
+//! A cell `(a, v, t)` is fingerprinted as `gamma - (a + alpha * v + alpha^2 * t)`, and a multiset
+//! by the product of the fingerprints of its cells. The initial memory is written at timestamp
+//! zero, every access reads a cell at an earlier timestamp and writes it back at the current one,
+//! and the final memory is read out when auditing. The memory is consistent if and only if the
+//! read and written multisets match, except with negligible probability over the challenges.
+//!
+//! The challenges are derived with Fiat-Shamir once the accesses are fixed. The state also carries
+//! a running hash of every access, and the challenges hash it alongside the initial and the final
+//! memory. The prover executes the program natively to derive them before proving it, and the
+//! audit checks that the challenges the program started with are the ones derived from the hash
+//! it ended with.
+
+use crate::{poseidon_constants, RandomOracle, VerificationError};
+use ark_crypto_primitives::sponge::{poseidon::PoseidonConfig, Absorb};
+use ark_ff::{BigInteger, PrimeField};
+use ark_r1cs_std::{
+    alloc::AllocVar,
+    fields::{fp::FpVar, FieldVar},
+    R1CSVar,
+};
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+use core::cmp::Ordering;
+
+/// The number of elements of the state of a program which are taken by a memory: the challenges
+/// `alpha` and `gamma`, the current timestamp, the fingerprints of the read and written sets, and
+/// the hash of the accesses so far.
+pub const MEMORY_ARITY: usize = 6;
+
+// Returns the fingerprint of a cell at the given address.
+fn fingerprint<F: PrimeField>(alpha: F, gamma: F, addr: usize, value: F, ts: u64) -> F {
+    gamma - (F::from(addr as u64) + alpha * value + alpha * alpha * F::from(ts))
+}
+
+/// A memory of values over the field `F`, alongside the timestamps of their last accesses and the
+/// hash of all accesses, which is taken with the random oracle `R`. This is the native
+/// counterpart of [`MemoryVar`], which provides the values read in-circuit.
+#[derive(Clone, Debug)]
+pub struct Memory<F: PrimeField, R: RandomOracle<F> = PoseidonConfig<F>> {
+    cells: Vec<(F, u64)>,
+    ts: u64,
+    trace: F,
+    oracle: R,
+}
+
+impl<F: PrimeField, R: RandomOracle<F>> PartialEq for Memory<F, R> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cells == other.cells && self.ts == other.ts && self.trace == other.trace
+    }
+}
+
+impl<F: PrimeField, R: RandomOracle<F>> Eq for Memory<F, R> {}
+
+impl<F: PrimeField + Absorb> Memory<F> {
+    /// Returns a memory holding the given values, which are written at timestamp zero. Accesses
+    /// are hashed with the default Poseidon sponge.
+    pub fn new(values: Vec<F>) -> Self {
+        Self::with_oracle(values, poseidon_constants())
+    }
+}
+
+impl<F: PrimeField, R: RandomOracle<F>> Memory<F, R> {
+    /// Returns a memory like [`Memory::new`], but hashes accesses with the given random oracle.
+    pub fn with_oracle(values: Vec<F>, oracle: R) -> Self {
+        Self {
+            cells: values.into_iter().map(|v| (v, 0)).collect(),
+            ts: 1,
+            trace: F::zero(),
+            oracle,
+        }
+    }
+
+    /// Returns the number of cells of the memory.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Returns whether the memory has no cells.
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Returns the initial state of a program which starts with this memory, and ends with the
+    /// given memory after executing natively. The state should be part of the initial input of
+    /// the program, and its challenges are derived from the accesses that led to `last`.
+    pub fn state(&self, last: &Self) -> Vec<F> {
+        let (alpha, gamma) = self.challenges(last, last.trace);
+        let written = self.fingerprint(alpha, gamma);
+        vec![
+            alpha,
+            gamma,
+            F::from(self.ts),
+            F::one(),
+            written,
+            self.trace,
+        ]
+    }
+
+    /// Reads the value at the given address, like [`MemoryVar::read`] does in-circuit. Cells out
+    /// of range read as zero, which fails the audit.
+    pub fn read(&mut self, addr: usize) -> F {
+        let value = self.cells.get(addr).map_or(F::zero(), |(v, _)| *v);
+        self.write(addr, value);
+        value
+    }
+
+    /// Writes a value at the given address, like [`MemoryVar::write`] does in-circuit.
+    pub fn write(&mut self, addr: usize, value: F) {
+        let (old, old_ts) = self.cells.get(addr).copied().unwrap_or_default();
+        self.trace = self.oracle.hash(&[
+            self.trace,
+            F::from(addr as u64),
+            old,
+            F::from(old_ts),
+            value,
+        ]);
+        if let Some(cell) = self.cells.get_mut(addr) {
+            *cell = (value, self.ts);
+        }
+        self.ts += 1;
+    }
+
+    /// Audits a program which started with the memory `initial`, and ended with this memory,
+    /// given its initial and final state. This checks that the program started with the state of
+    /// `initial` and the challenges derived from its accesses, and that these are consistent with
+    /// this memory.
+    pub fn audit(&self, initial: &Self, z0: &[F], state: &[F]) -> Result<(), VerificationError<F>> {
+        let [alpha, gamma, _, read, written, trace] = state else {
+            return Err(VerificationError::MemoryMismatch);
+        };
+        if self.len() != initial.len() {
+            return Err(VerificationError::MemoryMismatch);
+        }
+
+        // The challenges are derived from the accesses the program ended with, and have to be
+        // the ones it started and ended with.
+        let (expected_alpha, expected_gamma) = initial.challenges(self, *trace);
+        let expected = vec![
+            expected_alpha,
+            expected_gamma,
+            F::from(initial.ts),
+            F::one(),
+            initial.fingerprint(expected_alpha, expected_gamma),
+            initial.trace,
+        ];
+        if z0 != expected || (*alpha, *gamma) != (expected_alpha, expected_gamma) {
+            return Err(VerificationError::MemoryMismatch);
+        }
+
+        // Reading out the final memory completes the read set.
+        if *read * self.fingerprint(*alpha, *gamma) != *written {
+            return Err(VerificationError::MemoryMismatch);
+        }
+
+        Ok(())
+    }
+
+    // Derives the challenges of a program from the hash of its accesses, alongside this memory it
+    // started with and the memory it ended with.
+    fn challenges(&self, last: &Self, trace: F) -> (F, F) {
+        let cells = |memory: &Self| {
+            memory
+                .cells
+                .iter()
+                .flat_map(|(v, ts)| [*v, F::from(*ts)])
+                .collect::<Vec<F>>()
+        };
+        let hash = self
+            .oracle
+            .hash(&[vec![trace], cells(self), cells(last)].concat());
+        (
+            self.oracle.hash(&[hash, F::zero()]),
+            self.oracle.hash(&[hash, F::one()]),
+        )
+    }
+
+    // Returns the fingerprint of the multiset of all cells of the memory.
+    fn fingerprint(&self, alpha: F, gamma: F) -> F {
+        self.cells
+            .iter()
+            .enumerate()
+            .map(|(addr, (v, ts))| fingerprint(alpha, gamma, addr, *v, *ts))
+            .product()
+    }
+}
+
+/// A memory within a step circuit over the field `F`, which updates the state of a [`Memory`] with
+/// every access. The values read are provided by a native copy of the memory.
+pub struct MemoryVar<F: PrimeField, R: RandomOracle<F> = PoseidonConfig<F>> {
+    memory: Memory<F, R>,
+    alpha: FpVar<F>,
+    gamma: FpVar<F>,
+    ts: FpVar<F>,
+    read: FpVar<F>,
+    written: FpVar<F>,
+    trace: FpVar<F>,
+}
+
+impl<F: PrimeField, R: RandomOracle<F>> MemoryVar<F, R> {
+    /// Returns a memory over the given state, which takes [`MEMORY_ARITY`] elements of the input
+    /// of the step circuit. `memory` should be the native memory at the start of the step.
+    pub fn new(memory: Memory<F, R>, state: &[FpVar<F>]) -> Result<Self, SynthesisError> {
+        let [alpha, gamma, ts, read, written, trace] = state else {
+            return Err(SynthesisError::Unsatisfiable);
+        };
+        Ok(Self {
+            memory,
+            alpha: alpha.clone(),
+            gamma: gamma.clone(),
+            ts: ts.clone(),
+            read: read.clone(),
+            written: written.clone(),
+            trace: trace.clone(),
+        })
+    }
+
+    /// Reads the value at the given address.
+    pub fn read(
+        &mut self,
+        cs: ConstraintSystemRef<F>,
+        addr: &FpVar<F>,
+    ) -> Result<FpVar<F>, SynthesisError> {
+        self.access(cs, addr, None)
+    }
+
+    /// Writes a value at the given address.
+    pub fn write(
+        &mut self,
+        cs: ConstraintSystemRef<F>,
+        addr: &FpVar<F>,
+        value: &FpVar<F>,
+    ) -> Result<(), SynthesisError> {
+        self.access(cs, addr, Some(value)).map(|_| ())
+    }
+
+    /// Returns the state of the memory after the accesses so far, which should be part of the
+    /// output of the step circuit.
+    pub fn state(&self) -> Vec<FpVar<F>> {
+        vec![
+            self.alpha.clone(),
+            self.gamma.clone(),
+            self.ts.clone(),
+            self.read.clone(),
+            self.written.clone(),
+            self.trace.clone(),
+        ]
+    }
+
+    /// Returns the native memory after the accesses so far.
+    pub fn memory(&self) -> &Memory<F, R> {
+        &self.memory
+    }
+
+    // Reads the cell at the given address, and writes it back with the given value, or with the
+    // value read if there is none. Returns the value read.
+    fn access(
+        &mut self,
+        cs: ConstraintSystemRef<F>,
+        addr: &FpVar<F>,
+        value: Option<&FpVar<F>>,
+    ) -> Result<FpVar<F>, SynthesisError> {
+        // The cell is provided by the native memory, and only needs to be from the past.
+        let index = addr.value().map(|v| to_index(&v));
+        let cell = index.map(|i| self.memory.cells.get(i).copied().unwrap_or_default());
+        let old = FpVar::new_witness(cs.clone(), || cell.map(|(v, _)| v))?;
+        let old_ts = FpVar::new_witness(cs.clone(), || cell.map(|(_, ts)| F::from(ts)))?;
+        old_ts.enforce_cmp(&self.ts, Ordering::Less, false)?;
+
+        let new = value.unwrap_or(&old);
+        let alpha_sq = self.alpha.square()?;
+        self.read *= &self.gamma - (addr + &self.alpha * &old + &alpha_sq * &old_ts);
+        self.written *= &self.gamma - (addr + &self.alpha * new + &alpha_sq * &self.ts);
+        self.ts += FpVar::one();
+        self.trace = self.memory.oracle.hash_var(
+            cs,
+            &[
+                self.trace.clone(),
+                addr.clone(),
+                old.clone(),
+                old_ts,
+                new.clone(),
+            ],
+        )?;
+
+        if let Ok(i) = index {
+            self.memory.write(i, new.value().unwrap_or_default());
+        }
+        Ok(old)
+    }
+}
+
+// Returns the address held by a field element, saturating at the largest address.
+fn to_index<F: PrimeField>(v: &F) -> usize {
+    let bits = v.into_bigint().to_bits_le();
+    if bits[usize::BITS as usize..].iter().any(|bit| *bit) {
+        return usize::MAX;
+    }
+    bits[..usize::BITS as usize]
+        .iter()
+        .rev()
+        .fold(0, |acc, bit| (acc << 1) | *bit as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cycle::pasta::{Fq, PallasVesta},
+        r1cs::R1CS,
+        Arithmetization, Proof, PublicParamsBuilder, StepCircuit,
+    };
+    use ark_ff::{One, Zero};
+    use ark_relations::r1cs::ConstraintSystem;
+
+    // Adds the value at address zero to the input, and writes the sum to address one.
+    struct SumCircuit {
+        memory: Memory<Fq>,
+    }
+
+    impl StepCircuit<Fq> for SumCircuit {
+        fn arity(&self) -> usize {
+            1 + MEMORY_ARITY
+        }
+
+        fn synthesize(
+            &self,
+            cs: ConstraintSystemRef<Fq>,
+            z: &[FpVar<Fq>],
+        ) -> Result<(FpVar<Fq>, Vec<FpVar<Fq>>), SynthesisError> {
+            let mut memory = MemoryVar::new(self.memory.clone(), &z[1..])?;
+            let x = memory.read(cs.clone(), &FpVar::zero())?;
+            let y = &z[0] + x;
+            memory.write(cs.clone(), &FpVar::one(), &y)?;
+            Ok((FpVar::zero(), [vec![y], memory.state()].concat()))
+        }
+    }
+
+    // Runs a step of the sum circuit over the given state and memory, and returns the output.
+    fn step(memory: &Memory<Fq>, z: &[Fq]) -> (Vec<Fq>, Memory<Fq>) {
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let z = z
+            .iter()
+            .map(|v| FpVar::new_witness(cs.clone(), || Ok(*v)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let mut var = MemoryVar::new(memory.clone(), &z[1..]).unwrap();
+        let x = var.read(cs.clone(), &FpVar::zero()).unwrap();
+        let y = &z[0] + x;
+        var.write(cs.clone(), &FpVar::one(), &y).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+        let output = [vec![y], var.state()]
+            .concat()
+            .iter()
+            .map(|v| v.value().unwrap())
+            .collect();
+        (output, var.memory().clone())
+    }
+
+    // Executes the sum circuit natively for the given number of steps, and returns the final
+    // memory.
+    fn execute(initial: &Memory<Fq>, steps: usize) -> Memory<Fq> {
+        let (mut memory, mut sum) = (initial.clone(), Fq::one());
+        for _ in 0..steps {
+            sum += memory.read(0);
+            memory.write(1, sum);
+        }
+        memory
+    }
+
+    #[test]
+    fn test_memory() {
+        let initial = Memory::new(vec![Fq::from(3u64), Fq::zero(), Fq::from(7u64)]);
+        let native = execute(&initial, 2);
+        let z0 = [vec![Fq::one()], initial.state(&native)].concat();
+
+        // The accesses in-circuit match the native ones.
+        let (z1, memory) = step(&initial, &z0);
+        let (z2, memory) = step(&memory, &z1);
+        assert_eq!(memory, native);
+        memory.audit(&initial, &z0[1..], &z2[1..]).unwrap();
+
+        // Another final memory fails the audit.
+        let mut other = memory.clone();
+        other.cells[2].0 += Fq::one();
+        assert!(matches!(
+            other.audit(&initial, &z0[1..], &z2[1..]),
+            Err(VerificationError::MemoryMismatch)
+        ));
+
+        // So does another initial memory, or challenges which weren't derived from the accesses.
+        let mut other = initial.clone();
+        other.cells[0].0 += Fq::one();
+        assert!(matches!(
+            memory.audit(&other, &z0[1..], &z2[1..]),
+            Err(VerificationError::MemoryMismatch)
+        ));
+        let z0 = [vec![Fq::one()], initial.state(&execute(&initial, 1))].concat();
+        let (z1, memory) = step(&initial, &z0);
+        let (z2, memory) = step(&memory, &z1);
+        assert!(matches!(
+            memory.audit(&initial, &z0[1..], &z2[1..]),
+            Err(VerificationError::MemoryMismatch)
+        ));
+
+        // A step given a stale memory still satisfies its constraints, as it reads from the past,
+        // but fails the audit.
+        let z0 = [vec![Fq::one()], initial.state(&execute(&initial, 3))].concat();
+        let (z1, memory) = step(&initial, &z0);
+        let (z2, memory) = step(&memory, &z1);
+        let mut stale = memory.clone();
+        stale.cells[0] = (Fq::from(5u64), 0);
+        let (z3, _) = step(&stale, &z2);
+        assert!(matches!(
+            execute(&initial, 3).audit(&initial, &z0[1..], &z3[1..]),
+            Err(VerificationError::MemoryMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_memory_proof() {
+        // The challenges are derived from a native execution before proving it.
+        let initial = Memory::new(vec![Fq::from(3u64), Fq::zero()]);
+        let z0 = [vec![Fq::one()], initial.state(&execute(&initial, 2))].concat();
+        let circuit = SumCircuit {
+            memory: initial.clone(),
+        };
+        let params =
+            PublicParamsBuilder::<PallasVesta, R1CS<PallasVesta>>::new(b"supernova", z0.clone())
+                .circuit(&circuit)
+                .unwrap()
+                .build()
+                .unwrap();
+        let folded = R1CS::new(&params, 0, z0.clone(), &circuit).unwrap();
+        let mut proof = Proof::new(&params, vec![folded], z0).unwrap();
+
+        // The memory state is carried in the output, and so is bound by the hash of the state.
+        let (mut memory, mut sum) = (initial.clone(), Fq::one());
+        for _ in 0..2 {
+            let circuit = SumCircuit {
+                memory: memory.clone(),
+            };
+            proof.update(&params, &circuit).unwrap();
+            proof.verify(&params).unwrap();
+
+            sum += memory.read(0);
+            memory.write(1, sum);
+            assert_eq!(proof.output()[0], sum);
+        }
+        memory
+            .audit(&initial, &proof.z0()[1..], &proof.output()[1..])
+            .unwrap();
+    }
+}
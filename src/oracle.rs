@@ -0,0 +1,160 @@
+//! Random oracles used for hashing the state of the augmented circuits and for deriving the
+//! challenges of folding. Every oracle is evaluated both natively and in-circuit, and both
+//! evaluations should agree.
+
+use crate::serialization::{constants_serialized_size, deserialize_constants, serialize_constants};
+use ark_crypto_primitives::sponge::{
+    constraints::CryptographicSpongeVar,
+    poseidon::{
+        constraints::PoseidonSpongeVar, find_poseidon_ark_and_mds, PoseidonConfig, PoseidonSponge,
+    },
+    Absorb, CryptographicSponge, FieldBasedCryptographicSponge,
+};
+use ark_ff::PrimeField;
+use ark_r1cs_std::fields::fp::FpVar;
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+use ark_serialize::{Compress, Read, SerializationError, Validate, Write};
+
+/// A random oracle over the field `F`, which hashes a sequence of field elements into a single
+/// one. Its parameters are serialized into the digests of the circuits that use it.
+pub trait RandomOracle<F: PrimeField>: Clone + Send + Sync {
+    /// Hashes the given elements natively.
+    fn hash(&self, inputs: &[F]) -> F;
+
+    /// Hashes the given elements in-circuit.
+    fn hash_var(
+        &self,
+        cs: ConstraintSystemRef<F>,
+        inputs: &[FpVar<F>],
+    ) -> Result<FpVar<F>, SynthesisError>;
+
+    /// Serializes the parameters of the oracle.
+    fn serialize_with_mode<W: Write>(
+        &self,
+        writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError>;
+
+    /// Returns the size of the serialized parameters of the oracle.
+    fn serialized_size(&self, compress: Compress) -> usize;
+
+    /// Deserializes the parameters of the oracle, and ensures that they are well-formed.
+    fn deserialize_with_mode<R: Read>(
+        reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError>;
+}
+
+impl<F: PrimeField + Absorb> RandomOracle<F> for PoseidonConfig<F> {
+    fn hash(&self, inputs: &[F]) -> F {
+        let mut sponge = PoseidonSponge::<F>::new(self);
+        sponge.absorb(&inputs);
+        sponge.squeeze_native_field_elements(1)[0]
+    }
+
+    fn hash_var(
+        &self,
+        cs: ConstraintSystemRef<F>,
+        inputs: &[FpVar<F>],
+    ) -> Result<FpVar<F>, SynthesisError> {
+        let mut sponge = PoseidonSpongeVar::<F>::new(cs, self);
+        sponge.absorb(&inputs)?;
+        Ok(sponge.squeeze_field_elements(1)?.remove(0))
+    }
+
+    fn serialize_with_mode<W: Write>(
+        &self,
+        writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        serialize_constants(self, writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        constants_serialized_size(self, compress)
+    }
+
+    fn deserialize_with_mode<R: Read>(
+        reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        deserialize_constants(reader, compress, validate)
+    }
+}
+
+/// Returns the constants of a Poseidon sponge over `F` with a capacity of one element, and the
+/// given rate, amounts of rounds and S-box exponent. Wider rates absorb more elements per
+/// permutation, while more rounds raise the security level.
+pub fn poseidon_config<F: PrimeField>(
+    rate: usize,
+    full_rounds: usize,
+    partial_rounds: usize,
+    alpha: u64,
+) -> PoseidonConfig<F> {
+    let (ark, mds) = find_poseidon_ark_and_mds(
+        F::MODULUS_BIT_SIZE as u64,
+        rate,
+        full_rounds as u64,
+        partial_rounds as u64,
+        0,
+    );
+    PoseidonConfig {
+        full_rounds,
+        partial_rounds,
+        alpha,
+        ark,
+        mds,
+        rate,
+        capacity: 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cycle::pasta::Fq;
+    use ark_r1cs_std::{alloc::AllocVar, R1CSVar};
+    use ark_relations::r1cs::ConstraintSystem;
+    use rand_core::OsRng;
+
+    fn oracle<F: PrimeField + Absorb, R: RandomOracle<F>>(oracle: &R) {
+        let inputs = (0..7).map(|_| F::rand(&mut OsRng)).collect::<Vec<F>>();
+        let hash = oracle.hash(&inputs);
+
+        // The in-circuit hash agrees with the native one.
+        let cs = ConstraintSystem::<F>::new_ref();
+        let vars = inputs
+            .iter()
+            .map(|v| FpVar::new_witness(cs.clone(), || Ok(*v)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            oracle.hash_var(cs.clone(), &vars).unwrap().value().unwrap(),
+            hash
+        );
+        assert!(cs.is_satisfied().unwrap());
+
+        // The parameters survive serialization.
+        let mut bytes = vec![];
+        oracle
+            .serialize_with_mode(&mut bytes, Compress::Yes)
+            .unwrap();
+        assert_eq!(bytes.len(), oracle.serialized_size(Compress::Yes));
+        let other = R::deserialize_with_mode(&bytes[..], Compress::Yes, Validate::Yes).unwrap();
+        assert_eq!(other.hash(&inputs), hash);
+    }
+
+    #[test]
+    fn test_poseidon() {
+        let narrow = poseidon_config::<Fq>(2, 8, 43, 5);
+        let wide = poseidon_config::<Fq>(4, 8, 56, 5);
+        oracle(&narrow);
+        oracle(&wide);
+
+        // Other parameters yield other hashes.
+        let inputs = [Fq::from(1u64), Fq::from(2u64)];
+        assert_ne!(narrow.hash(&inputs), wide.hash(&inputs));
+    }
+}
@@ -0,0 +1,277 @@
+//! The public parameters of a SuperNova proof, which bundle the setup material of a program so
+//! that the prover and verifier can't use mismatching pieces of it.
+
+use crate::{
+    arithmetization::{either::Either, Arithmetization, Key, StepCircuit},
+    circuit::{program_digest, synthesize_secondary},
+    cycle::Swapped,
+    poseidon_constants,
+    r1cs::{R1CSShape, R1CS},
+    CommitmentScheme, CurveCycle, Pedersen, ProverError, RandomOracle, VerifierKey,
+};
+use ark_crypto_primitives::sponge::poseidon::PoseidonConfig;
+use ark_ff::Zero;
+use core::marker::PhantomData;
+
+/// The public parameters of a SuperNova proof over the curve cycle `G`, which hold the keys and
+/// random oracles used on either curve of the cycle, alongside the shapes of all circuits of the
+/// program and their digests. The label the keys are derived from is kept, as compressing a proof
+/// derives further generators from it. The oracles default to Poseidon.
+pub struct PublicParams<
+    G: CurveCycle,
+    A: Arithmetization<G>,
+    S: CommitmentScheme<G::C2> = Pedersen,
+    R1: RandomOracle<G::F1> = PoseidonConfig<<G as CurveCycle>::F1>,
+    R2: RandomOracle<G::F2> = PoseidonConfig<<G as CurveCycle>::F2>,
+> {
+    pub(crate) label: Vec<u8>,
+    pub(crate) key: Key<G, A>,
+    pub(crate) secondary_key: S::Key,
+    pub(crate) oracle: R1,
+    pub(crate) secondary_oracle: R2,
+    pub(crate) shapes: Vec<A::Shape>,
+    pub(crate) digests: Vec<G::F1>,
+    pub(crate) secondary_shape: R1CSShape<G::F2>,
+    pub(crate) secondary_digest: G::F2,
+}
+
+impl<
+        G: CurveCycle,
+        A: Arithmetization<G>,
+        S: CommitmentScheme<G::C2>,
+        R1: RandomOracle<G::F1>,
+        R2: RandomOracle<G::F2>,
+    > PublicParams<G, A, S, R1, R2>
+{
+    /// Returns the key used for committing to the circuits of the program.
+    pub fn key(&self) -> &Key<G, A> {
+        &self.key
+    }
+
+    /// Returns the key used for committing to the secondary circuit.
+    pub fn secondary_key(&self) -> &S::Key {
+        &self.secondary_key
+    }
+
+    /// Returns the random oracle used for hashing in the circuits of the program.
+    pub fn oracle(&self) -> &R1 {
+        &self.oracle
+    }
+
+    /// Returns the random oracle used for hashing in the secondary circuit.
+    pub fn secondary_oracle(&self) -> &R2 {
+        &self.secondary_oracle
+    }
+
+    /// Returns the shapes of the circuits of the program, in order of their program counter.
+    pub fn shapes(&self) -> &[A::Shape] {
+        &self.shapes
+    }
+
+    /// Returns the shape of the secondary circuit.
+    pub fn secondary_shape(&self) -> &R1CSShape<G::F2> {
+        &self.secondary_shape
+    }
+
+    /// Returns the key needed for verifying the public part of a proof.
+    pub fn verifier_key(&self) -> VerifierKey<G, R1, R2> {
+        VerifierKey {
+            params: self.digests.clone(),
+            secondary_params: self.secondary_digest,
+            oracle: self.oracle.clone(),
+            secondary_oracle: self.secondary_oracle.clone(),
+        }
+    }
+
+    // Returns the digest of the circuit at the given program counter.
+    pub(crate) fn circuit_digest(&self, pc: usize) -> Result<G::F1, ProverError> {
+        self.digests
+            .get(pc)
+            .copied()
+            .ok_or(ProverError::PCOutOfRange(pc, self.digests.len()))
+    }
+
+    // Returns the program digest, which hashes the digests of all circuits in order.
+    pub(crate) fn program_digest(&self) -> G::F1 {
+        program_digest(&self.oracle, &self.digests)
+    }
+}
+
+/// A builder for [`PublicParams`], which synthesizes every circuit of the program in order of
+/// their program counter, and sets up keys for as many bases as the largest circuit on either
+/// curve needs. Schemes may round the size of their keys up, such as to a power of two.
+pub struct PublicParamsBuilder<
+    G: CurveCycle,
+    A: Arithmetization<G>,
+    S: CommitmentScheme<G::C2> = Pedersen,
+    R1: RandomOracle<G::F1> = PoseidonConfig<<G as CurveCycle>::F1>,
+    R2: RandomOracle<G::F2> = PoseidonConfig<<G as CurveCycle>::F2>,
+> {
+    label: Vec<u8>,
+    z0: Vec<G::F1>,
+    oracle: R1,
+    secondary_oracle: R2,
+    shapes: Vec<A::Shape>,
+    _scheme: PhantomData<S>,
+}
+
+impl<G: CurveCycle, A: Arithmetization<G>, S: CommitmentScheme<G::C2>>
+    PublicParamsBuilder<G, A, S>
+{
+    /// Starts building the parameters of a program with the given initial input, whose keys are
+    /// derived from `label`. Hashing is done with the default Poseidon sponges.
+    pub fn new(label: &[u8], z0: Vec<G::F1>) -> Self {
+        Self::with_oracles(label, z0, poseidon_constants(), poseidon_constants())
+    }
+}
+
+impl<
+        G: CurveCycle,
+        A: Arithmetization<G>,
+        S: CommitmentScheme<G::C2>,
+        R1: RandomOracle<G::F1>,
+        R2: RandomOracle<G::F2>,
+    > PublicParamsBuilder<G, A, S, R1, R2>
+{
+    /// Starts building the parameters of a program like [`PublicParamsBuilder::new`], but hashes
+    /// with the given random oracles over either field of the cycle.
+    pub fn with_oracles(label: &[u8], z0: Vec<G::F1>, oracle: R1, secondary_oracle: R2) -> Self {
+        Self {
+            label: label.to_vec(),
+            z0,
+            oracle,
+            secondary_oracle,
+            shapes: vec![],
+            _scheme: PhantomData,
+        }
+    }
+
+    /// Adds the next circuit of the program, which is invoked whenever the program counter equals
+    /// the amount of circuits added before it. Fails if the circuit can't be synthesized on the
+    /// initial input.
+    pub fn circuit<C: StepCircuit<G::F1> + ?Sized>(
+        mut self,
+        circuit: &C,
+    ) -> Result<Self, ProverError> {
+        let shape = A::shape(self.shapes.len(), &self.z0, &self.oracle, circuit)?;
+        self.shapes.push(shape);
+        Ok(self)
+    }
+
+    /// Sets up the keys for the circuits added so far, and digests their shapes. The shape of the
+    /// secondary circuit depends on the amount of circuits it folds, but not on the circuits
+    /// themselves, so it's synthesized over placeholder instances. Fails if no circuits were
+    /// added, as the program counter can't select any, or if either scheme needs a trusted setup,
+    /// whose keys should be given to [`PublicParamsBuilder::build_with_keys`] instead.
+    pub fn build(self) -> Result<PublicParams<G, A, S, R1, R2>, ProverError> {
+        let secondary_shape = self.secondary_shape()?;
+        let size = self.shapes.iter().map(A::key_size).max().unwrap_or(0);
+        let key = A::Scheme::setup(&self.label, size)?;
+        let secondary_key = S::setup(
+            &self.label,
+            R1CS::<Swapped<G>, S>::key_size(&secondary_shape),
+        )?;
+        self.finish(key, secondary_key, secondary_shape)
+    }
+
+    /// Digests the shapes of the circuits added so far like [`PublicParamsBuilder::build`], but
+    /// with the given keys, such as keys loaded from the reference string of a trusted setup.
+    /// Fails if no circuits were added, or if either key has fewer bases than the largest circuit
+    /// on its curve needs.
+    pub fn build_with_keys(
+        self,
+        key: Key<G, A>,
+        secondary_key: S::Key,
+    ) -> Result<PublicParams<G, A, S, R1, R2>, ProverError> {
+        let secondary_shape = self.secondary_shape()?;
+        let sizes = [
+            (
+                self.shapes.iter().map(A::key_size).max().unwrap_or(0),
+                A::Scheme::bases(&key).len(),
+            ),
+            (
+                R1CS::<Swapped<G>, S>::key_size(&secondary_shape),
+                S::bases(&secondary_key).len(),
+            ),
+        ];
+        if let Some((required, available)) = sizes
+            .into_iter()
+            .find(|(required, available)| required > available)
+        {
+            return Err(ProverError::KeyTooSmall(required, available));
+        }
+
+        self.finish(key, secondary_key, secondary_shape)
+    }
+
+    // Synthesizes the shape of the secondary circuit, which fails if no circuits were added.
+    fn secondary_shape(&self) -> Result<R1CSShape<G::F2>, ProverError> {
+        if self.shapes.is_empty() {
+            return Err(ProverError::PCOutOfRange(0, 0));
+        }
+
+        let (running, latest, proof) = A::placeholders(&self.shapes);
+        let cs = synthesize_secondary::<G, _, _>(
+            G::F2::zero(),
+            0,
+            0,
+            &running,
+            &latest,
+            &proof,
+            &self.secondary_oracle,
+        )?;
+        Ok(R1CSShape::from_cs(&cs)?)
+    }
+
+    // Digests the shapes of all circuits with the keys they're committed with.
+    fn finish(
+        self,
+        key: Key<G, A>,
+        secondary_key: S::Key,
+        secondary_shape: R1CSShape<G::F2>,
+    ) -> Result<PublicParams<G, A, S, R1, R2>, ProverError> {
+        let digests = self
+            .shapes
+            .iter()
+            .map(|shape| A::digest(shape, &self.oracle, &key))
+            .collect::<Result<Vec<_>, _>>()?;
+        let secondary_digest = R1CS::<Swapped<G>, S>::digest(
+            &secondary_shape,
+            &self.secondary_oracle,
+            &secondary_key,
+        )?;
+
+        Ok(PublicParams {
+            label: self.label,
+            key,
+            secondary_key,
+            oracle: self.oracle,
+            secondary_oracle: self.secondary_oracle,
+            shapes: self.shapes,
+            digests,
+            secondary_shape,
+            secondary_digest,
+        })
+    }
+}
+
+impl<G, A, B, S, R1, R2> PublicParamsBuilder<G, Either<A, B>, S, R1, R2>
+where
+    G: CurveCycle,
+    A: Arithmetization<G>,
+    B: Arithmetization<G, Scheme = A::Scheme>,
+    S: CommitmentScheme<G::C2>,
+    R1: RandomOracle<G::F1>,
+    R2: RandomOracle<G::F2>,
+{
+    /// Adds the next circuit of the program like [`PublicParamsBuilder::circuit`], but
+    /// arithmetizes it with `B` rather than `A`.
+    pub fn right_circuit<C: StepCircuit<G::F1> + ?Sized>(
+        mut self,
+        circuit: &C,
+    ) -> Result<Self, ProverError> {
+        let shape = B::shape(self.shapes.len(), &self.z0, &self.oracle, circuit)?;
+        self.shapes.push(Either::Right(shape));
+        Ok(self)
+    }
+}
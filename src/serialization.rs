@@ -0,0 +1,452 @@
+//! Serialization of proofs and their public parameters, so that long-running computations can be
+//! checkpointed to disk and resumed elsewhere. Top-level structures are prefixed with a format
+//! version, which is checked when deserializing.
+
+use crate::{
+    cycle::Swapped, r1cs::R1CS, Arithmetization, CommitmentScheme, CurveCycle, Proof,
+    ProofInstance, RandomOracle, VerifierKey,
+};
+use ark_crypto_primitives::sponge::poseidon::PoseidonConfig;
+use ark_ff::PrimeField;
+use ark_relations::r1cs::ConstraintMatrices;
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, Read, SerializationError, Valid, Validate,
+    Write,
+};
+
+/// The version of the serialization format. This should be bumped whenever the layout of any
+/// serialized structure changes.
+pub const SERIALIZATION_VERSION: u8 = 5;
+
+fn serialize_version<W: Write>(writer: W) -> Result<(), SerializationError> {
+    SERIALIZATION_VERSION.serialize_uncompressed(writer)
+}
+
+fn deserialize_version<R: Read>(reader: R) -> Result<(), SerializationError> {
+    if u8::deserialize_uncompressed(reader)? != SERIALIZATION_VERSION {
+        return Err(SerializationError::InvalidData);
+    }
+
+    Ok(())
+}
+
+/// Serializes the Poseidon constants used for hashing.
+pub(crate) fn serialize_constants<F: PrimeField, W: Write>(
+    constants: &PoseidonConfig<F>,
+    mut writer: W,
+    compress: Compress,
+) -> Result<(), SerializationError> {
+    constants
+        .full_rounds
+        .serialize_with_mode(&mut writer, compress)?;
+    constants
+        .partial_rounds
+        .serialize_with_mode(&mut writer, compress)?;
+    constants.alpha.serialize_with_mode(&mut writer, compress)?;
+    constants.ark.serialize_with_mode(&mut writer, compress)?;
+    constants.mds.serialize_with_mode(&mut writer, compress)?;
+    constants.rate.serialize_with_mode(&mut writer, compress)?;
+    constants
+        .capacity
+        .serialize_with_mode(&mut writer, compress)
+}
+
+pub(crate) fn constants_serialized_size<F: PrimeField>(
+    constants: &PoseidonConfig<F>,
+    compress: Compress,
+) -> usize {
+    constants.full_rounds.serialized_size(compress)
+        + constants.partial_rounds.serialized_size(compress)
+        + constants.alpha.serialized_size(compress)
+        + constants.ark.serialized_size(compress)
+        + constants.mds.serialized_size(compress)
+        + constants.rate.serialized_size(compress)
+        + constants.capacity.serialized_size(compress)
+}
+
+/// Deserializes the Poseidon constants used for hashing, and ensures that the round constants and
+/// MDS matrix have dimensions which match the round counts and the sponge width.
+pub(crate) fn deserialize_constants<F: PrimeField, R: Read>(
+    mut reader: R,
+    compress: Compress,
+    validate: Validate,
+) -> Result<PoseidonConfig<F>, SerializationError> {
+    let constants = PoseidonConfig {
+        full_rounds: usize::deserialize_with_mode(&mut reader, compress, validate)?,
+        partial_rounds: usize::deserialize_with_mode(&mut reader, compress, validate)?,
+        alpha: u64::deserialize_with_mode(&mut reader, compress, validate)?,
+        ark: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+        mds: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+        rate: usize::deserialize_with_mode(&mut reader, compress, validate)?,
+        capacity: usize::deserialize_with_mode(&mut reader, compress, validate)?,
+    };
+
+    let width = constants.rate + constants.capacity;
+    if constants.ark.len() != constants.full_rounds + constants.partial_rounds
+        || constants.ark.iter().any(|row| row.len() != width)
+        || constants.mds.len() != width
+        || constants.mds.iter().any(|row| row.len() != width)
+    {
+        return Err(SerializationError::InvalidData);
+    }
+
+    Ok(constants)
+}
+
+/// Serializes the shape of a circuit, including the column indices of every coefficient.
+pub(crate) fn serialize_matrices<F: PrimeField, W: Write>(
+    shape: &ConstraintMatrices<F>,
+    mut writer: W,
+    compress: Compress,
+) -> Result<(), SerializationError> {
+    shape
+        .num_instance_variables
+        .serialize_with_mode(&mut writer, compress)?;
+    shape
+        .num_witness_variables
+        .serialize_with_mode(&mut writer, compress)?;
+    shape
+        .num_constraints
+        .serialize_with_mode(&mut writer, compress)?;
+    shape.a.serialize_with_mode(&mut writer, compress)?;
+    shape.b.serialize_with_mode(&mut writer, compress)?;
+    shape.c.serialize_with_mode(&mut writer, compress)
+}
+
+pub(crate) fn matrices_serialized_size<F: PrimeField>(
+    shape: &ConstraintMatrices<F>,
+    compress: Compress,
+) -> usize {
+    shape.num_instance_variables.serialized_size(compress)
+        + shape.num_witness_variables.serialized_size(compress)
+        + shape.num_constraints.serialized_size(compress)
+        + shape.a.serialized_size(compress)
+        + shape.b.serialized_size(compress)
+        + shape.c.serialized_size(compress)
+}
+
+/// Deserializes the shape of a circuit. The non-zero counts are recomputed from the matrices, and
+/// every matrix is checked to have a row per constraint and only reference existing variables.
+pub(crate) fn deserialize_matrices<F: PrimeField, R: Read>(
+    mut reader: R,
+    compress: Compress,
+    validate: Validate,
+) -> Result<ConstraintMatrices<F>, SerializationError> {
+    let num_instance_variables = usize::deserialize_with_mode(&mut reader, compress, validate)?;
+    let num_witness_variables = usize::deserialize_with_mode(&mut reader, compress, validate)?;
+    let num_constraints = usize::deserialize_with_mode(&mut reader, compress, validate)?;
+    let a = Vec::<Vec<(F, usize)>>::deserialize_with_mode(&mut reader, compress, validate)?;
+    let b = Vec::<Vec<(F, usize)>>::deserialize_with_mode(&mut reader, compress, validate)?;
+    let c = Vec::<Vec<(F, usize)>>::deserialize_with_mode(&mut reader, compress, validate)?;
+
+    let num_variables = num_instance_variables + num_witness_variables;
+    if [&a, &b, &c].iter().any(|m| {
+        m.len() != num_constraints
+            || m.iter()
+                .flatten()
+                .any(|(_coeff, index)| *index >= num_variables)
+    }) {
+        return Err(SerializationError::InvalidData);
+    }
+
+    let num_non_zero = |m: &[Vec<(F, usize)>]| m.iter().map(|row| row.len()).sum();
+    Ok(ConstraintMatrices {
+        num_instance_variables,
+        num_witness_variables,
+        num_constraints,
+        a_num_non_zero: num_non_zero(&a),
+        b_num_non_zero: num_non_zero(&b),
+        c_num_non_zero: num_non_zero(&c),
+        a,
+        b,
+        c,
+    })
+}
+
+impl<G: CurveCycle, A: Arithmetization<G> + CanonicalSerialize, S: CommitmentScheme<G::C2>>
+    CanonicalSerialize for Proof<G, A, S>
+{
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        serialize_version(&mut writer)?;
+        self.folded.serialize_with_mode(&mut writer, compress)?;
+        self.folded_secondary
+            .serialize_with_mode(&mut writer, compress)?;
+        self.latest_secondary
+            .serialize_with_mode(&mut writer, compress)?;
+        self.z0.serialize_with_mode(&mut writer, compress)?;
+        self.output.serialize_with_mode(&mut writer, compress)?;
+        self.pc.serialize_with_mode(&mut writer, compress)?;
+        self.i.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        SERIALIZATION_VERSION.serialized_size(compress)
+            + self.folded.serialized_size(compress)
+            + self.folded_secondary.serialized_size(compress)
+            + self.latest_secondary.serialized_size(compress)
+            + self.z0.serialized_size(compress)
+            + self.output.serialized_size(compress)
+            + self.pc.serialized_size(compress)
+            + self.i.serialized_size(compress)
+    }
+}
+
+impl<G: CurveCycle, A: Arithmetization<G> + CanonicalDeserialize, S: CommitmentScheme<G::C2>> Valid
+    for Proof<G, A, S>
+{
+    fn check(&self) -> Result<(), SerializationError> {
+        // The circuit which is folded into on the next update must exist, its input must match
+        // the current output, and the public IO of both secondary pairs consists of the hashes of
+        // both circuits.
+        if self.pc >= self.folded.len()
+            || self.z0.len() != self.output.len()
+            || self.folded_secondary.instance.len() != 2
+            || self.latest_secondary.instance.len() != 2
+        {
+            return Err(SerializationError::InvalidData);
+        }
+
+        // The assignments of every pair should match its shape.
+        self.folded.check()?;
+        self.folded_secondary.check()?;
+        self.latest_secondary.check()
+    }
+}
+
+impl<G: CurveCycle, A: Arithmetization<G> + CanonicalDeserialize, S: CommitmentScheme<G::C2>>
+    CanonicalDeserialize for Proof<G, A, S>
+{
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        deserialize_version(&mut reader)?;
+        let proof = Self {
+            folded: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+            folded_secondary: R1CS::<Swapped<G>, S>::deserialize_with_mode(
+                &mut reader,
+                compress,
+                validate,
+            )?,
+            latest_secondary: R1CS::<Swapped<G>, S>::deserialize_with_mode(
+                &mut reader,
+                compress,
+                validate,
+            )?,
+            z0: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+            output: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+            pc: usize::deserialize_with_mode(&mut reader, compress, validate)?,
+            i: usize::deserialize_with_mode(&mut reader, compress, validate)?,
+        };
+
+        if validate == Validate::Yes {
+            proof.check()?;
+        }
+
+        Ok(proof)
+    }
+}
+
+impl<G: CurveCycle, R1: RandomOracle<G::F1>, R2: RandomOracle<G::F2>> CanonicalSerialize
+    for VerifierKey<G, R1, R2>
+{
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        serialize_version(&mut writer)?;
+        self.params.serialize_with_mode(&mut writer, compress)?;
+        self.secondary_params
+            .serialize_with_mode(&mut writer, compress)?;
+        self.oracle.serialize_with_mode(&mut writer, compress)?;
+        self.secondary_oracle
+            .serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        SERIALIZATION_VERSION.serialized_size(compress)
+            + self.params.serialized_size(compress)
+            + self.secondary_params.serialized_size(compress)
+            + self.oracle.serialized_size(compress)
+            + self.secondary_oracle.serialized_size(compress)
+    }
+}
+
+impl<G: CurveCycle, R1: RandomOracle<G::F1>, R2: RandomOracle<G::F2>> Valid
+    for VerifierKey<G, R1, R2>
+{
+    fn check(&self) -> Result<(), SerializationError> {
+        Ok(())
+    }
+}
+
+impl<G: CurveCycle, R1: RandomOracle<G::F1>, R2: RandomOracle<G::F2>> CanonicalDeserialize
+    for VerifierKey<G, R1, R2>
+{
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        deserialize_version(&mut reader)?;
+        Ok(Self {
+            params: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+            secondary_params: G::F2::deserialize_with_mode(&mut reader, compress, validate)?,
+            oracle: R1::deserialize_with_mode(&mut reader, compress, validate)?,
+            secondary_oracle: R2::deserialize_with_mode(&mut reader, compress, validate)?,
+        })
+    }
+}
+
+impl<G: CurveCycle, A: Arithmetization<G>> CanonicalSerialize for ProofInstance<G, A>
+where
+    A::Instance: CanonicalSerialize,
+{
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        serialize_version(&mut writer)?;
+        self.folded.serialize_with_mode(&mut writer, compress)?;
+        self.folded_secondary
+            .serialize_with_mode(&mut writer, compress)?;
+        self.latest_secondary
+            .serialize_with_mode(&mut writer, compress)?;
+        self.z0.serialize_with_mode(&mut writer, compress)?;
+        self.output.serialize_with_mode(&mut writer, compress)?;
+        self.pc.serialize_with_mode(&mut writer, compress)?;
+        self.i.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        SERIALIZATION_VERSION.serialized_size(compress)
+            + self.folded.serialized_size(compress)
+            + self.folded_secondary.serialized_size(compress)
+            + self.latest_secondary.serialized_size(compress)
+            + self.z0.serialized_size(compress)
+            + self.output.serialized_size(compress)
+            + self.pc.serialized_size(compress)
+            + self.i.serialized_size(compress)
+    }
+}
+
+impl<G: CurveCycle, A: Arithmetization<G>> Valid for ProofInstance<G, A>
+where
+    A::Instance: CanonicalDeserialize,
+{
+    fn check(&self) -> Result<(), SerializationError> {
+        // The public IO of the latest secondary instance consists of the hashes of both circuits.
+        if self.latest_secondary.io.len() != 2 {
+            return Err(SerializationError::InvalidData);
+        }
+
+        Ok(())
+    }
+}
+
+impl<G: CurveCycle, A: Arithmetization<G>> CanonicalDeserialize for ProofInstance<G, A>
+where
+    A::Instance: CanonicalDeserialize,
+{
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        deserialize_version(&mut reader)?;
+        let instance = Self {
+            folded: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+            folded_secondary: CanonicalDeserialize::deserialize_with_mode(
+                &mut reader,
+                compress,
+                validate,
+            )?,
+            latest_secondary: CanonicalDeserialize::deserialize_with_mode(
+                &mut reader,
+                compress,
+                validate,
+            )?,
+            z0: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+            output: Vec::deserialize_with_mode(&mut reader, compress, validate)?,
+            pc: usize::deserialize_with_mode(&mut reader, compress, validate)?,
+            i: usize::deserialize_with_mode(&mut reader, compress, validate)?,
+        };
+
+        if validate == Validate::Yes {
+            instance.check()?;
+        }
+
+        Ok(instance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cycle::pasta::{Fp, Fq, PallasVesta},
+        poseidon_constants,
+    };
+    use ark_ff::One;
+
+    #[test]
+    fn test_serialize_verifier_key() {
+        let key = VerifierKey::<PallasVesta> {
+            params: vec![Fq::one(), Fq::from(2u64)],
+            secondary_params: Fp::from(3u64),
+            oracle: poseidon_constants(),
+            secondary_oracle: poseidon_constants(),
+        };
+
+        let mut bytes = vec![];
+        key.serialize_compressed(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), key.compressed_size());
+        let key_new = VerifierKey::<PallasVesta>::deserialize_compressed(&bytes[..]).unwrap();
+        assert_eq!(key.params, key_new.params);
+        assert_eq!(key.secondary_params, key_new.secondary_params);
+        assert_eq!(key.oracle.ark, key_new.oracle.ark);
+        assert_eq!(key.oracle.mds, key_new.oracle.mds);
+
+        // Ensure an unknown version is rejected.
+        bytes[0] = SERIALIZATION_VERSION + 1;
+        assert!(matches!(
+            VerifierKey::<PallasVesta>::deserialize_compressed(&bytes[..]),
+            Err(SerializationError::InvalidData)
+        ));
+    }
+
+    #[test]
+    fn test_serialize_matrices() {
+        let shape = ConstraintMatrices::<Fq> {
+            num_instance_variables: 2,
+            num_witness_variables: 1,
+            num_constraints: 1,
+            a_num_non_zero: 1,
+            b_num_non_zero: 1,
+            c_num_non_zero: 2,
+            a: vec![vec![(Fq::one(), 1)]],
+            b: vec![vec![(Fq::one(), 1)]],
+            c: vec![vec![(Fq::one(), 0), (Fq::from(5u64), 2)]],
+        };
+
+        let mut bytes = vec![];
+        serialize_matrices(&shape, &mut bytes, Compress::Yes).unwrap();
+        assert_eq!(bytes.len(), matrices_serialized_size(&shape, Compress::Yes));
+        let shape_new = deserialize_matrices(&bytes[..], Compress::Yes, Validate::Yes).unwrap();
+        assert_eq!(shape, shape_new);
+
+        // Ensure out of range column indices are rejected.
+        let mut shape = shape;
+        shape.c[0][1].1 = 3;
+        let mut bytes = vec![];
+        serialize_matrices(&shape, &mut bytes, Compress::Yes).unwrap();
+        assert!(deserialize_matrices::<Fq, _>(&bytes[..], Compress::Yes, Validate::Yes).is_err());
+    }
+}